@@ -168,7 +168,7 @@ impl<'a, T> Cursor<'a, T> {
     ///
     /// This simple example showcases the retrival of the root value.
     pub fn value(&self) -> &T {
-        return self.tree.get_value(self.idx).unwrap();
+        self.tree.get_value(self.idx).unwrap()
     }
 
     /// Adds a new child to the current node but keeps the cursor at the current node.
@@ -193,7 +193,7 @@ impl<'a, T> Cursor<'a, T> {
     ///  2   3
     /// ```
     pub fn value_mut(&mut self) -> &mut T {
-        return self.tree.value_mut(self.idx).unwrap();
+        self.tree.value_mut(self.idx).unwrap()
     }
 
     /// Puts the given value in place of the current position of the cursor.
@@ -216,11 +216,11 @@ impl<'a, T> Cursor<'a, T> {
     /// ```
     pub fn children(&'a self) -> Vec<(&'a T, usize)> {
         let children_idx = self.tree.get_children(self.idx).unwrap();
-        return children_idx
+        children_idx
             .iter()
             .map(|idx| (self.tree.get_value(*idx), *idx))
             .filter_map(|(val, idx)| val.map(|x| (x, idx)).ok())
-            .collect();
+            .collect()
     }
 
     /// Destroys the cursor by consuming it and returns the index of the current node of the cursor.
@@ -1,5 +1,4 @@
 mod cursor;
-mod node;
 mod notation;
 pub(crate) mod tree;
 
@@ -60,11 +60,11 @@ impl<T> Tree<T> {
     }
 
     pub fn root_value(&self) -> &T {
-        return &self
+        &self
             .nodes
             .get(self.root)
             .expect("No root value found")
-            .value;
+            .value
     }
 
     /// Retrieves the index of the root node of the tree.
@@ -1,9 +1,13 @@
 mod color;
+mod path;
 mod position;
 mod rectangle;
 mod size;
+mod stroke;
 
 pub use color::Color;
+pub use path::{FillRule, Path, Point, Polyline, Segment, TOLERANCE};
 pub use position::Position;
 pub use rectangle::Rectangle;
 pub use size::Size;
+pub use stroke::{Cap, Join, StrokeStyle};
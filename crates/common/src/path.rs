@@ -0,0 +1,477 @@
+use std::ops::{Add, Mul, Sub};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Position, Rectangle};
+
+/// Maximum distance between a curve and its flattened polyline
+pub const TOLERANCE: f64 = 0.1;
+
+/// Magic number to approximate a quarter circle with a cubic bezier curve
+const KAPPA: f64 = 0.552_284_749_8;
+
+/// A point with sub-pixel precision.
+/// Pixel `(x, y)` covers the area from `(x, y)` to `(x + 1, y + 1)`.
+#[derive(Clone, PartialEq, Debug, Copy, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(from = "[f64; 2]", into = "[f64; 2]")
+)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+
+    /// Center of the pixel at the given position
+    pub fn center_of(pos: &Position) -> Self {
+        Point::new(pos.x as f64 + 0.5, pos.y as f64 + 0.5)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn distance_to(&self, other: &Point) -> f64 {
+        (*other - *self).length()
+    }
+
+    /// Vector of length 1 in the same direction, the zero vector stays as it is
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            *self
+        } else {
+            *self * (1.0 / length)
+        }
+    }
+
+    /// The vector rotated by 90 degrees
+    pub fn perpendicular(&self) -> Self {
+        Point::new(-self.y, self.x)
+    }
+
+    pub fn dot(&self, other: &Point) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn cross(&self, other: &Point) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn lerp(&self, other: &Point, t: f64) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Add<Point> for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Point) -> Self::Output {
+        Point::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub<Point> for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Point) -> Self::Output {
+        Point::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Point::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl From<Position> for Point {
+    fn from(value: Position) -> Self {
+        Point::new(value.x as f64, value.y as f64)
+    }
+}
+
+impl From<[f64; 2]> for Point {
+    fn from([x, y]: [f64; 2]) -> Self {
+        Point::new(x, y)
+    }
+}
+
+impl From<Point> for [f64; 2] {
+    fn from(value: Point) -> Self {
+        [value.x, value.y]
+    }
+}
+
+/// Rule deciding which areas enclosed by a path are inside
+#[derive(Clone, PartialEq, Debug, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum FillRule {
+    /// Inside if the contours wind around the area at least once in total
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "nonzero"))]
+    NonZero,
+
+    /// Inside if the area is enclosed by an odd number of contours
+    #[cfg_attr(feature = "serde", serde(rename = "evenodd"))]
+    EvenOdd,
+}
+
+impl FillRule {
+    /// Whether the given winding number lies inside
+    pub fn is_inside(&self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(tag = "type"))]
+pub enum Segment {
+    /// Starts a new sub path
+    #[cfg_attr(feature = "serde", serde(rename = "move"))]
+    Move { to: Point },
+
+    #[cfg_attr(feature = "serde", serde(rename = "line"))]
+    Line { to: Point },
+
+    /// Quadratic bezier curve
+    #[cfg_attr(feature = "serde", serde(rename = "quad"))]
+    Quad { control: Point, to: Point },
+
+    /// Cubic bezier curve
+    #[cfg_attr(feature = "serde", serde(rename = "cubic"))]
+    Cubic {
+        control1: Point,
+        control2: Point,
+        to: Point,
+    },
+
+    /// Closes the current sub path with a line to its start
+    #[cfg_attr(feature = "serde", serde(rename = "close"))]
+    Close,
+}
+
+/// Sequence of connected lines and bezier curves, possibly made of multiple sub paths
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(transparent))]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+/// A flattened sub path
+#[derive(Clone, PartialEq, Debug)]
+pub struct Polyline {
+    pub points: Vec<Point>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Path { segments: vec![] }
+    }
+
+    pub fn move_to(mut self, to: Point) -> Self {
+        self.segments.push(Segment::Move { to });
+        self
+    }
+
+    pub fn line_to(mut self, to: Point) -> Self {
+        self.segments.push(Segment::Line { to });
+        self
+    }
+
+    pub fn quad_to(mut self, control: Point, to: Point) -> Self {
+        self.segments.push(Segment::Quad { control, to });
+        self
+    }
+
+    pub fn cubic_to(mut self, control1: Point, control2: Point, to: Point) -> Self {
+        self.segments.push(Segment::Cubic {
+            control1,
+            control2,
+            to,
+        });
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.segments.push(Segment::Close);
+        self
+    }
+
+    /// Appends all sub paths of the other path
+    pub fn append(mut self, other: Path) -> Self {
+        self.segments.extend(other.segments);
+        self
+    }
+
+    /// Closed path through the given points, or open if `closed` is not set
+    pub fn polyline(points: &[Point], closed: bool) -> Self {
+        let mut path = Path::new();
+        for (i, point) in points.iter().enumerate() {
+            path = if i == 0 {
+                path.move_to(*point)
+            } else {
+                path.line_to(*point)
+            };
+        }
+        if closed && !points.is_empty() {
+            path = path.close();
+        }
+        path
+    }
+
+    pub fn rectangle(min: Point, max: Point) -> Self {
+        Path::polyline(
+            &[min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)],
+            true,
+        )
+    }
+
+    /// Rectangle with corners rounded by the radius, which is limited to half of the smaller side
+    pub fn rounded_rectangle(min: Point, max: Point, radius: f64) -> Self {
+        let r = radius
+            .min((max.x - min.x) / 2.0)
+            .min((max.y - min.y) / 2.0)
+            .max(0.0);
+        let k = r * (1.0 - KAPPA);
+        Path::new()
+            .move_to(Point::new(min.x + r, min.y))
+            .line_to(Point::new(max.x - r, min.y))
+            .cubic_to(
+                Point::new(max.x - k, min.y),
+                Point::new(max.x, min.y + k),
+                Point::new(max.x, min.y + r),
+            )
+            .line_to(Point::new(max.x, max.y - r))
+            .cubic_to(
+                Point::new(max.x, max.y - k),
+                Point::new(max.x - k, max.y),
+                Point::new(max.x - r, max.y),
+            )
+            .line_to(Point::new(min.x + r, max.y))
+            .cubic_to(
+                Point::new(min.x + k, max.y),
+                Point::new(min.x, max.y - k),
+                Point::new(min.x, max.y - r),
+            )
+            .line_to(Point::new(min.x, min.y + r))
+            .cubic_to(
+                Point::new(min.x, min.y + k),
+                Point::new(min.x + k, min.y),
+                Point::new(min.x + r, min.y),
+            )
+            .close()
+    }
+
+    pub fn ellipse(center: Point, rx: f64, ry: f64) -> Self {
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let Point { x, y } = center;
+        Path::new()
+            .move_to(Point::new(x + rx, y))
+            .cubic_to(
+                Point::new(x + rx, y + ky),
+                Point::new(x + kx, y + ry),
+                Point::new(x, y + ry),
+            )
+            .cubic_to(
+                Point::new(x - kx, y + ry),
+                Point::new(x - rx, y + ky),
+                Point::new(x - rx, y),
+            )
+            .cubic_to(
+                Point::new(x - rx, y - ky),
+                Point::new(x - kx, y - ry),
+                Point::new(x, y - ry),
+            )
+            .cubic_to(
+                Point::new(x + kx, y - ry),
+                Point::new(x + rx, y - ky),
+                Point::new(x + rx, y),
+            )
+            .close()
+    }
+
+    /// The path moved by the given offset
+    pub fn translate(&self, offset: Point) -> Self {
+        let mut path = self.clone();
+        for segment in path.segments.iter_mut() {
+            match segment {
+                Segment::Move { to } | Segment::Line { to } => *to = *to + offset,
+                Segment::Quad { control, to } => {
+                    *control = *control + offset;
+                    *to = *to + offset;
+                }
+                Segment::Cubic {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    *control1 = *control1 + offset;
+                    *control2 = *control2 + offset;
+                    *to = *to + offset;
+                }
+                Segment::Close => {}
+            }
+        }
+        path
+    }
+
+    /// Pixels touched by the path including its control points, empty for a path without points
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::covering(self.segments.iter().flat_map(|segment| match segment {
+            Segment::Move { to } | Segment::Line { to } => vec![*to],
+            Segment::Quad { control, to } => vec![*control, *to],
+            Segment::Cubic {
+                control1,
+                control2,
+                to,
+            } => vec![*control1, *control2, *to],
+            Segment::Close => vec![],
+        }))
+    }
+
+    /// Approximates the curves of each sub path with lines that deviate at most by `tolerance`
+    pub fn flatten(&self, tolerance: f64) -> Vec<Polyline> {
+        let mut result = vec![];
+        let mut points: Vec<Point> = vec![];
+        let mut last = Point::default();
+        for segment in &self.segments {
+            if points.is_empty() && !matches!(segment, Segment::Move { .. } | Segment::Close) {
+                points.push(last);
+            }
+            match segment {
+                Segment::Move { to } => {
+                    if !points.is_empty() {
+                        result.push(Polyline {
+                            points: std::mem::take(&mut points),
+                            closed: false,
+                        });
+                    }
+                    points.push(*to);
+                    last = *to;
+                }
+                Segment::Line { to } => {
+                    points.push(*to);
+                    last = *to;
+                }
+                Segment::Quad { control, to } => {
+                    let steps = subdivisions((last - *control * 2.0 + *to).length(), tolerance);
+                    let from = last;
+                    points.extend((1..=steps).map(|i| {
+                        let t = i as f64 / steps as f64;
+                        from.lerp(control, t).lerp(&control.lerp(to, t), t)
+                    }));
+                    last = *to;
+                }
+                Segment::Cubic {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    let deviation = (last - *control1 * 2.0 + *control2)
+                        .length()
+                        .max((*control1 - *control2 * 2.0 + *to).length());
+                    let steps = subdivisions(deviation * 1.5, tolerance);
+                    let from = last;
+                    points.extend((1..=steps).map(|i| {
+                        let t = i as f64 / steps as f64;
+                        let (a, b, c) = (
+                            from.lerp(control1, t),
+                            control1.lerp(control2, t),
+                            control2.lerp(to, t),
+                        );
+                        a.lerp(&b, t).lerp(&b.lerp(&c, t), t)
+                    }));
+                    last = *to;
+                }
+                Segment::Close => {
+                    // the next sub path continues at the start of the closed one
+                    if let Some(start) = points.first() {
+                        last = *start;
+                        result.push(Polyline {
+                            points: std::mem::take(&mut points),
+                            closed: true,
+                        });
+                    }
+                }
+            }
+        }
+        if !points.is_empty() {
+            result.push(Polyline {
+                points,
+                closed: false,
+            });
+        }
+        result
+    }
+}
+
+/// Number of lines for a curve whose control polygon bends by `deviation`
+fn subdivisions(deviation: f64, tolerance: f64) -> usize {
+    ((deviation / (4.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 1000)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Rectangle;
+
+    use super::{Path, Point, TOLERANCE};
+
+    #[test]
+    fn flatten_sub_paths() {
+        let path = Path::rectangle(Point::new(0.0, 0.0), Point::new(4.0, 2.0))
+            .line_to(Point::new(8.0, 8.0))
+            .move_to(Point::new(10.0, 10.0))
+            .quad_to(Point::new(15.0, 20.0), Point::new(20.0, 10.0));
+        let polylines = path.flatten(TOLERANCE);
+        assert_eq!(polylines.len(), 3);
+        assert!(polylines[0].closed);
+        assert_eq!(polylines[0].points.len(), 4);
+        // continues at the start of the closed sub path
+        assert_eq!(
+            polylines[1].points,
+            vec![Point::new(0.0, 0.0), Point::new(8.0, 8.0)]
+        );
+        assert!(!polylines[2].closed);
+        assert_eq!(polylines[2].points.last(), Some(&Point::new(20.0, 10.0)));
+    }
+
+    #[test]
+    fn flattened_ellipse_is_accurate() {
+        let center = Point::new(50.0, 50.0);
+        let path = Path::ellipse(center, 40.0, 40.0);
+        let polylines = path.flatten(TOLERANCE);
+        for point in &polylines[0].points {
+            assert!((point.distance_to(&center) - 40.0).abs() < 0.1);
+        }
+        assert_eq!(path.bounds(), Rectangle::new(10, 10, 80, 80));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let json = r#"[{"type":"move","to":[1.0,2.0]},{"type":"quad","control":[3.0,4.0],"to":[5.5,6.0]},{"type":"close"}]"#;
+        let path: Path = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            path,
+            Path::new()
+                .move_to(Point::new(1.0, 2.0))
+                .quad_to(Point::new(3.0, 4.0), Point::new(5.5, 6.0))
+                .close()
+        );
+        assert_eq!(serde_json::to_string(&path).unwrap(), json);
+    }
+}
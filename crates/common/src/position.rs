@@ -82,6 +82,30 @@ impl Position {
         result.push(*b);
         result
     }
+
+    /// All pixels of the Bresenham line from `a` to `b`, including both ends, in the order from `a` to `b`.
+    pub fn bresenham(a: &Position, b: &Position) -> Vec<Position> {
+        let dx = i32::abs(b.x - a.x);
+        let dy = -i32::abs(b.y - a.y);
+        let step_x = if a.x < b.x { 1 } else { -1 };
+        let step_y = if a.y < b.y { 1 } else { -1 };
+        let mut error = dx + dy;
+        let mut current = *a;
+        let mut result = vec![current];
+        while current != *b {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                current.x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                current.y += step_y;
+            }
+            result.push(current);
+        }
+        result
+    }
 }
 
 impl Display for Position {
@@ -202,4 +226,15 @@ mod test {
             vec![(2, 0).into(), (3, 1).into(), (4, 1).into(), (5, 2).into()]
         );
     }
+
+    #[test]
+    fn bresenham() {
+        let line = Position::bresenham(&(5, 2).into(), &(2, 0).into());
+        assert_eq!(
+            line,
+            vec![(5, 2).into(), (4, 1).into(), (3, 1).into(), (2, 0).into()]
+        );
+        let line = Position::bresenham(&(1, 1).into(), &(1, 1).into());
+        assert_eq!(line, vec![(1, 1).into()]);
+    }
 }
@@ -3,7 +3,7 @@ use std::{
     ops::{Add, Sub},
 };
 
-use crate::{Position, Size};
+use crate::{Point, Position, Size};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Rectangle {
@@ -34,8 +34,9 @@ impl Rectangle {
             a.position.y + a.size.height as i32,
             b.position.y + b.size.height as i32,
         );
-        let width = (lower_x - upper_x) as u32;
-        let height = (lower_y - upper_y) as u32;
+        // disjoint rectangles intersect in an empty rectangle
+        let width = (lower_x - upper_x).max(0) as u32;
+        let height = (lower_y - upper_y).max(0) as u32;
 
         Rectangle {
             size: Size::new(width, height),
@@ -87,6 +88,25 @@ impl Rectangle {
             .reduce(|a, b| Rectangle::bounding(&a, &b))
             .unwrap_or((0, 0, 0, 0).into())
     }
+
+    /// Pixels touched by the points, empty if there are none
+    pub fn covering(points: impl Iterator<Item = Point>) -> Self {
+        let mut points = points.peekable();
+        if points.peek().is_none() {
+            return Rectangle::new(0, 0, 0, 0);
+        }
+        let (mut x0, mut y0) = (f64::MAX, f64::MAX);
+        let (mut x1, mut y1) = (f64::MIN, f64::MIN);
+        for p in points {
+            x0 = x0.min(p.x);
+            y0 = y0.min(p.y);
+            x1 = x1.max(p.x);
+            y1 = y1.max(p.y);
+        }
+        let (x0, y0) = (x0.floor() as i32, y0.floor() as i32);
+        let (x1, y1) = (x1.ceil() as i32, y1.ceil() as i32);
+        Rectangle::new(x0, y0, (x1 - x0) as u32, (y1 - y0) as u32)
+    }
 }
 
 impl Add<&Position> for &Rectangle {
@@ -164,6 +184,10 @@ mod test {
         let a: Rectangle = (0, 1, 2, 2).into();
         let b: Rectangle = (0, 0, 2, 2).into();
         assert_eq!(Rectangle::intersect(&a, &b), (0, 1, 2, 1).into());
+
+        let a: Rectangle = (0, 0, 2, 2).into();
+        let b: Rectangle = (5, 5, 2, 2).into();
+        assert_eq!(Rectangle::intersect(&a, &b), (5, 5, 0, 0).into());
     }
 
     #[test]
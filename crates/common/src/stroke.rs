@@ -0,0 +1,385 @@
+use std::f64::consts::PI;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::path::{Path, Point, Polyline, TOLERANCE};
+
+/// Shape of the corners where two lines of a stroke meet
+#[derive(Clone, PartialEq, Debug, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum Join {
+    /// Sharp corner, beveled once it would be longer than the miter limit
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "miter"))]
+    Miter,
+
+    #[cfg_attr(feature = "serde", serde(rename = "round"))]
+    Round,
+
+    #[cfg_attr(feature = "serde", serde(rename = "bevel"))]
+    Bevel,
+}
+
+/// Shape of the ends of open strokes and dashes
+#[derive(Clone, PartialEq, Debug, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum Cap {
+    /// Ends exactly at the end point
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "butt"))]
+    Butt,
+
+    #[cfg_attr(feature = "serde", serde(rename = "round"))]
+    Round,
+
+    /// Extends beyond the end point by half the width
+    #[cfg_attr(feature = "serde", serde(rename = "square"))]
+    Square,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(default))]
+pub struct StrokeStyle {
+    pub width: f64,
+    pub join: Join,
+    pub cap: Cap,
+    /// Maximum ratio of the miter length to the stroke width
+    pub miter_limit: f64,
+    /// Alternating lengths of dashes and gaps, a solid stroke if empty
+    pub dash: Vec<f64>,
+    /// Distance into the dash pattern at which the stroke starts
+    pub dash_offset: f64,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle::new(1.0, Join::default(), Cap::default())
+    }
+}
+
+impl StrokeStyle {
+    /// Solid stroke of the given width
+    pub fn new(width: f64, join: Join, cap: Cap) -> Self {
+        StrokeStyle {
+            width,
+            join,
+            cap,
+            miter_limit: 4.0,
+            dash: vec![],
+            dash_offset: 0.0,
+        }
+    }
+
+    /// Whether the stroke is dashed, patterns without any length or with negative lengths are solid
+    fn is_dashed(&self) -> bool {
+        self.dash.iter().any(|d| *d > 0.0) && self.dash.iter().all(|d| *d >= 0.0)
+    }
+
+    /// Approximate number of dashes the stroke of the path is split into, 0 for solid strokes
+    pub fn dash_count(&self, path: &Path) -> f64 {
+        if !self.is_dashed() {
+            return 0.0;
+        }
+        let length: f64 = path
+            .flatten(TOLERANCE)
+            .iter()
+            .map(|polyline| {
+                let mut points = polyline.points.clone();
+                if polyline.closed {
+                    points.extend(points.first().copied());
+                }
+                points
+                    .windows(2)
+                    .map(|w| w[0].distance_to(&w[1]))
+                    .sum::<f64>()
+            })
+            .sum();
+        // an odd number of entries is repeated, so every entry adds half a dash per period
+        let period: f64 = self.dash.iter().sum();
+        (length / period + 1.0) * self.dash.len() as f64 / 2.0
+    }
+}
+
+impl Path {
+    /// Contours covering the stroke of the path. Every contour runs in the same direction,
+    /// so the stroke is their union under the nonzero winding rule.
+    pub fn stroke(&self, style: &StrokeStyle) -> Vec<Vec<Point>> {
+        let mut result = vec![];
+        if style.width <= 0.0 {
+            return result;
+        }
+        for polyline in self.flatten(TOLERANCE) {
+            let mut points = polyline.points;
+            points.dedup();
+            if polyline.closed && points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            let polyline = Polyline {
+                points,
+                closed: polyline.closed,
+            };
+            if style.is_dashed() {
+                for dash in dashes(&polyline, &style.dash, style.dash_offset) {
+                    stroke_polyline(&dash, style, &mut result);
+                }
+            } else {
+                stroke_polyline(&polyline, style, &mut result);
+            }
+        }
+        result
+    }
+}
+
+/// Splits the polyline into the open pieces that are visible according to the dash pattern
+fn dashes(polyline: &Polyline, pattern: &[f64], offset: f64) -> Vec<Polyline> {
+    // an odd number of entries repeats the pattern, such that dashes and gaps alternate
+    let pattern: Vec<f64> = if pattern.len() % 2 == 1 {
+        pattern.iter().chain(pattern.iter()).copied().collect()
+    } else {
+        pattern.to_vec()
+    };
+    let total: f64 = pattern.iter().sum();
+    let mut points = polyline.points.clone();
+    if polyline.closed {
+        points.extend(points.first().copied());
+    }
+
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let mut offset = offset.rem_euclid(total);
+    while offset > 0.0 {
+        let step = offset.min(remaining);
+        offset -= step;
+        remaining -= step;
+        if remaining <= 0.0 {
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+    }
+
+    let mut result = vec![];
+    let mut current = vec![];
+    if index % 2 == 0 {
+        current.extend(points.first().copied());
+    }
+    for (a, b) in points.iter().zip(points.iter().skip(1)) {
+        let length = a.distance_to(b);
+        let mut position = 0.0;
+        while length - position > remaining {
+            position += remaining;
+            let point = a.lerp(b, position / length);
+            if index % 2 == 0 {
+                current.push(point);
+                result.push(Polyline {
+                    points: std::mem::take(&mut current),
+                    closed: false,
+                });
+            } else {
+                current.push(point);
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - position;
+        if index % 2 == 0 {
+            current.push(*b);
+        }
+    }
+    if index % 2 == 0 && !current.is_empty() {
+        result.push(Polyline {
+            points: current,
+            closed: false,
+        });
+    }
+    result
+}
+
+fn stroke_polyline(polyline: &Polyline, style: &StrokeStyle, result: &mut Vec<Vec<Point>>) {
+    let half = style.width / 2.0;
+    let points = &polyline.points;
+    let closed = polyline.closed && points.len() > 2;
+    if points.len() == 1 || points.windows(2).all(|w| w[0] == w[1]) {
+        // a dot is only visible with caps
+        let p = points[0];
+        match style.cap {
+            Cap::Butt => {}
+            Cap::Round => result.push(circle(p, half)),
+            Cap::Square => result.push(vec![
+                p + Point::new(-half, -half),
+                p + Point::new(half, -half),
+                p + Point::new(half, half),
+                p + Point::new(-half, half),
+            ]),
+        }
+        return;
+    }
+
+    let closing = points.last().zip(points.first()).filter(|_| closed);
+    let segments: Vec<(Point, Point)> = points
+        .iter()
+        .zip(points.iter().skip(1))
+        .chain(closing)
+        .filter(|(a, b)| a != b)
+        .map(|(a, b)| (*a, *b))
+        .collect();
+    for (a, b) in &segments {
+        let normal = (*b - *a).normalized().perpendicular() * half;
+        result.push(oriented(vec![
+            *a + normal,
+            *b + normal,
+            *b - normal,
+            *a - normal,
+        ]));
+    }
+
+    let joints = segments.iter().zip(segments.iter().skip(1));
+    let closing_joint = segments.last().zip(segments.first()).filter(|_| closed);
+    for ((a, b), (_, c)) in joints.chain(closing_joint) {
+        join(*a, *b, *c, style, result);
+    }
+
+    if !closed {
+        let (first, last) = (segments[0], segments[segments.len() - 1]);
+        cap(first.1, first.0, style, result);
+        cap(last.0, last.1, style, result);
+    }
+}
+
+/// Adds the join at the corner `b` of the lines from `a` to `b` and `b` to `c`
+fn join(a: Point, b: Point, c: Point, style: &StrokeStyle, result: &mut Vec<Vec<Point>>) {
+    let half = style.width / 2.0;
+    let (incoming, outgoing) = ((b - a).normalized(), (c - b).normalized());
+    let turn = incoming.cross(&outgoing);
+    if turn.abs() < 1e-9 && incoming.dot(&outgoing) > 0.0 {
+        return;
+    }
+    // the gap to fill opens on the outer side of the turn
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let (u1, u2) = (
+        incoming.perpendicular() * side,
+        outgoing.perpendicular() * side,
+    );
+    let (p1, p2) = (b + u1 * half, b + u2 * half);
+    match style.join {
+        Join::Round => result.push(circle(b, half)),
+        Join::Bevel => result.push(oriented(vec![b, p1, p2])),
+        Join::Miter => {
+            let bisector = (u1 + u2).normalized();
+            let cos = bisector.dot(&u1);
+            if cos > 1e-9 && 1.0 / cos <= style.miter_limit {
+                result.push(oriented(vec![b, p1, b + bisector * (half / cos), p2]));
+            } else {
+                result.push(oriented(vec![b, p1, p2]));
+            }
+        }
+    }
+}
+
+/// Adds the cap at the `end` of the line coming from `from`
+fn cap(from: Point, end: Point, style: &StrokeStyle, result: &mut Vec<Vec<Point>>) {
+    let half = style.width / 2.0;
+    let direction = (end - from).normalized() * half;
+    let normal = direction.perpendicular();
+    match style.cap {
+        Cap::Butt => {}
+        Cap::Round => result.push(circle(end, half)),
+        Cap::Square => result.push(oriented(vec![
+            end + normal,
+            end + normal + direction,
+            end - normal + direction,
+            end - normal,
+        ])),
+    }
+}
+
+fn circle(center: Point, radius: f64) -> Vec<Point> {
+    // segments short enough that the deviation from the exact circle stays below the tolerance
+    let segments = (PI / (1.0 - TOLERANCE / radius).max(-1.0).acos())
+        .ceil()
+        .clamp(8.0, 1000.0) as usize;
+    (0..segments)
+        .map(|i| 2.0 * PI * i as f64 / segments as f64)
+        .map(|angle| center + Point::new(angle.cos(), angle.sin()) * radius)
+        .collect()
+}
+
+/// The contour running in positive direction, such that overlapping contours add up
+fn oriented(mut contour: Vec<Point>) -> Vec<Point> {
+    let area: f64 = contour
+        .iter()
+        .zip(contour.iter().cycle().skip(1))
+        .map(|(a, b)| a.cross(b))
+        .sum();
+    if area < 0.0 {
+        contour.reverse();
+    }
+    contour
+}
+
+#[cfg(test)]
+mod test {
+    use crate::path::{Path, Point};
+
+    use super::{Cap, Join, StrokeStyle};
+
+    fn line() -> Path {
+        Path::new()
+            .move_to(Point::new(0.0, 0.0))
+            .line_to(Point::new(10.0, 0.0))
+    }
+
+    #[test]
+    fn caps() {
+        let butt = line().stroke(&StrokeStyle::new(2.0, Join::Miter, Cap::Butt));
+        assert_eq!(butt.len(), 1);
+        let square = line().stroke(&StrokeStyle::new(2.0, Join::Miter, Cap::Square));
+        let min_x = square.iter().flatten().map(|p| p.x).fold(0.0, f64::min);
+        assert_eq!(min_x, -1.0);
+    }
+
+    #[test]
+    fn miter_limit() {
+        let corner = Path::polyline(
+            &[
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+            ],
+            false,
+        );
+        let mut style = StrokeStyle::new(2.0, Join::Miter, Cap::Butt);
+        let miter = corner.stroke(&style);
+        let tip = Point::new(11.0, -1.0);
+        assert!(miter[2].iter().any(|p| p.distance_to(&tip) < 1e-9));
+        style.miter_limit = 1.0;
+        let bevel = corner.stroke(&style);
+        assert_eq!(bevel[2].len(), 3);
+    }
+
+    #[test]
+    fn dashes() {
+        let mut style = StrokeStyle::new(2.0, Join::Miter, Cap::Butt);
+        style.dash = vec![3.0, 2.0];
+        style.dash_offset = 1.0;
+        // dashes from 0 to 2, 4 to 7 and 9 to 10
+        let dashed = line().stroke(&style);
+        assert_eq!(dashed.len(), 3);
+        let xs: Vec<(f64, f64)> = dashed
+            .iter()
+            .map(|c| {
+                let xs = c.iter().map(|p| p.x);
+                (
+                    xs.clone().fold(f64::MAX, f64::min),
+                    xs.fold(f64::MIN, f64::max),
+                )
+            })
+            .collect();
+        assert_eq!(xs, vec![(0.0, 2.0), (4.0, 7.0), (9.0, 10.0)]);
+        assert_eq!(style.dash_count(&line()), 3.0);
+        style.dash = vec![1e-9];
+        assert!(style.dash_count(&line()) > 1e9);
+        style.dash = vec![0.0, -1.0];
+        assert_eq!(style.dash_count(&line()), 0.0);
+    }
+}
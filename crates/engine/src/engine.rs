@@ -4,7 +4,9 @@ use baum::{Cursor, Tree};
 use common::{Position, Size};
 #[cfg(feature = "wasm")]
 use imagine::WebGlBlender;
-use imagine::{generate_blender, Blender, Image, SoftwareBlender};
+use imagine::{
+    generate_blender, Blender, Compositing, Depth, Image, Mask, SoftwareBlender, WorkingSpace,
+};
 use serde::Serialize;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -13,7 +15,7 @@ use crate::{
     error::EngineError,
     layer::{Layer, LayerFlag},
     moment::{Meta, Moment},
-    step::Step,
+    step::{PendingPencil, PendingTransform, Step},
     utils,
 };
 
@@ -39,6 +41,10 @@ pub struct Engine {
     /// The content as it is created when wandering from the root to the current point in history
     pub(crate) content: Tree<Layer>,
 
+    /// Mask (in global coordinates) that drawing and effects are limited to, everything may be changed if there is none
+    #[serde(skip)]
+    pub(crate) selection: Option<Mask>,
+
     /// Context (needed for rendering multiform api calls)
     pub(crate) context: EngineContext,
 
@@ -50,15 +56,49 @@ pub struct Engine {
 #[derive(Serialize)]
 pub struct EngineContext {
     pub(crate) images: HashMap<String, Image>,
+    pub(crate) fonts: HashMap<String, Vec<u8>>,
     pub(crate) pending_step: Option<Step>,
+    /// Original bounds and grabbed handle of a layer transformation in progress
+    #[serde(skip)]
+    pub(crate) transform: Option<PendingTransform>,
+    /// Most recently drawn pixels of a pencil stroke in progress
+    #[serde(skip)]
+    pub(crate) pencil: Option<PendingPencil>,
     pub(crate) idx: Option<usize>,
+    /// 8-bit copy of the content for displaying documents of a higher depth
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    pub(crate) display: Option<Image>,
 }
 
 impl Engine {
     pub fn new(width: u32, height: u32) -> Engine {
+        Self::with_settings(
+            width,
+            height,
+            Compositing::default(),
+            Depth::default(),
+            WorkingSpace::default(),
+        )
+    }
+
+    /// Creates a session that composites its layers in the given color space, keeps them in the given depth
+    /// and converts imported images into the given working space
+    pub fn with_settings(
+        width: u32,
+        height: u32,
+        compositing: Compositing,
+        depth: Depth,
+        working_space: WorkingSpace,
+    ) -> Engine {
         log::info!("Initializing session");
         let size = Size { width, height };
-        let step = Step::ProjectCreate { size };
+        let step = Step::ProjectCreate {
+            size,
+            compositing,
+            depth,
+            working_space,
+        };
         let init_moment = Moment {
             meta: Meta {
                 timestamp: 87,
@@ -66,14 +106,18 @@ impl Engine {
             },
             data: step,
         };
-        let mut root_layer = Layer::default(width, height);
-        root_layer.flag = LayerFlag::Root;
+        let root_layer = Layer::root(width, height, depth);
         let context = EngineContext {
             images: HashMap::new(),
+            fonts: HashMap::new(),
             pending_step: None,
+            transform: None,
+            pencil: None,
             idx: None,
+            display: None,
         };
-        let blender = generate_blender();
+        let mut blender = generate_blender();
+        blender.set_compositing(compositing);
         Engine {
             name: "default".to_string(),
             version: "v1".to_string(),
@@ -81,6 +125,7 @@ impl Engine {
             history: Tree::new(init_moment),
             redo_stack: vec![],
             current: 0,
+            selection: None,
             context,
             blender,
         }
@@ -95,16 +140,47 @@ impl Engine {
             })?),
             _ => panic!("No such blender"),
         };
+        self.blender.set_compositing(self.compositing());
         log::info!("Changed blender to '{}'", value);
         Ok(())
     }
 
+    /// Color space the layers are composited in, as set when creating the project
+    pub fn compositing(&self) -> Compositing {
+        match self.history.root_value().data {
+            Step::ProjectCreate { compositing, .. } => compositing,
+            _ => Compositing::default(),
+        }
+    }
+
+    /// Depth of the layers, as set when creating the project
+    pub fn depth(&self) -> Depth {
+        match self.history.root_value().data {
+            Step::ProjectCreate { depth, .. } => depth,
+            _ => Depth::default(),
+        }
+    }
+
+    /// Color space of imported images and exports, as set when creating the project
+    pub fn working_space(&self) -> WorkingSpace {
+        match self.history.root_value().data {
+            Step::ProjectCreate { working_space, .. } => working_space,
+            _ => WorkingSpace::default(),
+        }
+    }
+
+    pub fn content_as_tiff_bytes(&self) -> Vec<u8> {
+        let img = &self.content.root_value().img;
+        img.into_tiff_bytes_in(self.working_space())
+    }
+
     pub fn content_as_base64(&self) -> String {
-        return self.content.root_value().img.encode_base64().unwrap();
+        self.content.root_value().img.encode_base64().unwrap()
     }
 
     pub fn content_as_png_bytes(&self) -> Vec<u8> {
-        return self.content.root_value().img.into_png_bytes();
+        let img = &self.content.root_value().img;
+        img.into_png_bytes_in(self.working_space())
     }
 
     pub fn size(&self) -> Size {
@@ -120,22 +196,39 @@ impl Engine {
     }
 
     pub fn bytes(&self) -> Vec<u8> {
-        return self.content.root_value().img.into_bytes();
+        self.content.root_value().img.into_bytes()
     }
 
     pub fn reconstruct(
         steps: &[Step],
-        context: HashMap<String, Image>,
+        images: HashMap<String, Image>,
+        fonts: HashMap<String, Vec<u8>>,
     ) -> Result<Engine, EngineError> {
         let first = steps
             .first()
             .ok_or(EngineError::user_error("No step provided"))?;
-        if let Step::ProjectCreate { size } = first {
-            let mut result = Engine::new(size.width, size.height);
+        if let Step::ProjectCreate {
+            size,
+            compositing,
+            depth,
+            working_space,
+        } = first
+        {
+            let mut result = Engine::with_settings(
+                size.width,
+                size.height,
+                *compositing,
+                *depth,
+                *working_space,
+            );
             let context = EngineContext {
-                images: context,
+                images,
+                fonts,
                 pending_step: None,
+                transform: None,
+                pencil: None,
                 idx: None,
+                display: None,
             };
             result.context = context;
             for step in steps.iter().skip(1) {
@@ -153,6 +246,11 @@ impl Engine {
         self.context.images.insert(lkj, l);
     }
 
+    /// Makes the font file available to text layers under the given name
+    pub fn set_context_font(&mut self, name: String, font: Vec<u8>) {
+        self.context.fonts.insert(name, font);
+    }
+
     pub fn perform(&mut self, step: &Step) -> Result<Option<usize>, EngineError> {
         if log::log_enabled!(log::Level::Debug) {
             log::debug!("Performing: {}", log::as_serde!(&step));
@@ -189,7 +287,7 @@ impl Engine {
                 .content
                 .get_value(idx)
                 .expect("Internal issues with layer traversal");
-            if a.flag == LayerFlag::Pixel && a.is_hit(&pos) {
+            if a.flag.is_leaf() && a.is_hit(&pos) {
                 return Some(idx);
             }
         }
@@ -203,9 +301,8 @@ impl Engine {
             ));
         }
         self.redo_stack.push(self.current);
-        let parent_idx = self.history.get_parent(self.current).map_err(|e| {
+        let parent_idx = self.history.get_parent(self.current).inspect_err(|_| {
             self.redo_stack.pop();
-            e
         })?;
         log::debug!("current {} -> {}", self.current, parent_idx);
         self.current = parent_idx;
@@ -223,10 +320,9 @@ impl Engine {
                 let cursor = Cursor::new(&mut self.history, idx)?;
                 &cursor.value().data.clone()
             };
-            if let Step::ProjectCreate { size } = step {
-                let mut root_layer = Layer::default(size.width, size.height);
-                root_layer.flag = LayerFlag::Root;
-                self.content = Tree::new(root_layer);
+            if let Step::ProjectCreate { size, depth, .. } = step {
+                self.content = Tree::new(Layer::root(size.width, size.height, *depth));
+                self.selection = None;
                 initalized = true;
             } else {
                 if !initalized {
@@ -363,6 +459,11 @@ impl Engine {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use common::Color;
+    use imagine::{Compositing, Depth, Image, WorkingSpace};
+
     use crate::{error::EngineError, step::Step};
 
     use super::Engine;
@@ -434,4 +535,103 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn compositing_is_kept_on_replay() -> Result<(), EngineError> {
+        let steps: Vec<Step> = [
+            r#"{ "type": "project/create", "size": [4, 4], "compositing": "linear" }"#,
+            r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+                "position": null, "color": "#00ff00ff", "name": null }"##,
+            r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+                "position": null, "color": "#ff0000ff", "name": null }"##,
+            r#"{ "type": "layer/attr", "id": 2, "alpha": 0.5 }"#,
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+        let mut state = Engine::reconstruct(&steps, HashMap::new(), HashMap::new())?;
+        assert_eq!(state.compositing(), Compositing::Linear);
+        let linear = state.content.root_value().img.pixel(0, 0);
+        assert!(linear.r > 180 && linear.g > 180);
+
+        state.undo()?;
+        state.redo()?;
+        assert_eq!(state.content.root_value().img.pixel(0, 0), linear);
+        state.switch_blender("Software")?;
+        state.perform(&steps[3])?;
+        assert_eq!(state.content.root_value().img.pixel(0, 0), linear);
+
+        // older sessions without compositing keep blending in sRGB
+        let create = r#"{ "type": "project/create", "size": [4, 4] }"#;
+        let mut steps = steps;
+        steps[0] = serde_json::from_str(create).unwrap();
+        let state = Engine::reconstruct(&steps, HashMap::new(), HashMap::new())?;
+        assert_eq!(state.compositing(), Compositing::Srgb);
+        let srgb = state.content.root_value().img.pixel(0, 0);
+        assert!(srgb.r <= 128 && srgb.g <= 128);
+        Ok(())
+    }
+
+    #[test]
+    fn layers_keep_the_depth_of_the_document() -> Result<(), EngineError> {
+        let create = r#"{ "type": "project/create", "size": [4, 4], "depth": "u16" }"#;
+        let steps: Vec<Step> = [
+            create,
+            r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+                "position": null, "color": "#000000ff", "name": null }"##,
+            r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+                "position": null, "color": "#010101ff", "name": null }"##,
+            r#"{ "type": "layer/attr", "id": 2, "alpha": 0.5 }"#,
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+        let mut state = Engine::reconstruct(&steps, HashMap::new(), HashMap::new())?;
+        assert_eq!(state.depth(), Depth::Sixteen);
+        assert_eq!(state.content.get_value(2)?.img.depth(), Depth::Sixteen);
+        // half of the smallest 8-bit step survives the blending
+        let root = &state.content.root_value().img;
+        assert_eq!(root.depth(), Depth::Sixteen);
+        assert!((root.get(0, 0)[0] * 65535.0 - 128.5).abs() <= 0.5);
+        assert_eq!(
+            Image::from_bytes(&state.content_as_png_bytes())
+                .unwrap()
+                .depth(),
+            Depth::Sixteen
+        );
+
+        state.undo()?;
+        state.undo()?;
+        assert_eq!(state.content.root_value().img.depth(), Depth::Sixteen);
+        Ok(())
+    }
+
+    #[test]
+    fn imports_are_converted_into_the_working_space() -> Result<(), EngineError> {
+        let red = Image::new_from_color(2, 2, &Color::RED)
+            .encode_base64()
+            .unwrap();
+        let steps: Vec<Step> = [
+            r#"{ "type": "project/create", "size": [2, 2], "working_space": "display_p3" }"#
+                .to_string(),
+            format!(
+                r#"{{ "type": "layer/create/from_data", "parent": 0,
+                "img": {{ "src": "encode/png", "data": "{}" }} }}"#,
+                red
+            ),
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+        let state = Engine::reconstruct(&steps, HashMap::new(), HashMap::new())?;
+        assert_eq!(state.working_space(), WorkingSpace::DisplayP3);
+        let red_in_p3 = state.content.get_value(1)?.img.pixel(0, 0);
+        assert!(red_in_p3.r < 240 && red_in_p3.g > 40);
+
+        // the export carries the profile, so it shows the same red elsewhere
+        let exported = Image::from_bytes(&state.content_as_png_bytes()).unwrap();
+        let back = exported.pixel(0, 0);
+        assert!(back.r >= 254 && back.g <= 1 && back.b <= 1);
+        Ok(())
+    }
 }
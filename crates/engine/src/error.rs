@@ -72,8 +72,11 @@ impl From<TreeError> for EngineError {
 }
 
 impl From<DtoTransformError> for EngineError {
-    fn from(_: DtoTransformError) -> Self {
-        EngineError::user_error("Bad image DTO")
+    fn from(value: DtoTransformError) -> Self {
+        match value {
+            DtoTransformError::InvalidFont => EngineError::user_error("Invalid font"),
+            _ => EngineError::user_error("Bad image DTO"),
+        }
     }
 }
 
@@ -1,13 +1,39 @@
+use std::collections::HashMap;
+
 use common::{Position, Rectangle};
-use imagine::{BlendMode, Image};
+use imagine::{
+    BlendMode, ColorBalance, Curves, Depth, FontDto, GrayscaleMethod, Image, Levels, Mask,
+    TextStyle,
+};
 use serde::{ser::SerializeStruct, Deserialize, Serialize};
 
+use crate::EngineError;
+
+/// How the children of a group are composited
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum GroupMode {
+    /// The children are blended into the image of the group, which is then blended as a unit
+    #[default]
+    #[serde(rename = "isolated")]
+    Isolated,
+
+    /// The children blend directly with what lies beneath the group
+    #[serde(rename = "pass_through")]
+    PassThrough,
+}
+
 /// Describes all the meta data of a layer
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct LayerAttributes {
     pub pos: Position,
     pub mode: BlendMode,
     pub alpha: f32,
+    /// Only shows up where the nearest non-clipped sibling below covers
+    #[serde(default)]
+    pub clipped: bool,
+    /// Only of interest for groups
+    #[serde(default)]
+    pub group_mode: GroupMode,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -16,7 +42,168 @@ pub enum LayerFlag {
     Root,
     Group,
     Pixel,
-    Text(String),
+    Text(Text),
+    Adjustment(Adjustment),
+}
+
+impl LayerFlag {
+    /// Whether the layer holds content of its own instead of combining the content of its children
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, LayerFlag::Pixel | LayerFlag::Text(_))
+    }
+
+    /// Whether the layer combines the content of its children
+    pub fn is_group(&self) -> bool {
+        matches!(self, LayerFlag::Root | LayerFlag::Group)
+    }
+}
+
+/// Effect of an adjustment layer, applied to the composite of the layers beneath it within its group
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "effect")]
+pub enum Adjustment {
+    #[serde(rename = "color/grayscale")]
+    ColorGrayscale {
+        #[serde(default)]
+        method: GrayscaleMethod,
+    },
+
+    #[serde(rename = "color/brightness_contrast")]
+    ColorBrightnessContrast {
+        #[serde(default)]
+        brightness: f64,
+        #[serde(default)]
+        contrast: f64,
+    },
+
+    #[serde(rename = "color/levels")]
+    ColorLevels(Levels),
+
+    #[serde(rename = "color/curves")]
+    ColorCurves(Curves),
+
+    #[serde(rename = "color/hue_saturation")]
+    ColorHueSaturation {
+        #[serde(default)]
+        hue: f64,
+        #[serde(default)]
+        saturation: f64,
+        #[serde(default)]
+        lightness: f64,
+    },
+
+    #[serde(rename = "color/balance")]
+    ColorBalance(ColorBalance),
+
+    #[serde(rename = "color/invert")]
+    ColorInvert,
+
+    #[serde(rename = "color/posterize")]
+    ColorPosterize { levels: u32 },
+
+    #[serde(rename = "color/threshold")]
+    ColorThreshold { level: f64 },
+
+    #[serde(rename = "color/vibrance")]
+    ColorVibrance { amount: f64 },
+
+    #[serde(rename = "color/exposure")]
+    ColorExposure {
+        exposure: f64,
+        #[serde(default)]
+        offset: f64,
+        #[serde(default = "Adjustment::neutral_gamma")]
+        gamma: f64,
+    },
+
+    #[serde(rename = "noise/gaussian")]
+    NoiseGaussian { mean: f64, stddev: f64, seed: u64 },
+}
+
+impl Adjustment {
+    fn neutral_gamma() -> f64 {
+        1.0
+    }
+
+    /// Rejects parameters the effect can't be applied with
+    pub fn check(&self) -> Result<(), EngineError> {
+        let valid = match self {
+            Adjustment::ColorLevels(levels) => levels.is_valid(),
+            Adjustment::ColorPosterize { levels } => *levels >= 2,
+            Adjustment::ColorExposure { gamma, .. } => *gamma > 0.0,
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(EngineError::user_error(
+                "Invalid parameters of the adjustment",
+            ))
+        }
+    }
+
+    pub fn apply(&self, img: &mut Image) {
+        match self {
+            Adjustment::ColorGrayscale { method } => img.grayscale(*method),
+            Adjustment::ColorBrightnessContrast {
+                brightness,
+                contrast,
+            } => img.brightness_contrast(*brightness, *contrast),
+            Adjustment::ColorLevels(levels) => img.levels(levels),
+            Adjustment::ColorCurves(curves) => img.curves(curves),
+            Adjustment::ColorHueSaturation {
+                hue,
+                saturation,
+                lightness,
+            } => img.hue_saturation(*hue, *saturation, *lightness),
+            Adjustment::ColorBalance(balance) => img.color_balance(balance),
+            Adjustment::ColorInvert => img.invert(),
+            Adjustment::ColorPosterize { levels } => img.posterize(*levels),
+            Adjustment::ColorThreshold { level } => img.threshold(*level),
+            Adjustment::ColorVibrance { amount } => img.vibrance(*amount),
+            Adjustment::ColorExposure {
+                exposure,
+                offset,
+                gamma,
+            } => img.exposure(*exposure, *offset, *gamma),
+            Adjustment::NoiseGaussian { mean, stddev, seed } => {
+                img.gaussian_noise(*mean, *stddev, *seed)
+            }
+        }
+    }
+}
+
+/// Content of a text layer that stays editable until the layer is rasterized
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Text {
+    pub content: String,
+    pub font: FontDto,
+    pub style: TextStyle,
+}
+
+/// Largest font size in pixels
+const MAX_TEXT_SIZE: f32 = 2000.0;
+/// Largest distance between lines relative to the natural line height
+const MAX_LINE_SPACING: f32 = 10.0;
+
+impl Text {
+    /// Renders the text, fonts that are not bundled are looked up in the given context
+    pub fn render(&self, fonts: &HashMap<String, Vec<u8>>) -> Result<Image, EngineError> {
+        let size = self.style.size;
+        if !(size > 0.0 && size <= MAX_TEXT_SIZE) {
+            return Err(EngineError::user_error(
+                "Font size has to be larger than 0 and at most 2000 pixels",
+            ));
+        }
+        let spacing = self.style.line_spacing;
+        if !(spacing > 0.0 && spacing <= MAX_LINE_SPACING) {
+            return Err(EngineError::user_error(
+                "Line spacing has to be larger than 0 and at most 10",
+            ));
+        }
+        let font = self.font.to_font(fonts).map_err(EngineError::from)?;
+        Ok(Image::from_text(&self.content, &font, &self.style))
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -24,6 +211,35 @@ pub struct GhostImage {
     pub img: Image,
     pub mode: BlendMode,
     pub alpha: f32,
+    /// Selection (in global coordinates) outside of which the ghost leaves the layer untouched
+    #[serde(skip)]
+    pub selection: Option<Mask>,
+}
+
+/// Grayscale mask of a layer, white reveals and black hides its content when compositing
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct LayerMask {
+    /// Shares the position and size of the image of the layer
+    #[serde(skip)]
+    pub img: Mask,
+    /// Disabled masks are kept but not applied
+    pub enabled: bool,
+    /// Whether painting targets the mask instead of the pixels of the layer
+    pub edit: bool,
+    /// The mask before the ghost of the layer got painted into it
+    #[serde(skip)]
+    pub zombie: Option<Mask>,
+}
+
+impl LayerMask {
+    pub fn new(img: Mask) -> Self {
+        LayerMask {
+            img,
+            enabled: true,
+            edit: false,
+            zombie: None,
+        }
+    }
 }
 
 /// A single layer
@@ -42,6 +258,7 @@ pub struct Layer {
     pub flag: LayerFlag,
     pub visible: bool,
     pub name: String,
+    pub mask: Option<LayerMask>,
 }
 
 impl Serialize for Layer {
@@ -49,12 +266,14 @@ impl Serialize for Layer {
     where
         S: serde::Serializer,
     {
-        let mut s = serializer.serialize_struct("Layer", 5)?;
+        let mut s = serializer.serialize_struct("Layer", 8)?;
         s.serialize_field("attr", &self.attr)?;
         s.serialize_field("visible", &self.visible)?;
         s.serialize_field("name", &self.name)?;
         s.serialize_field("flag", &self.flag)?;
         s.serialize_field("size", &vec![&self.img.width(), &self.img.height()])?;
+        s.serialize_field("mask", &self.mask)?;
+        s.serialize_field("depth", &self.img.depth())?;
 
         // do not serialize ghost, zombie or img but instead a pointer for preview (for wasm really)
         let ptr = self.img.into_array().as_ptr() as u32;
@@ -65,18 +284,20 @@ impl Serialize for Layer {
 }
 
 impl Layer {
-    /// Creates an empty layer at position (0, 0) using alpha blending with the given size.
-    pub fn default(width: u32, height: u32) -> Self {
+    /// Creates an empty layer at position (0, 0) using alpha blending with the given size and depth.
+    pub fn default(width: u32, height: u32, depth: Depth) -> Self {
         let pos: Position = Position { x: 0, y: 0 };
-        let img = Image::new(width, height);
+        let img = Image::new_with_depth(width, height, depth);
         let blend = BlendMode::Alpha;
         let attr = LayerAttributes {
             pos,
             mode: blend,
             alpha: 1.0,
+            clipped: false,
+            group_mode: GroupMode::Isolated,
         };
         let flag = LayerFlag::Pixel;
-        
+
         Layer {
             img,
             attr,
@@ -85,9 +306,15 @@ impl Layer {
             zombie: None,
             visible: true,
             name: "New Layer".to_string(),
+            mask: None,
         }
     }
 
+    /// The mask that applies when compositing, if there is an enabled one
+    pub fn active_mask(&self) -> Option<&Mask> {
+        self.mask.as_ref().filter(|m| m.enabled).map(|m| &m.img)
+    }
+
     pub fn rectangle(&self) -> Rectangle {
         (
             self.attr.pos.x,
@@ -98,13 +325,78 @@ impl Layer {
             .into()
     }
 
+    /// Creates the empty root layer of a document with the given depth.
+    pub fn root(width: u32, height: u32, depth: Depth) -> Self {
+        let mut result = Self::from_content(Image::new_with_depth(width, height, depth));
+        result.flag = LayerFlag::Root;
+        result
+    }
+
     /// Creates a default layer with the given content at position (0, 0).
     pub fn from_content(content: Image) -> Self {
-        let mut result = Self::default(content.width(), content.height());
+        let mut result = Self::default(content.width(), content.height(), content.depth());
         result.img = content;
         result
     }
 
+    /// How much the layer covers the position (in global coordinates) when composited, from 0 to 1
+    pub fn coverage(&self, pos: &Position) -> f64 {
+        let p = *pos - self.attr.pos;
+        if !self.visible || !self.img.contains(&p) {
+            return 0.0;
+        }
+        let alpha = self.img.pixel(p.x as u32, p.y as u32).a as f64 / 255.0;
+        let opacity = self.active_mask().map_or(1.0, |m| m.opacity(&p));
+        alpha * opacity * self.attr.alpha as f64
+    }
+
+    pub fn is_pass_through(&self) -> bool {
+        self.flag == LayerFlag::Group && self.attr.group_mode == GroupMode::PassThrough
+    }
+
+    /// How much of the children of a pass-through group shows up within the destination, given by
+    /// the opacity and the mask of the group. `None` if they fully show up.
+    pub fn pass_through_coverage(&self, destination: &Rectangle) -> Option<Mask> {
+        let mask = self.active_mask();
+        if mask.is_none() && self.attr.alpha >= 1.0 {
+            return None;
+        }
+        let (width, height) = destination.size.into();
+        let offset = destination.position - self.attr.pos;
+        Some(Mask::from_fn(width, height, |x, y| {
+            let p = Position::new(x as i32, y as i32) + offset;
+            let opacity = mask.map_or(1.0, |m| m.opacity(&p));
+            (opacity * self.attr.alpha as f64 * 255.0).round() as u8
+        }))
+    }
+
+    /// Mask limiting the layer to where the base layer covers, combined with its own mask.
+    /// If an area (in global coordinates) is given, the mask is only computed there and hides the rest.
+    pub fn clipped_by(&self, base: &Layer, area: Option<&Rectangle>) -> Mask {
+        let (width, height) = self.img.size().into();
+        let Some(area) = area else {
+            return Mask::from_fn(width, height, |x, y| self.clipped_value(base, x, y));
+        };
+        let own = Rectangle::of((0, 0).into(), self.img.size());
+        let area = Rectangle::intersect(&(area - &self.attr.pos), &own);
+        let mut mask = Mask::new(width, height);
+        let (x0, y0) = (area.position.x as u32, area.position.y as u32);
+        for x in x0..x0 + area.size.width {
+            for y in y0..y0 + area.size.height {
+                mask.set(x, y, self.clipped_value(base, x, y));
+            }
+        }
+        mask
+    }
+
+    /// Value of the mask of [Layer::clipped_by] at the position in image coordinates
+    fn clipped_value(&self, base: &Layer, x: u32, y: u32) -> u8 {
+        let p = Position::new(x as i32, y as i32);
+        let opacity = self.active_mask().map_or(1.0, |m| m.opacity(&p));
+        let coverage = base.coverage(&(p + self.attr.pos));
+        (coverage * opacity * 255.0).round() as u8
+    }
+
     pub fn is_hit(&self, pos: &Position) -> bool {
         if !self.visible {
             return false;
@@ -120,7 +412,10 @@ impl Layer {
             return false;
         }
         let pixel = self.img.pixel(x_image, y_image);
-        pixel.a != 0
+        let opacity = self
+            .active_mask()
+            .map_or(1.0, |m| m.opacity(&(*pos - self.attr.pos)));
+        pixel.a != 0 && opacity > 0.0
     }
 }
 
@@ -129,7 +424,7 @@ mod test {
     use common::Position;
     use imagine::{BlendMode, Image};
 
-    use super::{Layer, LayerAttributes, LayerFlag};
+    use super::{GroupMode, Layer, LayerAttributes, LayerFlag};
 
     #[test]
     fn check_hit() {
@@ -142,13 +437,33 @@ mod test {
                 pos: Position::new(1, 1),
                 mode: BlendMode::Alpha,
                 alpha: 0.9,
+                clipped: false,
+                group_mode: GroupMode::Isolated,
             },
             flag: LayerFlag::Pixel,
             visible: true,
             name: "lksdjf".to_string(),
+            mask: None,
         };
         assert!(!layer.is_hit(&Position::new(0, 0)));
         assert!(layer.is_hit(&Position::new(1, 1)));
         assert!(!layer.is_hit(&Position::new(1, 0)));
     }
+
+    #[test]
+    fn clip_mask_within_damage() {
+        let base = Layer::from_content(Image::new_four_pixels("#fff", "#ffffff00", "#fff", "#fff"));
+        let mut clipped =
+            Layer::from_content(Image::new_four_pixels("#f00", "#f00", "#f00", "#f00"));
+        clipped.attr.clipped = true;
+        let full = clipped.clipped_by(&base, None);
+        assert_eq!(full.get(&Position::new(0, 0)), 255);
+        assert_eq!(full.get(&Position::new(1, 0)), 0);
+        assert_eq!(full.get(&Position::new(1, 1)), 255);
+
+        let damaged = clipped.clipped_by(&base, Some(&(0, 0, 2, 1).into()));
+        assert_eq!(damaged.get(&Position::new(0, 0)), 255);
+        assert_eq!(damaged.get(&Position::new(1, 0)), 0);
+        assert_eq!(damaged.get(&Position::new(1, 1)), 0);
+    }
 }
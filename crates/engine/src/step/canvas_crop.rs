@@ -0,0 +1,124 @@
+use common::{Position, Rectangle};
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Area the canvas is cropped to, in global coordinates
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum CropArea {
+    /// Rectangle spanned by two corner pixels
+    #[serde(rename = "rectangle")]
+    Rectangle { from: Position, to: Position },
+
+    /// Bounds of the current selection
+    #[serde(rename = "selection")]
+    Selection,
+}
+
+/// Crops the canvas, the layers keep their pixels outside of it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanvasCrop {
+    pub area: CropArea,
+}
+
+impl IStep for CanvasCrop {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let area = match &self.area {
+            CropArea::Rectangle { from, to } => {
+                let canvas = session.size();
+                let (min_x, max_x) = (from.x.min(to.x) as i64, from.x.max(to.x) as i64);
+                let (min_y, max_y) = (from.y.min(to.y) as i64, from.y.max(to.y) as i64);
+                if max_x < 0
+                    || max_y < 0
+                    || min_x >= canvas.width as i64
+                    || min_y >= canvas.height as i64
+                {
+                    return Err(EngineError::user_error(
+                        "The area to crop to lies outside of the canvas",
+                    ));
+                }
+                // spans that don't fit are rejected as too large below
+                let span = |min: i64, max: i64| u32::try_from(max - min + 1).unwrap_or(u32::MAX);
+                Rectangle::new(
+                    min_x as i32,
+                    min_y as i32,
+                    span(min_x, max_x),
+                    span(min_y, max_y),
+                )
+            }
+            CropArea::Selection => session
+                .selection
+                .as_ref()
+                .ok_or(EngineError::user_error("Nothing is selected"))?
+                .bounds(),
+        };
+        utils::check_canvas_size(area.size)?;
+        let offset = Position::new(-area.position.x, -area.position.y);
+        utils::reframe_canvas(session, offset, area.size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn crop_to_rectangle_and_selection() {
+        let mut state = Engine::new(10, 10);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [2, 2],
+            "position": [4, 4], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let crop = r#"{ "type": "canvas/crop",
+            "area": { "type": "rectangle", "from": [8, 8], "to": [3, 3] } }"#;
+        state.perform(&step(crop)).unwrap();
+        assert_eq!(state.size(), (6, 6).into());
+        assert_eq!(
+            state.content.get_value(1).unwrap().attr.pos,
+            Position::new(1, 1)
+        );
+        assert_eq!(state.content.root_value().img.pixel(1, 1), Color::RED);
+
+        let crop = r#"{ "type": "canvas/crop", "area": { "type": "selection" } }"#;
+        assert!(state.perform(&step(crop)).is_err());
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "ellipse", "from": [2, 0], "to": [4, 2] } }"#;
+        state.perform(&step(select)).unwrap();
+        state.perform(&step(crop)).unwrap();
+        assert_eq!(state.size(), (3, 3).into());
+        assert_eq!(
+            state.content.get_value(1).unwrap().attr.pos,
+            Position::new(-1, 1)
+        );
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (0, 0, 3, 3).into()
+        );
+        assert_eq!(state.content.root_value().img.pixel(0, 1), Color::RED);
+        assert_eq!(state.content.root_value().img.pixel(1, 1).a, 0);
+    }
+
+    #[test]
+    fn rectangle_has_to_fit() {
+        let mut state = Engine::new(10, 10);
+        for (from, to) in [
+            ([10, 0], [20, 5]),
+            ([-5, -5], [-1, 3]),
+            ([-2147483648, 0], [2147483647, 5]),
+            ([-20000, 0], [5, 5]),
+        ] {
+            let crop = format!(
+                r#"{{ "type": "canvas/crop",
+                "area": {{ "type": "rectangle", "from": {from:?}, "to": {to:?} }} }}"#
+            );
+            assert!(state.perform(&step(&crop)).is_err());
+        }
+        assert_eq!(state.size(), (10, 10).into());
+        let resize = r#"{ "type": "canvas/resize", "size": [100000, 10] }"#;
+        assert!(state.perform(&step(resize)).is_err());
+    }
+}
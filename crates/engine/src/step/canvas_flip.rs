@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, utils::Reorientation, Engine, EngineError};
+
+use super::{layer_flip::FlipDirection, IStep};
+
+/// Mirrors the whole canvas, text layers have to be rasterized first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanvasFlip {
+    pub direction: FlipDirection,
+}
+
+impl IStep for CanvasFlip {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let reorientation = match self.direction {
+            FlipDirection::Horizontally => Reorientation::FlipHorizontally,
+            FlipDirection::Vertically => Reorientation::FlipVertically,
+        };
+        utils::reorient_canvas(session, reorientation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn layers_are_mirrored() {
+        let mut state = Engine::new(10, 6);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [3, 2],
+            "position": [1, 0], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [1, 1],
+            "position": [1, 0], "color": "#000000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+
+        let flip = r#"{ "type": "canvas/flip", "direction": "horizontally" }"#;
+        state.perform(&step(flip)).unwrap();
+        assert_eq!(
+            state.content.get_value(1).unwrap().attr.pos,
+            Position::new(6, 0)
+        );
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(8, 0)
+        );
+        let root = &state.content.root_value().img;
+        assert_eq!(root.pixel(8, 0), Color::BLACK);
+        assert_eq!(root.pixel(6, 0), Color::RED);
+
+        let flip = r#"{ "type": "canvas/flip", "direction": "vertically" }"#;
+        state.perform(&step(flip)).unwrap();
+        assert_eq!(state.size(), (10, 6).into());
+        assert_eq!(
+            state.content.get_value(1).unwrap().attr.pos,
+            Position::new(6, 4)
+        );
+        assert_eq!(state.content.root_value().img.pixel(8, 5), Color::BLACK);
+    }
+}
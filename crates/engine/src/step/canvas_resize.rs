@@ -0,0 +1,120 @@
+use common::{Position, Size};
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Where the former canvas stays when the size changes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Anchor {
+    #[serde(rename = "top_left")]
+    TopLeft,
+    #[serde(rename = "top")]
+    Top,
+    #[serde(rename = "top_right")]
+    TopRight,
+    #[serde(rename = "left")]
+    Left,
+    #[default]
+    #[serde(rename = "center")]
+    Center,
+    #[serde(rename = "right")]
+    Right,
+    #[serde(rename = "bottom_left")]
+    BottomLeft,
+    #[serde(rename = "bottom")]
+    Bottom,
+    #[serde(rename = "bottom_right")]
+    BottomRight,
+}
+
+impl Anchor {
+    /// Where the former top left corner ends up on the canvas of the new size
+    fn offset(&self, from: Size, to: Size) -> Position {
+        // halves of the difference to move along each axis
+        let (x, y) = match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+        let dx = to.width as i32 - from.width as i32;
+        let dy = to.height as i32 - from.height as i32;
+        Position::new(dx * x / 2, dy * y / 2)
+    }
+}
+
+/// Changes the size of the canvas, the layers keep their pixels and move along with the anchor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanvasResize {
+    pub size: Size,
+    #[serde(default)]
+    pub anchor: Anchor,
+}
+
+impl IStep for CanvasResize {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        utils::check_canvas_size(self.size)?;
+        let offset = self.anchor.offset(session.size(), self.size);
+        utils::reframe_canvas(session, offset, self.size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{layer::LayerFlag, step::test_util::step, Engine};
+
+    #[test]
+    fn layers_move_with_the_anchor() {
+        let mut state = Engine::new(10, 10);
+        let group = r#"{ "type": "layer/create/group", "move_idx": null }"#;
+        state.perform(&step(group)).unwrap();
+        let json = r##"{ "type": "layer/create/empty", "move_idx": 1, "size": [2, 2],
+            "position": [0, 0], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [0, 0], "to": [1, 1] } }"#;
+        state.perform(&step(select)).unwrap();
+
+        let resize = r#"{ "type": "canvas/resize", "size": [14, 12], "anchor": "bottom_right" }"#;
+        state.perform(&step(resize)).unwrap();
+        assert_eq!(state.size(), (14, 12).into());
+        let layer = state.content.get_value(2).unwrap();
+        assert_eq!(layer.attr.pos, Position::new(4, 2));
+        let group = state.content.get_value(1).unwrap();
+        assert_eq!(group.flag, LayerFlag::Group);
+        assert_eq!(group.rectangle(), (0, 0, 14, 12).into());
+        assert_eq!(group.img.pixel(5, 3), Color::RED);
+        let root = &state.content.root_value().img;
+        assert_eq!(root.pixel(5, 3), Color::RED);
+        assert_eq!(root.pixel(1, 1).a, 0);
+        let selection = state.selection.as_ref().unwrap();
+        assert_eq!(selection.bounds(), (4, 2, 2, 2).into());
+
+        let shrink = r#"{ "type": "canvas/resize", "size": [4, 6] }"#;
+        state.perform(&step(shrink)).unwrap();
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(-1, -1)
+        );
+        assert_eq!(state.content.root_value().img.pixel(0, 0), Color::RED);
+
+        // replaying the history ends up at the same canvas
+        let expected = state.content.root_value().img.clone();
+        state.undo().unwrap();
+        assert_eq!(state.size(), (14, 12).into());
+        state.redo().unwrap();
+        assert_eq!(state.content.root_value().img, expected);
+        assert!(state
+            .perform(&step(r#"{ "type": "canvas/resize", "size": [0, 4] }"#))
+            .is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, utils::Reorientation, Engine, EngineError};
+
+use super::IStep;
+
+/// Rotates the whole canvas clockwise by a multiple of 90 degrees, text layers have to be rasterized first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanvasRotate {
+    pub angle: i32,
+}
+
+impl IStep for CanvasRotate {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let reorientation = match self.angle.rem_euclid(360) {
+            0 => return Ok(()),
+            90 => Reorientation::Rotate90,
+            180 => Reorientation::Rotate180,
+            270 => Reorientation::Rotate270,
+            _ => {
+                return Err(EngineError::user_error(
+                    "The canvas can only be rotated by multiples of 90 degrees",
+                ))
+            }
+        };
+        utils::reorient_canvas(session, reorientation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{layer::LayerFlag, step::test_util::step, Engine};
+
+    #[test]
+    fn quarter_turns_swap_the_size() {
+        let mut state = Engine::new(10, 6);
+        let group = r#"{ "type": "layer/create/group", "move_idx": null }"#;
+        state.perform(&step(group)).unwrap();
+        let json = r##"{ "type": "layer/create/empty", "move_idx": 1, "size": [3, 2],
+            "position": [1, 0], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [0, 0], "to": [0, 0] } }"#;
+        state.perform(&step(select)).unwrap();
+
+        state
+            .perform(&step(r#"{ "type": "canvas/rotate", "angle": 90 }"#))
+            .unwrap();
+        assert_eq!(state.size(), (6, 10).into());
+        let layer = state.content.get_value(2).unwrap();
+        assert_eq!(layer.rectangle(), (4, 1, 2, 3).into());
+        assert_eq!(
+            state.content.get_value(1).unwrap().rectangle(),
+            (0, 0, 6, 10).into()
+        );
+        let root = &state.content.root_value().img;
+        assert_eq!(root.pixel(5, 1), Color::RED);
+        assert_eq!(root.pixel(5, 0).a, 0);
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (5, 0, 1, 1).into()
+        );
+
+        state
+            .perform(&step(r#"{ "type": "canvas/rotate", "angle": -90 }"#))
+            .unwrap();
+        assert_eq!(state.size(), (10, 6).into());
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(1, 0)
+        );
+        state
+            .perform(&step(r#"{ "type": "canvas/rotate", "angle": 180 }"#))
+            .unwrap();
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(6, 4)
+        );
+        assert_eq!(state.content.root_value().img.pixel(8, 5), Color::RED);
+
+        let expected = state.content.root_value().img.clone();
+        state.undo().unwrap();
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(1, 0)
+        );
+        state.redo().unwrap();
+        assert_eq!(state.content.root_value().img, expected);
+        assert!(state
+            .perform(&step(r#"{ "type": "canvas/rotate", "angle": 45 }"#))
+            .is_err());
+    }
+
+    #[test]
+    fn text_layers_have_to_be_rasterized() {
+        let mut state = Engine::new(40, 30);
+        let text = r##"{
+            "type": "layer/create/text",
+            "move_idx": null,
+            "position": [2, 3],
+            "name": "Title",
+            "text": {
+                "content": "Hi",
+                "font": { "src": "bundled" },
+                "style": { "size": 16.0, "color": "#ff0000ff" }
+            }
+        }"##;
+        state.perform(&step(text)).unwrap();
+        let size = state.content.get_value(1).unwrap().img.size();
+
+        let rotate = step(r#"{ "type": "canvas/rotate", "angle": 90 }"#);
+        let err = state.perform(&rotate).unwrap_err();
+        assert!(err.to_string().contains("Title"));
+        assert_eq!(state.size(), (40, 30).into());
+        assert!(matches!(
+            state.content.get_value(1).unwrap().flag,
+            LayerFlag::Text(_)
+        ));
+
+        state
+            .perform(&step(r#"{ "type": "layer/rasterize", "id": 1 }"#))
+            .unwrap();
+        state.perform(&rotate).unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.img.size(), (size.height, size.width).into());
+    }
+}
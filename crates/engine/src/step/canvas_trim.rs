@@ -0,0 +1,91 @@
+use imagine::Mask;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Which borders are trimmed away
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum TrimBorder {
+    /// Fully transparent pixels
+    #[default]
+    #[serde(rename = "transparent")]
+    Transparent,
+
+    /// Pixels of the same color as the top left one
+    #[serde(rename = "color")]
+    Color,
+}
+
+/// Crops the canvas to what differs from the border of the composite
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanvasTrim {
+    #[serde(default)]
+    pub border: TrimBorder,
+}
+
+impl IStep for CanvasTrim {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let img = &session.content.root_value().img;
+        let corner = img.get(0, 0);
+        let content = Mask::from_fn(img.width(), img.height(), |x, y| {
+            let pixel = img.get(x, y);
+            let border = match self.border {
+                TrimBorder::Transparent => pixel[3] == 0.0,
+                TrimBorder::Color => pixel == corner,
+            };
+            if border {
+                0
+            } else {
+                255
+            }
+        });
+        let area = content.bounds();
+        if area.size.width == 0 || area.size.height == 0 {
+            return Err(EngineError::user_error("There is nothing to trim to"));
+        }
+        let offset = common::Position::new(-area.position.x, -area.position.y);
+        utils::reframe_canvas(session, offset, area.size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Position;
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn trim_borders() {
+        let mut state = Engine::new(10, 10);
+        let trim = r#"{ "type": "canvas/trim" }"#;
+        assert!(state.perform(&step(trim)).is_err());
+
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [8, 8],
+            "position": [1, 1], "color": "#0000ffff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [2, 3],
+            "position": [4, 3], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+
+        state.perform(&step(trim)).unwrap();
+        assert_eq!(state.size(), (8, 8).into());
+        let trim = r#"{ "type": "canvas/trim", "border": "color" }"#;
+        state.perform(&step(trim)).unwrap();
+        assert_eq!(state.size(), (2, 3).into());
+        assert_eq!(
+            state.content.get_value(1).unwrap().attr.pos,
+            Position::new(-3, -2)
+        );
+        assert_eq!(
+            state.content.get_value(2).unwrap().attr.pos,
+            Position::new(0, 0)
+        );
+
+        state.undo().unwrap();
+        assert_eq!(state.size(), (8, 8).into());
+        state.redo().unwrap();
+        assert_eq!(state.size(), (2, 3).into());
+    }
+}
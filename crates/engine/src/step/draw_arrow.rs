@@ -0,0 +1,67 @@
+use common::{Cap, Join, Path, Point, Position, StrokeStyle};
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Arrow from the first to the last position of the track.
+/// The head is part of the stroke, it is never filled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawArrow {
+    pub id: usize,
+    pub style: ShapeStyle,
+    /// Length of the head, its width is the same
+    pub head: f64,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawArrow {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn outline(&self) -> Outline {
+        let (Some(start), Some(end)) = (self.track.first(), self.track.last()) else {
+            return Outline::default();
+        };
+        let (start, tip) = (Point::center_of(start), Point::center_of(end));
+        let direction = (tip - start).normalized();
+        let head = self.head.clamp(0.0, start.distance_to(&tip));
+        let base = tip - direction * head;
+        let style = StrokeStyle::new(self.style.stroke_width, Join::Round, Cap::Round);
+        let mut stroke = Path::polyline(&[start, base], false).stroke(&style);
+        if head > 0.0 {
+            // runs in the same direction as the stroke contours, so they add up
+            let normal = direction.perpendicular() * (head / 2.0);
+            stroke.push(vec![tip, base + normal, base - normal]);
+        }
+        Outline {
+            fill: Path::new(),
+            stroke,
+        }
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawArrow(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawArrow(s) => Some(s),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use common::{Color, Position, Rectangle};
+use imagine::{BlendMode, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError, Step};
+
+use super::{draw_lines::next_dabs, IncrementalStep};
+
+/// Paints pixels copied from `offset` away through the brush stamp (clone stamp).
+/// With `heal` set the copied texture is matched to the color and lighting of the painted area (healing brush).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawClone {
+    pub id: usize,
+    /// Layer to copy from, the painted layer itself (as it was when the step started) if not given
+    pub source: Option<usize>,
+    /// Offset from the painted position to the sampled position
+    pub offset: Position,
+    pub radius: f64,
+    pub hardness: f64,
+    pub mode: BlendMode,
+    #[serde(default)]
+    pub heal: bool,
+    pub track: Vec<Position>,
+    pub distance: usize,
+    pub skip: Option<usize>,
+}
+
+impl IncrementalStep for DrawClone {
+    type Increment = Position;
+
+    fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.distance == 0 {
+            return Err(EngineError::user_error(
+                "The distance between dabs must be at least 1",
+            ));
+        }
+        let mut data = self.clone();
+        data.track = vec![];
+        if let Some(source) = self.source {
+            session
+                .content
+                .get_value(source)
+                .map_err(EngineError::from)?;
+        }
+        let step = Step::DrawClone(data);
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        let (w, h) = layer.img.size().into();
+        let ghost = GhostImage {
+            img: Image::new_with_depth(w, h, layer.img.depth()),
+            mode: self.mode,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        };
+        layer.ghost = Some(ghost);
+        layer.zombie = Some(layer.img.clone());
+        session.context.pending_step = Some(step);
+        Ok(())
+    }
+
+    fn extend(&self, session: &mut Engine, data: &Position) -> Result<(), EngineError> {
+        let stamp = Image::new_stamp(&Color::BLACK, self.hardness, self.radius);
+
+        if let Some(Step::DrawClone(dc)) = &mut session.context.pending_step {
+            let root = &session.content.root_value().rectangle();
+            // track is in global coordinates
+            let track = next_dabs(&mut dc.track, &mut dc.skip, dc.distance, data);
+            if track.is_empty() {
+                return Ok(());
+            }
+            // take the ghost out of the layer so the source can be borrowed alongside
+            let mut ghost = session
+                .content
+                .value_mut(dc.id)
+                .map_err(|_| EngineError::application_error("Invalid ID in pending step"))?
+                .ghost
+                .take()
+                .ok_or(EngineError::user_error(
+                    "Can't call expand without previous step matching up",
+                ))?;
+            let layer = session.content.get_value(dc.id)?;
+            let zombie = layer.zombie.as_ref().ok_or(EngineError::application_error(
+                "Missing zombie of pending step",
+            ))?;
+            let (source, source_pos) = match dc.source {
+                Some(idx) if idx != dc.id => {
+                    let source = session.content.get_value(idx)?;
+                    (&source.img, source.attr.pos)
+                }
+                _ => (zombie, layer.attr.pos),
+            };
+            // track_to_draw and offset are in image coordinates
+            let track_to_draw: Vec<Position> =
+                track.into_iter().map(|p| p - layer.attr.pos).collect();
+            let offset = dc.offset + layer.attr.pos - source_pos;
+            let heal = if dc.heal { Some(zombie) } else { None };
+            let damage = ghost
+                .img
+                .draw_clone(&stamp, source, offset, &track_to_draw, heal); // damage in image coordinates
+            let damage = &damage + &layer.attr.pos; // damage in global coordinates
+            let damage = Rectangle::intersect(&damage, root); // damage constraint to root area
+            session.content.value_mut(dc.id)?.ghost = Some(ghost);
+            utils::propagate_damage(&mut session.blender, &mut session.content, dc.id, &damage)
+        } else {
+            Err(EngineError::user_error(
+                "Can't call expand without having initialized",
+            ))
+        }
+    }
+
+    fn finish(&self, session: &mut Engine) -> Result<(), EngineError> {
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id)?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
+        session.context.pending_step = None;
+        Ok(())
+    }
+
+    fn break_up(&self) -> Vec<Position> {
+        self.track.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position, Size};
+    use imagine::BlendMode;
+
+    use crate::{step::test_util::create_empty_layer, Engine, Step};
+
+    use super::DrawClone;
+
+    const BLUE: Color = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+
+    fn clone_step(heal: bool) -> DrawClone {
+        DrawClone {
+            id: 2,
+            source: Some(1),
+            offset: Position::new(-50, -50),
+            radius: 4.0,
+            hardness: 1.0,
+            mode: BlendMode::Alpha,
+            heal,
+            track: vec![(55, 55).into(), (56, 55).into()],
+            distance: 1,
+            skip: None,
+        }
+    }
+
+    #[test]
+    fn clone_from_other_layer() {
+        let mut state = Engine::new(100, 100);
+        state
+            .perform(&create_empty_layer(
+                Some(Color::RED),
+                Size::new(10, 10),
+                Position::zero(),
+            ))
+            .unwrap();
+        state
+            .perform(&create_empty_layer(
+                Some(BLUE),
+                Size::new(100, 100),
+                Position::zero(),
+            ))
+            .unwrap();
+        state.perform(&Step::DrawClone(clone_step(false))).unwrap();
+        assert_eq!(state.content.root_value().img.pixel(55, 55), Color::RED);
+        assert_eq!(state.content.root_value().img.pixel(90, 90), BLUE);
+    }
+
+    #[test]
+    fn heal_adjusts_to_destination() {
+        let mut state = Engine::new(100, 100);
+        state
+            .perform(&create_empty_layer(
+                Some(Color::RED),
+                Size::new(10, 10),
+                Position::zero(),
+            ))
+            .unwrap();
+        state
+            .perform(&create_empty_layer(
+                Some(BLUE),
+                Size::new(100, 100),
+                Position::zero(),
+            ))
+            .unwrap();
+        state.perform(&Step::DrawClone(clone_step(true))).unwrap();
+        assert_eq!(state.content.root_value().img.pixel(55, 55), BLUE);
+    }
+
+    #[test]
+    fn dabs_need_a_distance() {
+        let mut state = Engine::new(100, 100);
+        state
+            .perform(&create_empty_layer(
+                Some(Color::RED),
+                Size::new(10, 10),
+                Position::zero(),
+            ))
+            .unwrap();
+        state
+            .perform(&create_empty_layer(
+                Some(BLUE),
+                Size::new(100, 100),
+                Position::zero(),
+            ))
+            .unwrap();
+        let mut step = clone_step(false);
+        step.distance = 0;
+        assert!(state.perform(&Step::DrawClone(step)).is_err());
+        assert_eq!(state.content.root_value().img.pixel(55, 55), BLUE);
+    }
+}
@@ -0,0 +1,86 @@
+use common::{Join, Path, Position};
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Ellipse inside the box spanned by the first and last position of the track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawEllipse {
+    pub id: usize,
+    pub style: ShapeStyle,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawEllipse {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn outline(&self) -> Outline {
+        Outline::in_box(&self.track, &self.style, Join::Round, |min, max, _| {
+            let center = min.lerp(&max, 0.5);
+            Path::ellipse(center, (max.x - min.x) / 2.0, (max.y - min.y) / 2.0)
+        })
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawEllipse(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawEllipse(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+    use imagine::BlendMode;
+
+    use crate::{
+        step::{draw_shape::ShapeStyle, test_util::engine_with_layer},
+        Step,
+    };
+
+    use super::DrawEllipse;
+
+    #[test]
+    fn fill_without_stroke() {
+        let mut state = engine_with_layer(40, 40);
+        let step = DrawEllipse {
+            id: 1,
+            style: ShapeStyle {
+                stroke_width: 0.0,
+                stroke: None,
+                fill: Some(Color::RED),
+                antialias: false,
+                mode: BlendMode::Alpha,
+            },
+            track: vec![(30, 30).into(), (10, 20).into()],
+        };
+        state.perform(&Step::DrawEllipse(step)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(20, 25), Color::RED);
+        assert_eq!(img.pixel(10, 25), Color::RED);
+        assert_eq!(img.pixel(30, 25), Color::RED);
+        assert_eq!(img.pixel(10, 20), Color::TRANSPARENT);
+        assert_eq!(img.pixel(30, 30), Color::TRANSPARENT);
+    }
+}
@@ -0,0 +1,171 @@
+use common::{Color, Position, Rectangle};
+use imagine::{BlendMode, ColorDistance, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Image a region of similar color is determined on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum FillSample {
+    /// The layer that is filled
+    #[default]
+    #[serde(rename = "layer")]
+    Layer,
+
+    /// What is visible of all layers together
+    #[serde(rename = "composite")]
+    Composite,
+}
+
+impl FillSample {
+    /// Image to sample for the layer with the given id, along with its position in global coordinates
+    pub(super) fn image<'a>(
+        &self,
+        session: &'a Engine,
+        id: usize,
+    ) -> Result<(&'a Image, Position), EngineError> {
+        let layer = match self {
+            FillSample::Layer => session.content.get_value(id)?,
+            FillSample::Composite => session.content.root_value(),
+        };
+        Ok((&layer.img, layer.attr.pos))
+    }
+}
+
+/// Fills the region of similar color around a position (paint bucket)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawFill {
+    pub id: usize,
+    /// Seed of the region in global coordinates
+    pub position: Position,
+    pub color: Color,
+    /// Maximum difference to the color at the seed, from 0 to 255
+    pub tolerance: f64,
+    #[serde(default)]
+    pub distance: ColorDistance,
+    /// Only fill pixels connected to the seed, otherwise every similar pixel of the image
+    pub contiguous: bool,
+    #[serde(default)]
+    pub sample: FillSample,
+    #[serde(default)]
+    pub antialias: bool,
+    pub mode: BlendMode,
+}
+
+impl IStep for DrawFill {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let root_rectangle = session.content.root_value().rectangle();
+        let (img, origin) = self.sample.image(session, self.id)?;
+        let seed = self.position - origin;
+        let region = img.similar_region(&seed, self.tolerance, self.distance, self.contiguous);
+        let region = if self.antialias {
+            region.antialiased()
+        } else {
+            region
+        };
+
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        let (w, h) = layer.img.size().into();
+        let mut img = Image::new_with_depth(w, h, layer.img.depth());
+        let damage = img.fill_mask(&region, layer.attr.pos - origin, &self.color); // damage in image coordinates
+        let damage = &damage + &layer.attr.pos; // damage in global coordinates
+        let damage = Rectangle::intersect(&damage, &root_rectangle); // damage constraint to root area
+        layer.ghost = Some(GhostImage {
+            img,
+            mode: self.mode,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        });
+        layer.zombie = Some(layer.img.clone());
+        utils::propagate_damage(&mut session.blender, &mut session.content, self.id, &damage)?;
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position, Size};
+    use imagine::{BlendMode, ColorDistance};
+
+    use crate::{step::test_util::create_empty_layer, Engine, Step};
+
+    use super::{DrawFill, FillSample};
+
+    const BLUE: Color = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+
+    fn fill(id: usize, sample: FillSample) -> Step {
+        Step::DrawFill(DrawFill {
+            id,
+            position: Position::new(2, 2),
+            color: BLUE,
+            tolerance: 0.0,
+            distance: ColorDistance::Rgb,
+            contiguous: true,
+            sample,
+            antialias: false,
+            mode: BlendMode::Alpha,
+        })
+    }
+
+    #[test]
+    fn fill_layer_region() {
+        let mut state = Engine::new(20, 20);
+        state
+            .perform(&create_empty_layer(
+                None,
+                Size::new(20, 20),
+                Position::zero(),
+            ))
+            .unwrap();
+        state
+            .perform(&create_empty_layer(
+                Some(Color::RED),
+                Size::new(5, 5),
+                Position::new(10, 10),
+            ))
+            .unwrap();
+
+        // only sees the empty layer itself
+        state.perform(&fill(1, FillSample::Layer)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(12, 12), Color::RED);
+        assert_eq!(state.content.get_value(1).unwrap().img.pixel(12, 12), BLUE);
+        assert!(state.undoable());
+    }
+
+    #[test]
+    fn fill_composite_region() {
+        let mut state = Engine::new(20, 20);
+        state
+            .perform(&create_empty_layer(
+                Some(Color::RED),
+                Size::new(5, 5),
+                Position::new(10, 10),
+            ))
+            .unwrap();
+        state
+            .perform(&create_empty_layer(
+                None,
+                Size::new(20, 20),
+                Position::zero(),
+            ))
+            .unwrap();
+
+        // the red square of the other layer limits the region
+        state.perform(&fill(2, FillSample::Composite)).unwrap();
+        let layer = state.content.get_value(2).unwrap();
+        assert_eq!(layer.img.pixel(0, 19), BLUE);
+        assert_eq!(layer.img.pixel(12, 12), Color::TRANSPARENT);
+    }
+}
@@ -0,0 +1,182 @@
+use common::{Point, Position, Rectangle};
+use imagine::{BlendMode, Gradient, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError, Step};
+
+use super::IncrementalStep;
+
+/// Gradient over the whole layer, running from the first to the last position of the track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawGradient {
+    pub id: usize,
+    pub gradient: Gradient,
+    pub mode: BlendMode,
+    pub track: Vec<Position>,
+}
+
+impl IncrementalStep for DrawGradient {
+    type Increment = Position;
+
+    fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let mut data = self.clone();
+        data.track.clear();
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        let (w, h) = layer.img.size().into();
+        let ghost = GhostImage {
+            img: Image::new_with_depth(w, h, layer.img.depth()),
+            mode: self.mode,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        };
+        layer.ghost = Some(ghost);
+        layer.zombie = Some(layer.img.clone());
+        session.context.pending_step = Some(Step::DrawGradient(data));
+        Ok(())
+    }
+
+    fn extend(&self, session: &mut Engine, data: &Position) -> Result<(), EngineError> {
+        let Some(Step::DrawGradient(step)) = session.context.pending_step.as_mut() else {
+            return Err(EngineError::user_error(
+                "Can't call expand without having initialized",
+            ));
+        };
+        if step.track.last() == Some(data) {
+            return Ok(());
+        }
+        // only the start and the current end are of interest
+        if step.track.len() > 1 {
+            step.track.pop();
+        }
+        step.track.push(*data);
+        let (Some(start), Some(end)) = (step.track.first(), step.track.last()) else {
+            return Ok(());
+        };
+
+        let root = &session.content.root_value().rectangle();
+        let layer = session
+            .content
+            .value_mut(step.id)
+            .map_err(|_| EngineError::application_error("Invalid ID in pending step"))?;
+        let ghost = layer.ghost.as_mut().ok_or(EngineError::user_error(
+            "Can't call expand without previous step matching up",
+        ))?;
+        // the handles sit on the centers of the pixels, in image coordinates
+        let pos = layer.attr.pos;
+        let (start, end) = (
+            Point::center_of(&(*start - pos)),
+            Point::center_of(&(*end - pos)),
+        );
+        let (w, h) = ghost.img.size().into();
+        ghost.img = Image::new_with_depth(w, h, ghost.img.depth());
+        ghost.img.draw_gradient(&step.gradient, start, end);
+        // the whole layer changes with every move of the handle
+        let damage = Rectangle::intersect(&layer.rectangle(), root);
+        utils::propagate_damage(&mut session.blender, &mut session.content, step.id, &damage)
+    }
+
+    fn finish(&self, session: &mut Engine) -> Result<(), EngineError> {
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id)?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
+        session.context.pending_step = None;
+        Ok(())
+    }
+
+    fn break_up(&self) -> Vec<Position> {
+        self.track.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use common::{Color, Position};
+    use imagine::Depth;
+
+    use crate::{
+        step::{test_util::engine_with_layer, LayerCreateEmpty, LayerMoveRelative},
+        Engine, Step,
+    };
+
+    fn gradient(track: Vec<Position>) -> Step {
+        let json = r##"{
+            "type": "draw/gradient",
+            "id": 1,
+            "gradient": {
+                "shape": "linear",
+                "colors": [
+                    { "offset": 0.0, "color": "#000000ff" },
+                    { "offset": 1.0, "color": "#ff0000ff" }
+                ],
+                "dither": true
+            },
+            "mode": "alpha",
+            "track": []
+        }"##;
+        let Step::DrawGradient(mut step) = serde_json::from_str(json).unwrap() else {
+            panic!("Not a gradient step");
+        };
+        step.track = track;
+        Step::DrawGradient(step)
+    }
+
+    #[test]
+    fn linear_across_moved_layer() {
+        let mut state = engine_with_layer(20, 20);
+        let move_layer = LayerMoveRelative {
+            id: 1,
+            delta: Position::new(5, 0),
+        };
+        state.perform(&Step::LayerMoveRelative(move_layer)).unwrap();
+        state
+            .perform(&gradient(vec![(5, 0).into(), (14, 0).into()]))
+            .unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(4, 3), Color::TRANSPARENT);
+        assert!(img.pixel(5, 3).r < 3);
+        assert_eq!(img.pixel(16, 3), Color::RED);
+    }
+
+    #[test]
+    fn preview_follows_the_handle() {
+        let mut state = engine_with_layer(20, 20);
+        state.start_step(&gradient(vec![])).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(19.0, 0.0).unwrap();
+        assert!(state.content.root_value().img.pixel(10, 0).r < 200);
+        state.extend_step(4.0, 0.0).unwrap();
+        assert_eq!(state.content.root_value().img.pixel(10, 0), Color::RED);
+        state.finish_step().unwrap();
+        assert_eq!(state.content.root_value().img.pixel(10, 0), Color::RED);
+    }
+
+    #[test]
+    fn keeps_the_depth_of_the_layer() {
+        let create = r#"{ "type": "project/create", "size": [20, 20], "depth": "u16" }"#;
+        let steps = vec![serde_json::from_str(create).unwrap()];
+        let mut state = Engine::reconstruct(&steps, HashMap::new(), HashMap::new()).unwrap();
+        let cl = LayerCreateEmpty {
+            move_idx: None,
+            size: None,
+            position: None,
+            color: None,
+            name: None,
+        };
+        state.perform(&Step::LayerCreateEmpty(cl)).unwrap();
+        state
+            .perform(&gradient(vec![(0, 0).into(), (19, 0).into()]))
+            .unwrap();
+        // 8 bits can only hold multiples of 1/255
+        let img = &state.content.get_value(1).unwrap().img;
+        assert_eq!(img.depth(), Depth::Sixteen);
+        assert!((0..20).any(|x| {
+            let red = img.get(x, 3)[0] * 255.0;
+            (red - red.round()).abs() > 0.01
+        }));
+    }
+}
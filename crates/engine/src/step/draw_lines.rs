@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::{layer::GhostImage, utils, Engine, EngineError, Step};
 
-use super::IncrementalStep;
+use super::{
+    symmetry::{mirror_track, Symmetry},
+    IncrementalStep,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DrawLine {
@@ -16,12 +19,22 @@ pub struct DrawLine {
     pub track: Vec<Position>,
     pub distance: usize,
     pub skip: Option<usize>,
+    #[serde(default)]
+    pub symmetry: Option<Symmetry>,
 }
 
 impl IncrementalStep for DrawLine {
     type Increment = Position;
 
     fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.distance == 0 {
+            return Err(EngineError::user_error(
+                "The distance between dabs must be at least 1",
+            ));
+        }
+        if let Some(symmetry) = &self.symmetry {
+            symmetry.check()?;
+        }
         let mut data = self.clone();
         data.track = vec![];
         let step = Step::DrawLine(data);
@@ -29,11 +42,13 @@ impl IncrementalStep for DrawLine {
             .content
             .value_mut(self.id)
             .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
         let (w, h) = layer.img.size().into();
         let ghost = GhostImage {
-            img: Image::new(w, h),
+            img: Image::new_with_depth(w, h, layer.img.depth()),
             mode: self.mode,
             alpha: self.color.a as f32 / 255.,
+            selection: session.selection.clone(),
         };
         layer.ghost = Some(ghost);
         layer.zombie = Some(layer.img.clone());
@@ -51,40 +66,32 @@ impl IncrementalStep for DrawLine {
                 .value_mut(dl.id)
                 .map_err(|_| EngineError::application_error("Invalid ID in pending step"))?;
             // track is in global coordinates
-            let track = if let Some(pos) = dl.track.last() {
-                if pos == data {
-                    return Ok(());
-                }
-                Position::interpolate(pos, data)
-            } else {
-                vec![*data, *data]
-            };
-            dl.track.push(*data);
-            let track_len = track.len() - 1; // adjust the fact that interpolate keeps first and last too
-            let still_to_skip = dl.skip.unwrap_or(0);
-            if still_to_skip >= track_len {
-                dl.skip = Some(still_to_skip - track_len);
+            let track = next_dabs(&mut dl.track, &mut dl.skip, dl.distance, data);
+            if track.is_empty() {
                 return Ok(());
-            } else {
-                dl.skip = Some(dl.distance - ((track_len - still_to_skip) % dl.distance));
             }
-            // track_to_draw is in image coordinates
-            let track_to_draw: &Vec<Position> = &track
-                .into_iter()
-                .skip(still_to_skip)
-                .step_by(self.distance)
-                .map(|p| p - layer.attr.pos) // maybe??
-                .collect();
-            if let Some(ghost) = &mut layer.ghost {
-                let damage = ghost.img.draw_line(&stamp, track_to_draw); // damage in image coordinates
+            let ghost = layer.ghost.as_mut().ok_or(EngineError::user_error(
+                "Can't call expand without previous step matching up",
+            ))?;
+            // each symmetric copy is damage tracked on its own to keep the damaged areas small
+            let mut damages = vec![];
+            for copy in mirror_track(&dl.symmetry, &track) {
+                // track_to_draw is in image coordinates
+                let track_to_draw: Vec<Position> =
+                    copy.into_iter().map(|p| p - layer.attr.pos).collect();
+                let damage = ghost.img.draw_line(&stamp, &track_to_draw); // damage in image coordinates
                 let damage = &damage + &layer.attr.pos; // damage in global coordinates
-                let damage = Rectangle::intersect(&damage, root); // damage constraint to root area
-                utils::propagate_damage(&mut session.blender, &mut session.content, dl.id, &damage)
-            } else {
-                Err(EngineError::user_error(
-                    "Can't call expand without previous step matching up",
-                ))
+                damages.push(Rectangle::intersect(&damage, root)); // damage constraint to root area
+            }
+            for damage in damages {
+                utils::propagate_damage(
+                    &mut session.blender,
+                    &mut session.content,
+                    dl.id,
+                    &damage,
+                )?;
             }
+            Ok(())
         } else {
             Err(EngineError::user_error(
                 "Can't call expand without having initialized",
@@ -104,17 +111,51 @@ impl IncrementalStep for DrawLine {
     }
 }
 
+/// Appends `data` to the `track` and returns the positions since the last entry of the track that receive a dab,
+/// such that consecutive dabs are `distance` apart. `skip` keeps the positions still to skip in between calls.
+pub(super) fn next_dabs(
+    track: &mut Vec<Position>,
+    skip: &mut Option<usize>,
+    distance: usize,
+    data: &Position,
+) -> Vec<Position> {
+    let interpolated = if let Some(pos) = track.last() {
+        if pos == data {
+            return vec![];
+        }
+        Position::interpolate(pos, data)
+    } else {
+        vec![*data, *data]
+    };
+    track.push(*data);
+    let track_len = interpolated.len() - 1; // adjust the fact that interpolate keeps first and last too
+    let still_to_skip = skip.unwrap_or(0);
+    if still_to_skip >= track_len {
+        *skip = Some(still_to_skip - track_len);
+        return vec![];
+    }
+    *skip = Some(distance - ((track_len - still_to_skip) % distance));
+    interpolated
+        .into_iter()
+        .skip(still_to_skip)
+        .step_by(distance)
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use common::{Color, Position};
     use imagine::BlendMode;
 
     use crate::{
-        step::{LayerCreateEmpty, LayerMoveRelative},
+        step::{test_util::engine_with_layer, LayerCreateEmpty, LayerMoveRelative},
         Engine, Step,
     };
 
-    use super::DrawLine;
+    use super::{
+        super::symmetry::{Symmetry, SymmetryMode},
+        DrawLine,
+    };
 
     #[test]
     fn simple_draw() {
@@ -128,6 +169,7 @@ mod test {
             track: vec![(1, 2).into(), (20, 10).into()],
             distance: 5,
             skip: None,
+            symmetry: None,
         };
         let cl = LayerCreateEmpty {
             move_idx: None,
@@ -143,6 +185,24 @@ mod test {
         assert_eq!(&color, compare);
     }
 
+    #[test]
+    fn zero_distance_is_rejected() {
+        let dl = DrawLine {
+            id: 1,
+            radius: 10.0,
+            color: Color::RED,
+            mode: BlendMode::Alpha,
+            hardness: 1.0,
+            track: vec![(1, 2).into(), (20, 10).into()],
+            distance: 0,
+            skip: None,
+            symmetry: None,
+        };
+        let mut state = engine_with_layer(100, 100);
+        assert!(state.perform(&Step::DrawLine(dl)).is_err());
+        assert_eq!(state.content.root_value().img.pixel(20, 10).a, 0);
+    }
+
     #[test]
     fn draw_outside_layer() {
         let color = Color::RED;
@@ -161,6 +221,7 @@ mod test {
             hardness: 1.0,
             distance: 2,
             skip: None,
+            symmetry: None,
         };
         let cl = LayerCreateEmpty {
             move_idx: None,
@@ -182,4 +243,38 @@ mod test {
         let compare = &state.content.root_value().img.pixel(20, 10);
         assert_eq!(&color, compare);
     }
+
+    #[test]
+    fn radial_symmetry() {
+        let color = Color::RED;
+        let dl = DrawLine {
+            id: 1,
+            radius: 3.0,
+            color,
+            mode: BlendMode::Alpha,
+            hardness: 1.0,
+            track: vec![(70, 50).into(), (72, 50).into()],
+            distance: 1,
+            skip: None,
+            symmetry: Some(Symmetry {
+                mode: SymmetryMode::Radial { segments: 4 },
+                center: Position::new(50, 50),
+            }),
+        };
+        let cl = LayerCreateEmpty {
+            move_idx: None,
+            size: None,
+            position: None,
+            color: None,
+            name: None,
+        };
+        let mut state = Engine::new(100, 100);
+        state.perform(&Step::LayerCreateEmpty(cl)).unwrap();
+        state.perform(&Step::DrawLine(dl)).unwrap();
+        let img = &state.content.root_value().img;
+        for (x, y) in [(70, 50), (50, 70), (30, 50), (50, 30)] {
+            assert_eq!(img.pixel(x, y), color);
+        }
+        assert_eq!(img.pixel(50, 50), Color::TRANSPARENT);
+    }
 }
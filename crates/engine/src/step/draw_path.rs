@@ -0,0 +1,117 @@
+use common::{Color, FillRule, Path, Point, StrokeStyle};
+use imagine::{BlendMode, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Most dashes a stroke may be split into
+const MAX_DASHES: f64 = 100_000.0;
+
+/// Fills and strokes a vector path given in global coordinates
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawPath {
+    pub id: usize,
+    pub path: Path,
+    /// Color of the inner area, not filled if not given
+    pub fill: Option<Color>,
+    #[serde(default)]
+    pub fill_rule: FillRule,
+    /// Color of the outline, no outline if not given
+    pub stroke: Option<Color>,
+    #[serde(default)]
+    pub stroke_style: StrokeStyle,
+    pub antialias: bool,
+    pub mode: BlendMode,
+}
+
+impl IStep for DrawPath {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.stroke.is_some() && self.stroke_style.dash_count(&self.path) > MAX_DASHES {
+            return Err(EngineError::user_error(
+                "The dash pattern is too fine for the length of the path",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        let (w, h) = layer.img.size().into();
+        let mut img = Image::new_with_depth(w, h, layer.img.depth());
+        let path = self.path.translate(Point::new(
+            -layer.attr.pos.x as f64,
+            -layer.attr.pos.y as f64,
+        ));
+        if let Some(fill) = &self.fill {
+            img.fill_path(&path, self.fill_rule, fill, self.antialias);
+        }
+        if let Some(stroke) = &self.stroke {
+            img.stroke_path(&path, &self.stroke_style, stroke, self.antialias);
+        }
+        layer.ghost = Some(GhostImage {
+            img,
+            mode: self.mode,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        });
+        layer.zombie = Some(layer.img.clone());
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id)?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::LayerCreateEmpty, Engine, Step};
+
+    #[test]
+    fn fill_even_odd_and_stroke_dashed() {
+        let mut state = Engine::new(40, 40);
+        let cl = LayerCreateEmpty {
+            move_idx: None,
+            size: None,
+            position: None,
+            color: None,
+            name: None,
+        };
+        state.perform(&Step::LayerCreateEmpty(cl)).unwrap();
+        let json = r##"{
+            "type": "draw/path",
+            "id": 1,
+            "path": [
+                {"type": "move", "to": [5, 5]},
+                {"type": "line", "to": [35, 5]},
+                {"type": "line", "to": [35, 35]},
+                {"type": "line", "to": [5, 35]},
+                {"type": "close"},
+                {"type": "move", "to": [15, 15]},
+                {"type": "cubic", "control1": [25, 10], "control2": [30, 20], "to": [25, 25]},
+                {"type": "line", "to": [15, 25]},
+                {"type": "close"}
+            ],
+            "fill": "#ff0000",
+            "fill_rule": "evenodd",
+            "stroke": "#000000",
+            "stroke_style": {"width": 2, "dash": [4, 4]},
+            "antialias": false,
+            "mode": "alpha"
+        }"##;
+        let step: Step = serde_json::from_str(json).unwrap();
+        state.perform(&step).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(10, 10), Color::RED);
+        assert_eq!(img.pixel(18, 20), Color::TRANSPARENT);
+        // dashes along the top edge, outside of the filled area
+        assert_eq!(img.pixel(6, 4), Color::BLACK);
+        assert_eq!(img.pixel(11, 4), Color::TRANSPARENT);
+        assert_eq!(img.pixel(14, 4), Color::BLACK);
+
+        let too_fine = json.replace("[4, 4]", "[1e-9, 1e-9]");
+        let step: Step = serde_json::from_str(&too_fine).unwrap();
+        assert!(state.perform(&step).is_err());
+    }
+}
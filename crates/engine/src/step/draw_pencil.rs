@@ -0,0 +1,279 @@
+use common::{Color, Position, Rectangle, Size};
+use imagine::{BlendMode, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError, Step};
+
+use super::{
+    symmetry::{mirror_track, Symmetry},
+    IncrementalStep,
+};
+
+/// Largest width and height of the squares the pencil sets
+const MAX_SIZE: u32 = 256;
+
+/// Hard edged pencil for pixel art.
+/// Sets `size` x `size` squares of exactly `color` along Bresenham lines between the positions of the track,
+/// so a track of just two positions draws a straight line.
+/// The pixels are replaced instead of blended, semi-transparent colors end up unchanged in the layer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawPencil {
+    pub id: usize,
+    pub size: u32,
+    pub color: Color,
+    /// Removes L-shaped corners of the drawn path (only for 1px pencils)
+    #[serde(default)]
+    pub pixel_perfect: bool,
+    pub track: Vec<Position>,
+    #[serde(default)]
+    pub symmetry: Option<Symmetry>,
+}
+
+/// State of a pencil stroke that is being drawn, kept in the context of the session
+#[derive(Debug, Clone)]
+pub(crate) struct PendingPencil {
+    /// The most recently drawn pixels per symmetric copy (in global coordinates)
+    drawn: Vec<Vec<Position>>,
+    /// What the pencil draws on before the stroke, corners removed again are restored from it
+    original: Image,
+}
+
+/// Whether `b` is the corner of an L-shape formed by `a`, `b` and `c`.
+fn is_corner(a: &Position, b: &Position, c: &Position) -> bool {
+    (a.x == b.x || a.y == b.y) && (c.x == b.x || c.y == b.y) && a.x != c.x && a.y != c.y
+}
+
+impl IncrementalStep for DrawPencil {
+    type Increment = Position;
+
+    fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.size == 0 || self.size > MAX_SIZE {
+            return Err(EngineError::user_error(
+                "Pencil size must be between 1 and 256",
+            ));
+        }
+        if let Some(symmetry) = &self.symmetry {
+            symmetry.check()?;
+        }
+        let mut data = self.clone();
+        data.track = vec![];
+        let step = Step::DrawPencil(data);
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        // the ghost starts as a copy of what is drawn on and replaces it, so drawn pixels keep their exact color
+        let original = match &layer.mask {
+            Some(mask) if mask.edit => mask.img.to_image(),
+            _ => layer.img.clone(),
+        };
+        let ghost = GhostImage {
+            img: original.clone(),
+            mode: BlendMode::Source,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        };
+        layer.ghost = Some(ghost);
+        layer.zombie = Some(layer.img.clone());
+        session.context.pending_step = Some(step);
+        session.context.pencil = Some(PendingPencil {
+            drawn: vec![],
+            original,
+        });
+        Ok(())
+    }
+
+    fn extend(&self, session: &mut Engine, data: &Position) -> Result<(), EngineError> {
+        if let (Some(Step::DrawPencil(dp)), Some(pending)) = (
+            &mut session.context.pending_step,
+            &mut session.context.pencil,
+        ) {
+            let root = &session.content.root_value().rectangle();
+            let layer = session
+                .content
+                .value_mut(dp.id)
+                .map_err(|_| EngineError::application_error("Invalid ID in pending step"))?;
+            // segments are in global coordinates, one per symmetric copy
+            let segments: Vec<Vec<Position>> = match dp.track.last() {
+                Some(last) if last == data => return Ok(()),
+                Some(last) => mirror_track(&dp.symmetry, &[*last, *data])
+                    .into_iter()
+                    .map(|copy| Position::bresenham(&copy[0], &copy[1]))
+                    .map(|line| line.into_iter().skip(1).collect())
+                    .collect(),
+                None => mirror_track(&dp.symmetry, &[*data]),
+            };
+            dp.track.push(*data);
+            pending.drawn.resize(segments.len(), vec![]);
+            let ghost = layer.ghost.as_mut().ok_or(EngineError::user_error(
+                "Can't call expand without previous step matching up",
+            ))?;
+            let mut damages = vec![];
+            for (pixels, drawn) in segments.into_iter().zip(pending.drawn.iter_mut()) {
+                let mut copy_damages = vec![];
+                for pixel in pixels {
+                    if dp.pixel_perfect && dp.size == 1 {
+                        if let [.., a, b] = drawn[..] {
+                            if is_corner(&a, &b, &pixel) {
+                                let corner = b - layer.attr.pos;
+                                if ghost.img.contains(&corner) {
+                                    let (x, y) = (corner.x as u32, corner.y as u32);
+                                    ghost.img.set(x, y, pending.original.get(x, y));
+                                }
+                                copy_damages.push(Rectangle::of(corner, Size::new(1, 1)));
+                                drawn.pop();
+                            }
+                        }
+                    }
+                    let draw = &[pixel - layer.attr.pos];
+                    copy_damages.push(ghost.img.draw_pixels(&dp.color, dp.size, draw));
+                    drawn.push(pixel);
+                    if drawn.len() > 2 {
+                        drawn.remove(0);
+                    }
+                }
+                let damage = Rectangle::bounding_all(copy_damages); // damage in image coordinates
+                let damage = &damage + &layer.attr.pos; // damage in global coordinates
+                damages.push(Rectangle::intersect(&damage, root)); // damage constraint to root area
+            }
+            for damage in damages {
+                utils::propagate_damage(
+                    &mut session.blender,
+                    &mut session.content,
+                    dp.id,
+                    &damage,
+                )?;
+            }
+            Ok(())
+        } else {
+            Err(EngineError::user_error(
+                "Can't call expand without having initialized",
+            ))
+        }
+    }
+
+    fn finish(&self, session: &mut Engine) -> Result<(), EngineError> {
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id)?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
+        session.context.pending_step = None;
+        session.context.pencil = None;
+        Ok(())
+    }
+
+    fn break_up(&self) -> Vec<Position> {
+        self.track.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{step::test_util::engine_with_layer, Step};
+
+    use super::{
+        super::symmetry::{Symmetry, SymmetryMode},
+        DrawPencil,
+    };
+
+    fn pencil(track: Vec<Position>, pixel_perfect: bool) -> Step {
+        Step::DrawPencil(DrawPencil {
+            id: 1,
+            size: 1,
+            color: Color::RED,
+            pixel_perfect,
+            track,
+            symmetry: None,
+        })
+    }
+
+    #[test]
+    fn straight_line_between_two_clicks() {
+        let mut state = engine_with_layer(30, 30);
+        let track = vec![(2, 2).into(), (12, 7).into()];
+        state.perform(&pencil(track, false)).unwrap();
+        let img = &state.content.root_value().img;
+        let drawn: Vec<(u32, u32)> = (0..30)
+            .flat_map(|x| (0..30).map(move |y| (x, y)))
+            .filter(|(x, y)| img.pixel(*x, *y) == Color::RED)
+            .collect();
+        assert_eq!(drawn.len(), 11);
+        assert!(drawn.contains(&(2, 2)));
+        assert!(drawn.contains(&(12, 7)));
+    }
+
+    #[test]
+    fn pixel_perfect_removes_corners() {
+        let track: Vec<Position> = vec![(5, 5).into(), (6, 5).into(), (6, 6).into()];
+        let mut state = engine_with_layer(30, 30);
+        state.perform(&pencil(track.clone(), false)).unwrap();
+        assert_eq!(state.content.root_value().img.pixel(6, 5), Color::RED);
+
+        let mut state = engine_with_layer(30, 30);
+        state.perform(&pencil(track, true)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::RED);
+        assert_eq!(img.pixel(6, 5), Color::TRANSPARENT);
+        assert_eq!(img.pixel(6, 6), Color::RED);
+    }
+
+    #[test]
+    fn colors_are_replaced_exactly() {
+        let mut state = engine_with_layer(30, 30);
+        let mut step = pencil(vec![(2, 2).into(), (12, 2).into()], false);
+        if let Step::DrawPencil(dp) = &mut step {
+            dp.size = 3;
+        }
+        state.perform(&step).unwrap();
+        let translucent = Color {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 100,
+        };
+        if let Step::DrawPencil(dp) = &mut step {
+            dp.size = 1;
+            dp.color = translucent;
+        }
+        state.perform(&step).unwrap();
+        let layer = &state.content.get_value(1).unwrap().img;
+        assert_eq!(layer.pixel(5, 2), translucent);
+        assert_eq!(layer.pixel(5, 3), Color::RED);
+        assert_eq!(layer.pixel(5, 4).a, 0);
+
+        if let Step::DrawPencil(dp) = &mut step {
+            dp.size = 100_000;
+        }
+        assert!(state.perform(&step).is_err());
+    }
+
+    #[test]
+    fn mirrored_along_both_axes() {
+        let mut state = engine_with_layer(30, 30);
+        let mut step = pencil(vec![(2, 3).into(), (5, 3).into()], false);
+        if let Step::DrawPencil(dp) = &mut step {
+            dp.symmetry = Some(Symmetry {
+                mode: SymmetryMode::Both,
+                center: Position::new(15, 15),
+            });
+        }
+        state.perform(&step).unwrap();
+        let img = &state.content.root_value().img;
+        for (x, y) in [(2, 3), (5, 3), (28, 3), (25, 3), (2, 27), (5, 27), (28, 27)] {
+            assert_eq!(img.pixel(x, y), Color::RED);
+        }
+        assert_eq!(img.pixel(15, 3), Color::TRANSPARENT);
+
+        let mut step = pencil(vec![(2, 3).into()], false);
+        if let Step::DrawPencil(dp) = &mut step {
+            dp.symmetry = Some(Symmetry {
+                mode: SymmetryMode::Radial {
+                    segments: 100_000_000,
+                },
+                center: Position::new(15, 15),
+            });
+        }
+        assert!(state.perform(&step).is_err());
+    }
+}
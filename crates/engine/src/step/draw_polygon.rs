@@ -0,0 +1,88 @@
+use common::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Closed polygon through all positions of the track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawPolygon {
+    pub id: usize,
+    pub style: ShapeStyle,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawPolygon {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn push(&mut self, pos: Position) {
+        self.track.push(pos);
+    }
+
+    fn outline(&self) -> Outline {
+        Outline::through(&self.track, &self.style, true)
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawPolygon(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawPolygon(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+    use imagine::BlendMode;
+
+    use crate::{
+        step::{draw_shape::ShapeStyle, test_util::engine_with_layer},
+        Step,
+    };
+
+    use super::DrawPolygon;
+
+    #[test]
+    fn every_position_is_a_vertex() {
+        let mut state = engine_with_layer(40, 40);
+        let step = Step::DrawPolygon(DrawPolygon {
+            id: 1,
+            style: ShapeStyle {
+                stroke_width: 1.0,
+                stroke: None,
+                fill: Some(Color::RED),
+                antialias: false,
+                mode: BlendMode::Alpha,
+            },
+            track: vec![],
+        });
+        state.start_step(&step).unwrap();
+        state.extend_step(5.0, 5.0).unwrap();
+        state.extend_step(30.0, 5.0).unwrap();
+        state.extend_step(30.0, 30.0).unwrap();
+        state.finish_step().unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(25, 10), Color::RED);
+        assert_eq!(img.pixel(10, 25), Color::TRANSPARENT);
+    }
+}
@@ -0,0 +1,51 @@
+use common::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Open line through all positions of the track, it is never filled
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawPolyline {
+    pub id: usize,
+    pub style: ShapeStyle,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawPolyline {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn push(&mut self, pos: Position) {
+        self.track.push(pos);
+    }
+
+    fn outline(&self) -> Outline {
+        Outline::through(&self.track, &self.style, false)
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawPolyline(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawPolyline(s) => Some(s),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use common::{Join, Path, Position};
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Rectangle spanned by the first and last position of the track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawRectangle {
+    pub id: usize,
+    pub style: ShapeStyle,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawRectangle {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn outline(&self) -> Outline {
+        Outline::in_box(&self.track, &self.style, Join::Miter, |min, max, _| {
+            Path::rectangle(min, max)
+        })
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawRectangle(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawRectangle(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+    use imagine::BlendMode;
+
+    use crate::{
+        step::{draw_shape::ShapeStyle, test_util::engine_with_layer, LayerMoveRelative},
+        Step,
+    };
+
+    use super::DrawRectangle;
+
+    const BLUE: Color = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+
+    fn rectangle(track: Vec<Position>) -> DrawRectangle {
+        DrawRectangle {
+            id: 1,
+            style: ShapeStyle {
+                stroke_width: 2.0,
+                stroke: Some(Color::RED),
+                fill: Some(BLUE),
+                antialias: true,
+                mode: BlendMode::Alpha,
+            },
+            track,
+        }
+    }
+
+    #[test]
+    fn stroke_and_fill() {
+        let mut state = engine_with_layer(40, 40);
+        let step = rectangle(vec![(5, 5).into(), (14, 9).into()]);
+        state.perform(&Step::DrawRectangle(step)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::RED);
+        assert_eq!(img.pixel(6, 6), Color::RED);
+        assert_eq!(img.pixel(14, 9), Color::RED);
+        assert_eq!(img.pixel(10, 7), BLUE);
+        assert_eq!(img.pixel(15, 7), Color::TRANSPARENT);
+        assert_eq!(img.pixel(4, 7), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn respects_layer_position() {
+        let mut state = engine_with_layer(40, 40);
+        let move_layer = LayerMoveRelative {
+            id: 1,
+            delta: Position::new(10, 10),
+        };
+        state.perform(&Step::LayerMoveRelative(move_layer)).unwrap();
+        let step = rectangle(vec![(15, 15).into(), (20, 20).into()]);
+        state.perform(&Step::DrawRectangle(step)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(15, 15), Color::RED);
+        assert_eq!(img.pixel(18, 18), BLUE);
+        assert_eq!(img.pixel(8, 8), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn preview_follows_the_drag() {
+        let mut state = engine_with_layer(40, 40);
+        let step = Step::DrawRectangle(rectangle(vec![]));
+        state.start_step(&step).unwrap();
+        state.extend_step(5.0, 5.0).unwrap();
+        state.extend_step(30.0, 30.0).unwrap();
+        state.extend_step(10.0, 10.0).unwrap();
+        state.finish_step().unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(10, 10), Color::RED);
+        assert_eq!(img.pixel(20, 20), Color::TRANSPARENT);
+        assert_eq!(img.pixel(30, 30), Color::TRANSPARENT);
+    }
+}
@@ -0,0 +1,52 @@
+use common::{Join, Path, Position};
+use serde::{Deserialize, Serialize};
+
+use crate::Step;
+
+use super::draw_shape::{Outline, Shape, ShapeStyle};
+
+/// Rectangle with rounded corners spanned by the first and last position of the track
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawRoundedRectangle {
+    pub id: usize,
+    pub style: ShapeStyle,
+    /// Radius of the outer corners
+    pub radius: f64,
+    pub track: Vec<Position>,
+}
+
+impl Shape for DrawRoundedRectangle {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    fn track(&self) -> &Vec<Position> {
+        &self.track
+    }
+
+    fn track_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.track
+    }
+
+    fn outline(&self) -> Outline {
+        Outline::in_box(&self.track, &self.style, Join::Round, |min, max, inset| {
+            // the radius applies to the outer edge, the path follows it concentrically
+            Path::rounded_rectangle(min, max, self.radius - inset)
+        })
+    }
+
+    fn into_step(self) -> Step {
+        Step::DrawRoundedRectangle(self)
+    }
+
+    fn pending(step: &mut Step) -> Option<&mut Self> {
+        match step {
+            Step::DrawRoundedRectangle(s) => Some(s),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use common::{Cap, Color, FillRule, Join, Path, Point, Position, Rectangle, StrokeStyle};
+use imagine::{BlendMode, Image};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::GhostImage, utils, Engine, EngineError, Step};
+
+use super::IncrementalStep;
+
+/// Appearance shared by all shape steps
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShapeStyle {
+    pub stroke_width: f64,
+    /// Color of the outline, no outline if not given
+    pub stroke: Option<Color>,
+    /// Color of the inner area, not filled if not given
+    pub fill: Option<Color>,
+    pub antialias: bool,
+    pub mode: BlendMode,
+}
+
+/// Geometry of a shape in global coordinates
+#[derive(Default)]
+pub(super) struct Outline {
+    /// Area filled with the fill color using the nonzero winding rule
+    pub fill: Path,
+    /// Contours of the stroke, see [Path::stroke]
+    pub stroke: Vec<Vec<Point>>,
+}
+
+/// A shape that is drawn by dragging.
+/// While the track changes the whole shape is drawn again into the ghost of the layer.
+pub(super) trait Shape: Clone {
+    fn id(&self) -> usize;
+
+    fn style(&self) -> &ShapeStyle;
+
+    fn track(&self) -> &Vec<Position>;
+
+    fn track_mut(&mut self) -> &mut Vec<Position>;
+
+    /// Contours of the shape for the current track
+    fn outline(&self) -> Outline;
+
+    fn into_step(self) -> Step;
+
+    /// The shape if the given step is the pending step of this kind
+    fn pending(step: &mut Step) -> Option<&mut Self>;
+
+    /// Adds a position to the track. By default only the start and the current end are kept.
+    fn push(&mut self, pos: Position) {
+        let track = self.track_mut();
+        if track.len() > 1 {
+            track.pop();
+        }
+        track.push(pos);
+    }
+}
+
+impl<T> IncrementalStep for T
+where
+    T: Shape,
+{
+    type Increment = Position;
+
+    fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let mut data = self.clone();
+        data.track_mut().clear();
+        let layer = session
+            .content
+            .value_mut(self.id())
+            .map_err(EngineError::from)?;
+        utils::check_paintable(layer)?;
+        let (w, h) = layer.img.size().into();
+        let ghost = GhostImage {
+            img: Image::new_with_depth(w, h, layer.img.depth()),
+            mode: self.style().mode,
+            alpha: 1.0,
+            selection: session.selection.clone(),
+        };
+        layer.ghost = Some(ghost);
+        layer.zombie = Some(layer.img.clone());
+        session.context.pending_step = Some(data.into_step());
+        Ok(())
+    }
+
+    fn extend(&self, session: &mut Engine, data: &Position) -> Result<(), EngineError> {
+        let Some(shape) = session.context.pending_step.as_mut().and_then(T::pending) else {
+            return Err(EngineError::user_error(
+                "Can't call expand without having initialized",
+            ));
+        };
+        if shape.track().last() == Some(data) {
+            return Ok(());
+        }
+        let previous = if shape.track().is_empty() {
+            Rectangle::new(0, 0, 0, 0)
+        } else {
+            shape.outline().bounds()
+        };
+        shape.push(*data);
+        let outline = shape.outline();
+        let bounds = outline.bounds();
+        let style = shape.style();
+
+        let root = &session.content.root_value().rectangle();
+        let layer = session
+            .content
+            .value_mut(shape.id())
+            .map_err(|_| EngineError::application_error("Invalid ID in pending step"))?;
+        let ghost = layer.ghost.as_mut().ok_or(EngineError::user_error(
+            "Can't call expand without previous step matching up",
+        ))?;
+        // everything below is in image coordinates
+        let pos = layer.attr.pos;
+        let offset = Point::new(-pos.x as f64, -pos.y as f64);
+        ghost.img.clean(&(&previous - &pos));
+        if let Some(fill) = &style.fill {
+            let path = outline.fill.translate(offset);
+            ghost
+                .img
+                .fill_path(&path, FillRule::NonZero, fill, style.antialias);
+        }
+        if let Some(stroke) = &style.stroke {
+            let contours: Vec<Vec<Point>> = outline
+                .stroke
+                .iter()
+                .map(|c| c.iter().map(|p| *p + offset).collect())
+                .collect();
+            ghost
+                .img
+                .fill_polygons(&contours, FillRule::NonZero, stroke, style.antialias);
+        }
+        // damage in global coordinates, constraint to root area
+        let damage = Rectangle::intersect(&Rectangle::bounding(&previous, &bounds), root);
+        utils::propagate_damage(
+            &mut session.blender,
+            &mut session.content,
+            shape.id(),
+            &damage,
+        )
+    }
+
+    fn finish(&self, session: &mut Engine) -> Result<(), EngineError> {
+        utils::merge_ghost(&mut session.blender, &mut session.content, self.id())?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id())?;
+        session.context.pending_step = None;
+        Ok(())
+    }
+
+    fn break_up(&self) -> Vec<Position> {
+        self.track().clone()
+    }
+}
+
+impl Outline {
+    /// Pixels touched by the outline
+    fn bounds(&self) -> Rectangle {
+        let fill = self.fill.bounds();
+        let stroke = Rectangle::covering(self.stroke.iter().flatten().copied());
+        match (
+            fill.size.width * fill.size.height,
+            stroke.size.width * stroke.size.height,
+        ) {
+            (0, _) => stroke,
+            (_, 0) => fill,
+            _ => Rectangle::bounding(&fill, &stroke),
+        }
+    }
+
+    /// Outline of a closed shape inside the box spanned by the first and last position of the track.
+    /// `path` gives the shape for a box that is inset by the given amount,
+    /// such that the stroke along it stays within the spanned box.
+    pub fn in_box(
+        track: &[Position],
+        style: &ShapeStyle,
+        join: Join,
+        path: impl Fn(Point, Point, f64) -> Path,
+    ) -> Self {
+        let (Some(a), Some(b)) = (track.first(), track.last()) else {
+            return Outline::default();
+        };
+        let min = Point::new(a.x.min(b.x) as f64, a.y.min(b.y) as f64);
+        let max = Point::new(a.x.max(b.x) as f64 + 1.0, a.y.max(b.y) as f64 + 1.0);
+        let stroked = style.stroke.is_some() && style.stroke_width > 0.0;
+        let inset = if stroked {
+            style.stroke_width / 2.0
+        } else {
+            0.0
+        };
+        let inset = inset.min((max.x - min.x) / 2.0).min((max.y - min.y) / 2.0);
+        let fill = path(
+            min + Point::new(inset, inset),
+            max - Point::new(inset, inset),
+            inset,
+        );
+        let stroke = if stroked {
+            fill.stroke(&StrokeStyle::new(style.stroke_width, join, Cap::Butt))
+        } else {
+            vec![]
+        };
+        Outline { fill, stroke }
+    }
+
+    /// Outline of a line through the centers of the pixels, with round joins and caps
+    pub fn through(track: &[Position], style: &ShapeStyle, closed: bool) -> Self {
+        let points: Vec<Point> = track.iter().map(Point::center_of).collect();
+        let path = Path::polyline(&points, closed);
+        let stroke = path.stroke(&StrokeStyle::new(
+            style.stroke_width,
+            Join::Round,
+            Cap::Round,
+        ));
+        Outline {
+            fill: if closed { path } else { Path::new() },
+            stroke,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Largest radius of a box blur
+const MAX_RADIUS: u32 = 1000;
+
+/// Mean over a square of `2 * radius + 1` pixels
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectBlurBox {
+    pub id: usize,
+    pub radius: u32,
+}
+
+impl IStep for EffectBlurBox {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if !(1..=MAX_RADIUS).contains(&self.radius) {
+            return Err(EngineError::user_error(
+                "The radius of a blur has to be from 1 to 1000",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.box_blur(self.radius)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Largest standard deviation of a blur, anything wider only flattens the layer
+pub(super) const MAX_SIGMA: f64 = 500.0;
+
+/// Gaussian blur, the standard deviation is given in pixels
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectBlurGaussian {
+    pub id: usize,
+    pub sigma: f64,
+}
+
+impl IStep for EffectBlurGaussian {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if !(self.sigma > 0.0 && self.sigma <= MAX_SIGMA) {
+            return Err(EngineError::user_error(
+                "The standard deviation of a blur has to be positive and at most 500",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.gaussian_blur(self.sigma)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn blur_within_selection() {
+        let mut state = Engine::new(20, 20);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [10, 20],
+            "position": [5, 0], "color": "#ffffffff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [0, 0], "to": [19, 9] } }"#;
+        state.perform(&step(select)).unwrap();
+        let blur = r#"{ "type": "effect/blur/gaussian", "id": 1, "sigma": 1e9 }"#;
+        assert!(state.perform(&step(blur)).is_err());
+        let blur = r#"{ "type": "effect/blur/gaussian", "id": 1, "sigma": 2.0 }"#;
+        state.perform(&step(blur)).unwrap();
+        let img = &state.content.get_value(1).unwrap().img;
+        // transparency outside of the layer doesn't come in, the color stays white
+        assert_eq!(img.get(0, 5), [1.0; 4]);
+        assert_eq!(img.get(0, 15), [1.0; 4]);
+
+        let invalid = r#"{ "type": "effect/blur/gaussian", "id": 1, "sigma": 0.0 }"#;
+        assert!(state.perform(&step(invalid)).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Blurs along a line, the angle is in degrees counterclockwise from the x-axis
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectBlurMotion {
+    pub id: usize,
+    pub angle: f64,
+    pub distance: f64,
+}
+
+impl IStep for EffectBlurMotion {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if !(self.distance > 0.0 && self.distance.is_finite() && self.angle.is_finite()) {
+            return Err(EngineError::user_error(
+                "The distance of a motion blur has to be positive",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.motion_blur(self.angle, self.distance)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,40 @@
+use common::Position;
+use imagine::RadialBlurKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Spin or zoom blur around a center in global coordinates.
+/// Spins take the angle in degrees as amount, zooms the fraction of the distance to the center.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectBlurRadial {
+    pub id: usize,
+    pub center: Position,
+    pub kind: RadialBlurKind,
+    pub amount: f64,
+}
+
+impl IStep for EffectBlurRadial {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let valid = match self.kind {
+            RadialBlurKind::Spin => self.amount > 0.0 && self.amount <= 360.0,
+            RadialBlurKind::Zoom => self.amount > 0.0 && self.amount <= 1.0,
+        };
+        if !valid {
+            return Err(EngineError::user_error(
+                "The amount of a radial blur is out of range",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        let center = self.center - layer.attr.pos; // center in image coordinates
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.radial_blur(center, self.kind, self.amount)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,27 @@
+use imagine::ColorBalance;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Shifts the colors of shadows, midtones and highlights
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorBalance {
+    pub id: usize,
+    #[serde(flatten)]
+    pub balance: ColorBalance,
+}
+
+impl IStep for EffectColorBalance {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.color_balance(&self.balance)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Brightness and contrast from -1 to 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorBrightnessContrast {
+    pub id: usize,
+    #[serde(default)]
+    pub brightness: f64,
+    #[serde(default)]
+    pub contrast: f64,
+}
+
+impl IStep for EffectColorBrightnessContrast {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.brightness_contrast(self.brightness, self.contrast)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
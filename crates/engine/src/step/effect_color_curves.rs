@@ -0,0 +1,27 @@
+use imagine::Curves;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Tone curves through control points
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorCurves {
+    pub id: usize,
+    #[serde(flatten)]
+    pub curves: Curves,
+}
+
+impl IStep for EffectColorCurves {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.curves(&self.curves)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
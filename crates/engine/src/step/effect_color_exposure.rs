@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Exposure in stops and offset in linear light, followed by a gamma correction
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorExposure {
+    pub id: usize,
+    pub exposure: f64,
+    #[serde(default)]
+    pub offset: f64,
+    #[serde(default = "EffectColorExposure::neutral_gamma")]
+    pub gamma: f64,
+}
+
+impl EffectColorExposure {
+    fn neutral_gamma() -> f64 {
+        1.0
+    }
+}
+
+impl IStep for EffectColorExposure {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.gamma <= 0.0 {
+            return Err(EngineError::user_error("The gamma has to be positive"));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.exposure(self.exposure, self.offset, self.gamma)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -1,3 +1,4 @@
+use imagine::GrayscaleMethod;
 use serde::{Deserialize, Serialize};
 
 use crate::{utils, EngineError};
@@ -7,16 +8,43 @@ use super::IStep;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EffectColorGrayscale {
     pub id: usize,
+    #[serde(default)]
+    pub method: GrayscaleMethod,
 }
 
 impl IStep for EffectColorGrayscale {
     fn perform_on(&self, session: &mut crate::Engine) -> Result<(), crate::EngineError> {
-        (session.content)
+        let layer = (session.content)
             .value_mut(self.id)
-            .map_err(EngineError::from)?
-            .img
-            .grayscale();
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.grayscale(self.method)
+        })?;
         utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn grayscale_within_selection() {
+        let mut state = Engine::new(20, 20);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#ff000080", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [0, 0], "to": [9, 19] } }"#;
+        state.perform(&step(select)).unwrap();
+        let gray = r#"{ "type": "effect/color/grayscale", "id": 1, "method": "red" }"#;
+        state.perform(&step(gray)).unwrap();
+        let img = &state.content.get_value(1).unwrap().img;
+        let (r, g, b, a) = (255, 255, 255, 128);
+        assert_eq!(img.pixel(5, 5), Color { r, g, b, a });
+        assert_eq!(img.pixel(15, 5), Color::RED.with_alpha(128));
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Rotates the hue by degrees, saturation and lightness are changed from -1 to 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorHueSaturation {
+    pub id: usize,
+    #[serde(default)]
+    pub hue: f64,
+    #[serde(default)]
+    pub saturation: f64,
+    #[serde(default)]
+    pub lightness: f64,
+}
+
+impl IStep for EffectColorHueSaturation {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.hue_saturation(self.hue, self.saturation, self.lightness)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
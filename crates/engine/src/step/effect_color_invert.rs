@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Inverts the colors, alpha is kept
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorInvert {
+    pub id: usize,
+}
+
+impl IStep for EffectColorInvert {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| img.invert())?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
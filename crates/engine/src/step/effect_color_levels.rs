@@ -0,0 +1,74 @@
+use imagine::Levels;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Input range and gamma of all channels and of the single channels
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorLevels {
+    pub id: usize,
+    #[serde(flatten)]
+    pub levels: Levels,
+}
+
+impl IStep for EffectColorLevels {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if !self.levels.is_valid() {
+            return Err(EngineError::user_error(
+                "The gamma of levels has to be positive",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.levels(&self.levels)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::test_util::step, Engine};
+
+    fn engine_with_gray_layer() -> Engine {
+        let mut state = Engine::new(10, 10);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#404040ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        state
+    }
+
+    #[test]
+    fn levels_as_effect_and_adjustment() {
+        let mut state = engine_with_gray_layer();
+        let levels = r#"{ "type": "effect/color/levels", "id": 1,
+            "rgb": { "black": 0, "white": 128, "gamma": 1.0 }, "blue": { "black": 64, "white": 255, "gamma": 1.0 } }"#;
+        state.perform(&step(levels)).unwrap();
+        let (r, g, b, a) = (128, 128, 0, 255);
+        assert_eq!(
+            state.content.root_value().img.pixel(0, 0),
+            Color { r, g, b, a }
+        );
+
+        let invalid = r#"{ "type": "effect/color/levels", "id": 1, "rgb": { "black": 0, "white": 255, "gamma": 0 } }"#;
+        assert!(state.perform(&step(invalid)).is_err());
+        let invalid = r#"{ "type": "effect/color/posterize", "id": 1, "levels": 1 }"#;
+        assert!(state.perform(&step(invalid)).is_err());
+
+        let invert = r#"{ "type": "layer/create/adjustment", "move_idx": null, "name": null,
+            "adjustment": { "effect": "color/invert" } }"#;
+        state.perform(&step(invert)).unwrap();
+        let (r, g, b, a) = (127, 127, 255, 255);
+        assert_eq!(
+            state.content.root_value().img.pixel(0, 0),
+            Color { r, g, b, a }
+        );
+    }
+}
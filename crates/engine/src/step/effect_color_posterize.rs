@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Reduces every channel to a number of evenly spaced values
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorPosterize {
+    pub id: usize,
+    pub levels: u32,
+}
+
+impl IStep for EffectColorPosterize {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.levels < 2 {
+            return Err(EngineError::user_error(
+                "Posterizing needs at least 2 levels",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.posterize(self.levels)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Black and white by luminance, the level is given on the 8-bit scale
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorThreshold {
+    pub id: usize,
+    pub level: f64,
+}
+
+impl IStep for EffectColorThreshold {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.threshold(self.level)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
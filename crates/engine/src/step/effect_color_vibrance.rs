@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Saturates muted colors more than saturated ones, from -1 to 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectColorVibrance {
+    pub id: usize,
+    pub amount: f64,
+}
+
+impl IStep for EffectColorVibrance {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.vibrance(self.amount)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,66 @@
+use imagine::Convolution;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Convolution with a user defined kernel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectConvolve {
+    pub id: usize,
+    #[serde(flatten)]
+    pub convolution: Convolution,
+}
+
+impl IStep for EffectConvolve {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        self.convolution
+            .validate()
+            .map_err(EngineError::user_error)?;
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.convolve(&self.convolution)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn malformed_kernels_are_rejected() {
+        let mut state = Engine::new(4, 4);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        for kernel in [
+            r#""kernel": []"#,
+            r#""kernel": [[1, 2], [3]]"#,
+            r#""kernel": [[1]], "divisor": 0"#,
+            r#""kernel": [[1]], "channels": { "red": false, "green": false, "blue": false, "alpha": false }"#,
+        ] {
+            let json = format!(r#"{{ "type": "effect/convolve", "id": 1, {kernel} }}"#);
+            assert!(state.perform(&step(&json)).is_err());
+        }
+        assert_eq!(
+            state.content.get_value(1).unwrap().img.pixel(0, 0),
+            Color::RED
+        );
+
+        let json = r#"{ "type": "effect/convolve", "id": 1, "kernel": [[0, 0, 0], [0, 0, 1]],
+            "edge": "transparent", "channels": { "alpha": true } }"#;
+        state.perform(&step(json)).unwrap();
+        let img = &state.content.get_value(1).unwrap().img;
+        assert_eq!(img.pixel(3, 0).a, 0);
+        assert_eq!(img.pixel(3, 3).a, 0);
+        assert_eq!(img.pixel(2, 2), Color::RED);
+    }
+}
@@ -0,0 +1,27 @@
+use imagine::EdgeOperator;
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Replaces the colors with the strength of the edges
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectEdgeDetect {
+    pub id: usize,
+    #[serde(default)]
+    pub operator: EdgeOperator,
+}
+
+impl IStep for EffectEdgeDetect {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.detect_edges(self.operator)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -17,7 +17,9 @@ impl IStep for EffectNoiseGaussian {
     fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
         let mut cursor = Cursor::new(&mut session.content, self.id).map_err(EngineError::from)?;
         let layer = cursor.value_mut();
-        layer.img.gaussian_noise(self.mean, self.stddev, self.seed);
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.gaussian_noise(self.mean, self.stddev, self.seed)
+        })?;
         utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
         Ok(())
     }
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{utils, Engine, EngineError};
+
+use super::{effect_blur_gaussian::MAX_SIGMA, IStep};
+
+/// Sharpens by adding the difference to a gaussian blur.
+/// Differences below the threshold on the 8-bit scale are left alone to keep noise down.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectSharpenUnsharp {
+    pub id: usize,
+    pub sigma: f64,
+    pub amount: f64,
+    #[serde(default)]
+    pub threshold: f64,
+}
+
+impl IStep for EffectSharpenUnsharp {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if !(self.sigma > 0.0 && self.sigma <= MAX_SIGMA) {
+            return Err(EngineError::user_error(
+                "The standard deviation of a blur has to be positive and at most 500",
+            ));
+        }
+        if !(self.amount >= 0.0 && (0.0..=255.0).contains(&self.threshold)) {
+            return Err(EngineError::user_error(
+                "Unsharp masks need a positive amount and a threshold from 0 to 255",
+            ));
+        }
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        utils::apply_effect(session.selection.as_ref(), layer, |img| {
+            img.unsharp_mask(self.sigma, self.amount, self.threshold)
+        })?;
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    layer::{Adjustment, LayerFlag},
+    utils, Engine, EngineError,
+};
+
+use super::IStep;
+
+/// Changes the effect of an adjustment layer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerAdjustment {
+    pub id: usize,
+    pub adjustment: Adjustment,
+}
+
+impl IStep for LayerAdjustment {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        self.adjustment.check()?;
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        if !matches!(layer.flag, LayerFlag::Adjustment(_)) {
+            return Err(EngineError::user_error("Layer is not an adjustment layer"));
+        }
+        layer.flag = LayerFlag::Adjustment(self.adjustment.clone());
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{layer::LayerFlag, step::test_util::step, Engine};
+
+    const NOISE: &str = r#"{ "effect": "noise/gaussian", "mean": 0.0, "stddev": 60.0, "seed": 3 }"#;
+
+    fn engine_with_adjustment() -> Engine {
+        let mut state = Engine::new(20, 20);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#808080ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let json = format!(
+            r#"{{ "type": "layer/create/adjustment", "move_idx": null, "name": null, "adjustment": {NOISE} }}"#
+        );
+        state.perform(&step(&json)).unwrap();
+        state
+    }
+
+    #[test]
+    fn applies_to_layers_beneath() {
+        let mut state = engine_with_adjustment();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.img.pixel(3, 3).r, 128);
+        let noisy = state.content.root_value().img.clone();
+        assert!((0..20).any(|x| noisy.pixel(x, 3) != layer.img.pixel(x, 3)));
+
+        // follows changes of the layers beneath, also in the preview
+        let paint = step(
+            r##"{ "type": "draw/rectangle", "id": 1, "track": [[0, 0], [19, 19]],
+            "style": { "stroke_width": 0, "stroke": null, "fill": "#000000ff", "antialias": false, "mode": "remove" } }"##,
+        );
+        state.start_step(&paint).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(19.0, 19.0).unwrap();
+        assert_eq!(state.content.root_value().img.pixel(3, 3).a, 0);
+        state.finish_step().unwrap();
+        assert_eq!(state.content.root_value().img.pixel(3, 3).a, 0);
+        state.undo().unwrap();
+        assert_eq!(state.content.root_value().img, noisy);
+
+        let hide = r#"{ "type": "layer/attr", "id": 2, "visible": false }"#;
+        state.perform(&step(hide)).unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(state.content.root_value().img, layer.img);
+    }
+
+    #[test]
+    fn edit_parameters() {
+        let mut state = engine_with_adjustment();
+        let noisy = state.content.root_value().img.clone();
+        let json = r#"{ "type": "layer/adjustment", "id": 2,
+            "adjustment": { "effect": "noise/gaussian", "mean": 0.0, "stddev": 60.0, "seed": 4 } }"#;
+        state.perform(&step(json)).unwrap();
+        assert_ne!(state.content.root_value().img, noisy);
+        let transparent = r#"{ "type": "layer/attr", "id": 2, "alpha": 0.0 }"#;
+        state.perform(&step(transparent)).unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(state.content.root_value().img, layer.img);
+        assert!(matches!(
+            state.content.get_value(2).unwrap().flag,
+            LayerFlag::Adjustment(_)
+        ));
+        let not_adjustment = r#"{ "type": "layer/adjustment", "id": 1,
+            "adjustment": { "effect": "color/grayscale" } }"#;
+        assert!(state.perform(&step(not_adjustment)).is_err());
+    }
+}
@@ -3,7 +3,7 @@ use common::Position;
 use imagine::BlendMode;
 use serde::{Deserialize, Serialize};
 
-use crate::{utils, Engine, EngineError};
+use crate::{layer::GroupMode, utils, Engine, EngineError};
 
 use super::IStep;
 
@@ -15,6 +15,8 @@ pub struct LayerAttributes {
     mode: Option<BlendMode>,
     visible: Option<bool>,
     name: Option<String>,
+    clipped: Option<bool>,
+    group_mode: Option<GroupMode>,
 }
 
 impl IStep for LayerAttributes {
@@ -37,8 +39,61 @@ impl IStep for LayerAttributes {
             if let Some(name) = &self.name {
                 layer.name = name.clone();
             }
+            if let Some(clipped) = &self.clipped {
+                layer.attr.clipped = *clipped;
+            }
+            if let Some(group_mode) = &self.group_mode {
+                layer.attr.group_mode = *group_mode;
+            }
         }
         utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn clipped_to_layer_below() {
+        let mut state = Engine::new(20, 20);
+        let base = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [10, 10],
+            "position": [0, 0], "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(base)).unwrap();
+        let shading = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#000000ff", "name": null }"##;
+        state.perform(&step(shading)).unwrap();
+        let clip = r#"{ "type": "layer/attr", "id": 2, "clipped": true }"#;
+        state.perform(&step(clip)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::BLACK);
+        assert_eq!(img.pixel(15, 15), Color::TRANSPARENT);
+
+        // the clip follows changes of the base, also in the preview
+        let paint = step(
+            r##"{ "type": "draw/rectangle", "id": 1, "track": [[0, 0], [9, 9]],
+            "style": { "stroke_width": 0, "stroke": null, "fill": "#000000ff", "antialias": false, "mode": "remove" } }"##,
+        );
+        state.start_step(&paint).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(4.0, 4.0).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(2, 2), Color::TRANSPARENT);
+        assert_eq!(img.pixel(7, 7), Color::BLACK);
+        state.finish_step().unwrap();
+        assert_eq!(
+            state.content.root_value().img.pixel(2, 2),
+            Color::TRANSPARENT
+        );
+
+        let hide_base = r#"{ "type": "layer/attr", "id": 1, "visible": false }"#;
+        state.perform(&step(hide_base)).unwrap();
+        assert_eq!(
+            state.content.root_value().img.pixel(7, 7),
+            Color::TRANSPARENT
+        );
+    }
+}
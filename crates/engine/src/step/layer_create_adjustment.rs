@@ -0,0 +1,43 @@
+use imagine::Image;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::EngineError,
+    layer::{Adjustment, LayerFlag},
+    utils, Engine,
+};
+
+use super::IStep;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerCreateAdjustment {
+    pub move_idx: Option<isize>,
+    pub name: Option<String>,
+    pub adjustment: Adjustment,
+}
+
+impl IStep for LayerCreateAdjustment {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        self.adjustment.check()?;
+        // the content is rendered from the layers beneath
+        let (width, height) = session.size().into();
+        let idx = utils::add_layer(
+            session,
+            session.content.root,
+            &None,
+            Image::new_with_depth(width, height, session.depth()),
+            self.name.clone(),
+        )?;
+        session
+            .content
+            .value_mut(idx)
+            .map_err(EngineError::from)?
+            .flag = LayerFlag::Adjustment(self.adjustment.clone());
+        let move_idx = self.move_idx.unwrap_or(session.content.root as isize);
+        utils::spawn_layer(session, idx, move_idx)?;
+        // the layers beneath are only known after spawning
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, idx)?;
+        session.context.idx = Some(idx);
+        Ok(())
+    }
+}
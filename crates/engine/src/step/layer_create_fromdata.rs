@@ -18,7 +18,7 @@ impl IStep for LayerCreateFromData {
     fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
         let content = self
             .img
-            .to_image(&session.context.images)
+            .to_image(&session.context.images, session.working_space())
             .map_err(EngineError::from)?;
         let layer = utils::add_layer(
             session,
@@ -4,7 +4,7 @@ use imagine::{BlendMode, Image};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    layer::{Layer, LayerAttributes, LayerFlag},
+    layer::{GroupMode, Layer, LayerAttributes, LayerFlag},
     utils, Engine, EngineError,
 };
 
@@ -19,17 +19,20 @@ impl IStep for LayerCreateGroup {
     fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
         let size = session.size();
         let layer = Layer {
-            img: Image::new(size.width, size.height),
+            img: Image::new_with_depth(size.width, size.height, session.depth()),
             ghost: None,
             zombie: None,
             attr: LayerAttributes {
                 pos: Position::new(0, 0),
                 mode: BlendMode::Alpha,
                 alpha: 1.0,
+                clipped: false,
+                group_mode: GroupMode::Isolated,
             },
             flag: LayerFlag::Group,
             visible: true,
             name: "".to_string(),
+            mask: None,
         };
         let move_idx = self.move_idx.unwrap_or(session.content.root as isize);
         let root_idx = session.content.root;
@@ -43,3 +46,71 @@ impl IStep for LayerCreateGroup {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{step::test_util::step, Engine};
+
+    /// White layer beneath a group holding a red layer in difference mode
+    fn engine_with_group() -> Engine {
+        let mut state = Engine::new(20, 20);
+        let white = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#ffffffff", "name": null }"##;
+        state.perform(&step(white)).unwrap();
+        state
+            .perform(&step(
+                r#"{ "type": "layer/create/group", "move_idx": null }"#,
+            ))
+            .unwrap();
+        let red = r##"{ "type": "layer/create/empty", "move_idx": 2, "size": null,
+            "position": null, "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(red)).unwrap();
+        assert_eq!(state.content.get_parent(3).unwrap(), 2);
+        let difference = r#"{ "type": "layer/attr", "id": 3, "mode": "difference" }"#;
+        state.perform(&step(difference)).unwrap();
+        state
+    }
+
+    #[test]
+    fn isolated_and_pass_through() {
+        let mut state = engine_with_group();
+        // the red layer has nothing to differ from within the isolated group
+        let pixel = state.content.root_value().img.pixel(5, 5);
+        assert_eq!((pixel.r, pixel.g, pixel.b), (255, 0, 0));
+        let half = r#"{ "type": "layer/attr", "id": 2, "alpha": 0.5 }"#;
+        state.perform(&step(half)).unwrap();
+        let pixel = state.content.root_value().img.pixel(5, 5);
+        assert_eq!((pixel.r, pixel.g / 10), (255, 12));
+
+        let pass = r#"{ "type": "layer/attr", "id": 2, "group_mode": "pass_through" }"#;
+        state.perform(&step(pass)).unwrap();
+        let pixel = state.content.root_value().img.pixel(5, 5);
+        assert_eq!((pixel.r, pixel.g, pixel.b), (127, 255, 255));
+        let opaque = r#"{ "type": "layer/attr", "id": 2, "alpha": 1.0 }"#;
+        state.perform(&step(opaque)).unwrap();
+        let pixel = state.content.root_value().img.pixel(5, 5);
+        assert_eq!((pixel.r, pixel.g, pixel.b), (0, 255, 255));
+    }
+
+    #[test]
+    fn pass_through_preview() {
+        let mut state = engine_with_group();
+        let pass =
+            r#"{ "type": "layer/attr", "id": 2, "group_mode": "pass_through", "alpha": 0.5 }"#;
+        state.perform(&step(pass)).unwrap();
+        let paint = step(
+            r##"{ "type": "draw/rectangle", "id": 3, "track": [[0, 0], [9, 9]],
+            "style": { "stroke_width": 0, "stroke": null, "fill": "#0000ffff", "antialias": false, "mode": "alpha" } }"##,
+        );
+        state.start_step(&paint).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(9.0, 9.0).unwrap();
+        let preview = state.content.root_value().img.clone();
+        let pixel = preview.pixel(5, 5);
+        assert_eq!((pixel.r, pixel.g, pixel.b), (255, 255, 127));
+        let pixel = preview.pixel(15, 15);
+        assert_eq!((pixel.r, pixel.g, pixel.b), (127, 255, 255));
+        state.finish_step().unwrap();
+        assert_eq!(state.content.root_value().img, preview);
+    }
+}
@@ -0,0 +1,40 @@
+use common::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::EngineError,
+    layer::{LayerFlag, Text},
+    utils, Engine,
+};
+
+use super::IStep;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerCreateText {
+    pub move_idx: Option<isize>,
+    pub position: Option<Position>,
+    /// Defaults to the first line of the text
+    pub name: Option<String>,
+    pub text: Text,
+}
+
+impl IStep for LayerCreateText {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let content = self.text.render(&session.context.fonts)?;
+        let name = self.name.clone().or_else(|| {
+            let first_line = self.text.content.lines().next().unwrap_or_default();
+            Some(first_line.to_string()).filter(|n| !n.is_empty())
+        });
+        let idx = utils::add_layer(session, session.content.root, &self.position, content, name)?;
+        session
+            .content
+            .value_mut(idx)
+            .map_err(EngineError::from)?
+            .flag = LayerFlag::Text(self.text.clone());
+        let move_idx = self.move_idx.unwrap_or(session.content.root as isize);
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, idx)?;
+        utils::spawn_layer(session, idx, move_idx)?;
+        session.context.idx = Some(idx);
+        Ok(())
+    }
+}
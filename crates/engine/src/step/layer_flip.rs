@@ -25,10 +25,17 @@ impl IStep for LayerFlip {
             .content
             .value_mut(self.id)
             .map_err(EngineError::from)?;
+        utils::check_pixels_editable(layer)?;
         match self.direction {
             FlipDirection::Horizontally => layer.img.flip_horizontally(),
             FlipDirection::Vertically => layer.img.flip_vertically(),
         }
+        if let Some(mask) = &mut layer.mask {
+            match self.direction {
+                FlipDirection::Horizontally => mask.img.flip_horizontally(),
+                FlipDirection::Vertically => mask.img.flip_vertically(),
+            }
+        }
         utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
         Ok(())
     }
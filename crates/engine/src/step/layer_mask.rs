@@ -0,0 +1,183 @@
+use common::Position;
+use imagine::Mask;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    layer::{LayerFlag, LayerMask},
+    utils, Engine, EngineError,
+};
+
+use super::IStep;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum LayerMaskModification {
+    /// Adds a mask that reveals the selection, or everything if there is none.
+    /// Hides everything instead if `hide` is set.
+    #[serde(rename = "add")]
+    Add {
+        #[serde(default)]
+        hide: bool,
+    },
+
+    /// Throws the mask away, the layer is fully revealed again
+    #[serde(rename = "remove")]
+    Remove,
+
+    /// Hides what was revealed and the other way around
+    #[serde(rename = "invert")]
+    Invert,
+
+    /// Bakes the mask into the alpha of the pixels and removes it
+    #[serde(rename = "apply")]
+    Apply,
+
+    /// Applies the mask when compositing again
+    #[serde(rename = "enable")]
+    Enable,
+
+    /// Keeps the mask without applying it
+    #[serde(rename = "disable")]
+    Disable,
+
+    /// Makes painting target the mask, or the pixels again
+    #[serde(rename = "target")]
+    Target { mask: bool },
+}
+
+/// Changes the mask of a layer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerMaskModify {
+    pub id: usize,
+    pub modification: LayerMaskModification,
+}
+
+impl IStep for LayerMaskModify {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        if self.id == session.content.root {
+            return Err(EngineError::user_error("The root can't have a mask"));
+        }
+        let selection = session.selection.as_ref();
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        let (width, height) = layer.img.size().into();
+        match (&self.modification, layer.mask.as_mut()) {
+            (LayerMaskModification::Add { .. }, Some(_)) => {
+                return Err(EngineError::user_error("Layer already has a mask"));
+            }
+            (LayerMaskModification::Add { hide }, None) => {
+                let img = match selection {
+                    _ if *hide => Mask::new(width, height),
+                    // the selection is in global coordinates
+                    Some(selection) => selection
+                        .translated(Position::new(0, 0) - layer.attr.pos, (width, height).into()),
+                    None => Mask::full(width, height),
+                };
+                layer.mask = Some(LayerMask::new(img));
+            }
+            (_, None) => return Err(EngineError::user_error("Layer has no mask")),
+            (LayerMaskModification::Remove, Some(_)) => layer.mask = None,
+            (LayerMaskModification::Invert, Some(mask)) => mask.img.invert(),
+            (LayerMaskModification::Apply, Some(mask)) => {
+                if layer.flag != LayerFlag::Pixel {
+                    return Err(EngineError::user_error(
+                        "Only masks of pixel layers can be applied",
+                    ));
+                }
+                layer.img.apply_mask(&mask.img);
+                layer.mask = None;
+            }
+            (LayerMaskModification::Enable, Some(mask)) => mask.enabled = true,
+            (LayerMaskModification::Disable, Some(mask)) => mask.enabled = false,
+            (LayerMaskModification::Target { mask: target }, Some(mask)) => mask.edit = *target,
+        }
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{step::test_util::step, Engine};
+
+    fn mask(state: &mut Engine, modification: &str) {
+        let json =
+            format!(r#"{{ "type": "layer/mask", "id": 1, "modification": {modification} }}"#);
+        state.perform(&step(&json)).unwrap();
+    }
+
+    fn engine_with_red_layer() -> Engine {
+        let mut state = Engine::new(20, 20);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": "#ff0000ff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        state
+    }
+
+    #[test]
+    fn hide_and_reveal() {
+        let mut state = engine_with_red_layer();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [0, 0], "to": [9, 19] } }"#;
+        state.perform(&step(select)).unwrap();
+        mask(&mut state, r#"{ "type": "add" }"#);
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::RED);
+        assert_eq!(img.pixel(15, 5), Color::TRANSPARENT);
+        // the pixels themselves are untouched
+        assert_eq!(
+            state.content.get_value(1).unwrap().img.pixel(15, 5),
+            Color::RED
+        );
+        let again = r#"{ "type": "layer/mask", "id": 1, "modification": { "type": "add" } }"#;
+        assert!(state.perform(&step(again)).is_err());
+
+        mask(&mut state, r#"{ "type": "invert" }"#);
+        assert_eq!(
+            state.content.root_value().img.pixel(5, 5),
+            Color::TRANSPARENT
+        );
+        mask(&mut state, r#"{ "type": "disable" }"#);
+        assert_eq!(state.content.root_value().img.pixel(5, 5), Color::RED);
+        mask(&mut state, r#"{ "type": "enable" }"#);
+        mask(&mut state, r#"{ "type": "apply" }"#);
+        let layer = state.content.get_value(1).unwrap();
+        assert!(layer.mask.is_none());
+        assert_eq!(layer.img.pixel(5, 5).a, 0);
+        assert_eq!(layer.img.pixel(15, 5), Color::RED);
+        assert_eq!(state.content.root_value().img.pixel(15, 5), Color::RED);
+    }
+
+    #[test]
+    fn paint_on_mask() {
+        let mut state = engine_with_red_layer();
+        mask(&mut state, r#"{ "type": "add" }"#);
+        mask(&mut state, r#"{ "type": "target", "mask": true }"#);
+        let paint = step(
+            r##"{ "type": "draw/rectangle", "id": 1, "track": [[0, 0], [9, 9]],
+            "style": { "stroke_width": 0, "stroke": null, "fill": "#000000ff", "antialias": false, "mode": "alpha" } }"##,
+        );
+        state.start_step(&paint).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(9.0, 9.0).unwrap();
+        // the preview hides already
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::TRANSPARENT);
+        assert_eq!(img.pixel(15, 15), Color::RED);
+        state.finish_step().unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.img.pixel(5, 5), Color::RED);
+        assert_eq!(
+            state.content.root_value().img.pixel(5, 5),
+            Color::TRANSPARENT
+        );
+
+        mask(&mut state, r#"{ "type": "target", "mask": false }"#);
+        state.perform(&paint).unwrap();
+        mask(&mut state, r#"{ "type": "remove" }"#);
+        assert_eq!(state.content.root_value().img.pixel(5, 5), Color::BLACK);
+    }
+}
@@ -38,10 +38,16 @@ impl IStep for LayerMergeDown {
         if bottom.flag != LayerFlag::Pixel {
             return Ok(());
         }
+        // the mask of the top layer only survives baked into its pixels
+        let mut img = top.img;
+        if let Some(mask) = top.mask.as_ref().filter(|m| m.enabled) {
+            img.apply_mask(&mask.img);
+        }
         let ghost = GhostImage {
-            img: top.img,
+            img,
             mode: top.attr.mode,
             alpha: top.attr.alpha,
+            selection: None,
         };
         let zombie = bottom.img.clone();
         bottom.ghost = Some(ghost);
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::LayerFlag, Engine, EngineError};
+
+use super::IStep;
+
+/// Turns a text layer into a pixel layer, its text can't be edited anymore afterwards
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerRasterize {
+    pub id: usize,
+}
+
+impl IStep for LayerRasterize {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        if !matches!(layer.flag, LayerFlag::Text(_)) {
+            return Err(EngineError::user_error(
+                "Only text layers can be rasterized",
+            ));
+        }
+        // the image already holds the rendered text
+        layer.flag = LayerFlag::Pixel;
+        Ok(())
+    }
+}
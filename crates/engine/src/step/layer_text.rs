@@ -0,0 +1,183 @@
+use imagine::{FontDto, TextStyle};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::LayerFlag, utils, Engine, EngineError};
+
+use super::IStep;
+
+/// Edits a text layer, everything that is not given stays as it is
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerText {
+    pub id: usize,
+    pub content: Option<String>,
+    pub font: Option<FontDto>,
+    pub style: Option<TextStyle>,
+}
+
+impl IStep for LayerText {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let depth = session.depth();
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        let LayerFlag::Text(text) = &layer.flag else {
+            return Err(EngineError::user_error("Layer is not a text layer"));
+        };
+        let mut text = text.clone();
+        if let Some(content) = &self.content {
+            text.content = content.clone();
+        }
+        if let Some(font) = &self.font {
+            text.font = font.clone();
+        }
+        if let Some(style) = &self.style {
+            text.style = style.clone();
+        }
+        layer.img = text.render(&session.context.fonts)?.into_depth(depth);
+        layer.flag = LayerFlag::Text(text);
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use common::{Color, Position};
+
+    use crate::{layer::LayerFlag, step::test_util::step, Engine, Step};
+
+    fn create_text() -> Step {
+        let json = r##"{
+            "type": "layer/create/text",
+            "move_idx": null,
+            "position": [2, 3],
+            "name": null,
+            "text": {
+                "content": "Hi\nthere",
+                "font": { "src": "bundled" },
+                "style": { "size": 16.0, "color": "#ff0000ff", "align": "center" }
+            }
+        }"##;
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn create_edit_and_rasterize() {
+        let mut state = Engine::new(100, 60);
+        let idx = state.perform(&create_text()).unwrap().unwrap();
+        let layer = state.content.get_value(idx).unwrap();
+        assert_eq!(layer.name, "Hi");
+        assert_eq!(layer.attr.pos, Position::new(2, 3));
+        assert!(matches!(&layer.flag, LayerFlag::Text(t) if t.content == "Hi\nthere"));
+        let (width, height) = (layer.img.width(), layer.img.height());
+        assert!(state.content.root_value().img.pixel(1, 3) == Color::TRANSPARENT);
+
+        let edit = format!(r#"{{ "type": "layer/text", "id": {idx}, "content": "Hi" }}"#);
+        state
+            .perform(&serde_json::from_str(&edit).unwrap())
+            .unwrap();
+        let layer = state.content.get_value(idx).unwrap();
+        assert!(layer.img.width() < width && layer.img.height() < height);
+        let root = &state.content.root_value().img;
+        let covered = (0..100).flat_map(|x| (0..60).map(move |y| (x, y)));
+        let covered: Vec<(u32, u32)> = covered.filter(|(x, y)| root.pixel(*x, *y).a > 0).collect();
+        assert!(covered.iter().all(|(_, y)| *y < 3 + layer.img.height()));
+
+        let rasterize = format!(r#"{{ "type": "layer/rasterize", "id": {idx} }}"#);
+        state
+            .perform(&serde_json::from_str(&rasterize).unwrap())
+            .unwrap();
+        assert_eq!(state.content.get_value(idx).unwrap().flag, LayerFlag::Pixel);
+        assert!(state
+            .perform(&serde_json::from_str(&edit).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn pixels_change_only_after_rasterization() {
+        let mut state = Engine::new(100, 60);
+        let idx = state.perform(&create_text()).unwrap().unwrap();
+        let fill = step(&format!(
+            r##"{{ "type": "draw/fill", "id": {idx}, "position": [0, 0], "color": "#0000ffff",
+                "tolerance": 0.0, "contiguous": true, "mode": "alpha" }}"##
+        ));
+        let invert = step(&format!(
+            r#"{{ "type": "effect/color/invert", "id": {idx} }}"#
+        ));
+        let flip = step(&format!(
+            r#"{{ "type": "layer/flip", "id": {idx}, "direction": "horizontally" }}"#
+        ));
+        for pixel_change in [&fill, &invert, &flip] {
+            assert!(state.perform(pixel_change).is_err());
+        }
+        state
+            .perform(&step(&format!(
+                r#"{{ "type": "layer/rasterize", "id": {idx} }}"#
+            )))
+            .unwrap();
+        for pixel_change in [&fill, &invert, &flip] {
+            state.perform(pixel_change).unwrap();
+        }
+    }
+
+    #[test]
+    fn style_is_bounded() {
+        let mut state = Engine::new(100, 60);
+        let idx = state.perform(&create_text()).unwrap().unwrap();
+        for style in [
+            r##"{ "size": 0.0, "color": "#ff0000ff" }"##,
+            r##"{ "size": 1e9, "color": "#ff0000ff" }"##,
+            r##"{ "size": 16.0, "color": "#ff0000ff", "line_spacing": -1.0 }"##,
+            r##"{ "size": 16.0, "color": "#ff0000ff", "line_spacing": 1e9 }"##,
+        ] {
+            let edit = format!(r#"{{ "type": "layer/text", "id": {idx}, "style": {style} }}"#);
+            assert!(state.perform(&step(&edit)).is_err());
+        }
+        let edit = format!(
+            r##"{{ "type": "layer/text", "id": {idx}, "style": {{ "size": 24.0, "color": "#ff0000ff", "line_spacing": 1.5 }} }}"##
+        );
+        state.perform(&step(&edit)).unwrap();
+    }
+
+    #[test]
+    fn font_from_context() {
+        let mut state = Engine::new(100, 60);
+        let json = r##"{
+            "type": "layer/create/text",
+            "move_idx": null,
+            "position": null,
+            "name": "Title",
+            "text": {
+                "content": "Hi",
+                "font": { "src": "multipart", "data": "font" },
+                "style": { "size": 16.0, "color": "#ff0000ff" }
+            }
+        }"##;
+        let step: Step = serde_json::from_str(json).unwrap();
+        assert!(state.perform(&step).is_err());
+        state.set_context_font("font".to_string(), vec![0, 1, 2]);
+        assert!(state.perform(&step).is_err());
+        let font = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../imagine/res/fonts/DejaVuSans.ttf"
+        ))
+        .unwrap();
+        state.set_context_font("font".to_string(), font.clone());
+        let idx = state.perform(&step).unwrap().unwrap();
+        assert_eq!(state.content.get_value(idx).unwrap().name, "Title");
+
+        // restoring the session needs the font again
+        let create = r#"{ "type": "project/create", "size": [100, 60] }"#;
+        let steps = vec![serde_json::from_str(create).unwrap(), step];
+        assert!(Engine::reconstruct(&steps, HashMap::new(), HashMap::new()).is_err());
+        let fonts = HashMap::from([("font".to_string(), font)]);
+        let restored = Engine::reconstruct(&steps, HashMap::new(), fonts).unwrap();
+        assert_eq!(
+            restored.content.root_value().img,
+            state.content.root_value().img
+        );
+    }
+}
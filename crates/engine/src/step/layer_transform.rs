@@ -0,0 +1,348 @@
+use common::{Point, Position, Rectangle};
+use imagine::{Projection, Resampling};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer::LayerFlag, utils, Engine, EngineError, Step};
+
+use super::IncrementalStep;
+
+/// Scaling, rotation and skewing happen around the center of the layer
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum Transformation {
+    /// Factors along the axes, negative ones mirror
+    #[serde(rename = "scale")]
+    Scale { x: f64, y: f64 },
+
+    /// Angle in degrees, clockwise
+    #[serde(rename = "rotate")]
+    Rotate { angle: f64 },
+
+    /// Shear angles in degrees, `x` moves the bottom to the right and `y` the right side down
+    #[serde(rename = "skew")]
+    Skew { x: f64, y: f64 },
+
+    /// Where the corners of the layer end up in global coordinates, clockwise from the top left
+    #[serde(rename = "perspective")]
+    Perspective { corners: [Point; 4] },
+}
+
+impl Transformation {
+    fn check(&self) -> Result<(), EngineError> {
+        let valid = match self {
+            Transformation::Scale { x, y } => [x, y].iter().all(|f| f.is_finite() && **f != 0.0),
+            Transformation::Rotate { angle } => angle.is_finite(),
+            Transformation::Skew { x, y } => [x, y].iter().all(|a| a.abs() < 90.0),
+            Transformation::Perspective { corners } => corners
+                .iter()
+                .all(|corner| corner.x.is_finite() && corner.y.is_finite()),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(EngineError::user_error("Invalid transformation"))
+        }
+    }
+
+    /// Projection of the global coordinates of the layer with the given bounds
+    fn projection(&self, bounds: &Rectangle) -> Result<Projection, EngineError> {
+        let center = center_of(bounds);
+        let around = |projection: Projection| {
+            Projection::translate(-center.x, -center.y)
+                .then(&projection)
+                .then(&Projection::translate(center.x, center.y))
+        };
+        match self {
+            Transformation::Scale { x, y } => Ok(around(Projection::scale(*x, *y))),
+            Transformation::Rotate { angle } => Ok(around(Projection::rotate(*angle))),
+            Transformation::Skew { x, y } => Ok(around(Projection::skew(*x, *y))),
+            Transformation::Perspective { corners } => Projection::quad(bounds, *corners).ok_or(
+                EngineError::user_error("The corners have to form a convex quadrilateral"),
+            ),
+        }
+    }
+
+    /// The transformation after the handle at `grab` has been dragged to `to`
+    fn dragged(&self, bounds: &Rectangle, grab: Point, to: Point) -> Self {
+        let center = center_of(bounds);
+        let (from, delta) = (grab - center, to - grab);
+        // handles too close to the center don't give a direction
+        let lever = |length: f64| length.abs() >= 1.0;
+        match self {
+            Transformation::Scale { x, y } => {
+                let factor = |from: f64, to: f64| if lever(from) { to / from } else { 1.0 };
+                Transformation::Scale {
+                    x: x * factor(from.x, from.x + delta.x),
+                    y: y * factor(from.y, from.y + delta.y),
+                }
+            }
+            Transformation::Rotate { angle } => {
+                let to = from + delta;
+                let turn = to.y.atan2(to.x) - from.y.atan2(from.x);
+                Transformation::Rotate {
+                    angle: angle + turn.to_degrees(),
+                }
+            }
+            Transformation::Skew { x, y } => {
+                let shear = |angle: f64, delta: f64, from: f64| match lever(from) {
+                    true => (angle.to_radians().tan() + delta / from)
+                        .atan()
+                        .to_degrees(),
+                    false => angle,
+                };
+                Transformation::Skew {
+                    x: shear(*x, delta.x, from.y),
+                    y: shear(*y, delta.y, from.x),
+                }
+            }
+            Transformation::Perspective { corners } => {
+                let mut corners = *corners;
+                let distance = |i: &usize| corners[*i].distance_to(&grab);
+                if let Some(nearest) = (0..4).min_by(|a, b| distance(a).total_cmp(&distance(b))) {
+                    corners[nearest] = corners[nearest] + delta;
+                }
+                Transformation::Perspective { corners }
+            }
+        }
+    }
+}
+
+fn center_of(bounds: &Rectangle) -> Point {
+    Point::new(
+        bounds.position.x as f64 + bounds.size.width as f64 / 2.0,
+        bounds.position.y as f64 + bounds.size.height as f64 / 2.0,
+    )
+}
+
+/// State of a transformation that is being performed, kept in the context of the session
+#[derive(Debug, Clone)]
+pub(crate) struct PendingTransform {
+    /// Bounds of the layer before the transformation
+    bounds: Rectangle,
+
+    /// Where the handle was grabbed and the transformation at that moment
+    grab: Option<(Point, Transformation)>,
+}
+
+/// Transforms a pixel layer, the layer is resized to the new bounds.
+/// Performed incrementally the first position grabs a handle and the following ones drag it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayerTransform {
+    pub id: usize,
+    pub transformation: Transformation,
+    #[serde(default)]
+    pub resampling: Resampling,
+}
+
+impl LayerTransform {
+    /// Renders the transformation of the original layer, which is kept as zombie
+    fn render(&self, session: &mut Engine, bounds: &Rectangle) -> Result<(), EngineError> {
+        let (x, y) = (bounds.position.x as f64, bounds.position.y as f64);
+        let projection = Projection::translate(x, y).then(&self.transformation.projection(bounds)?);
+        let too_large = || EngineError::user_error("The transformed layer would be too large");
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        let original = layer.zombie.as_ref().ok_or(EngineError::application_error(
+            "Transforming without the original layer",
+        ))?;
+        let (img, pos) = original
+            .transformed(&projection, self.resampling)
+            .ok_or_else(too_large)?;
+        let mask = match &layer.mask {
+            Some(mask) => {
+                let original = mask.zombie.as_ref().unwrap_or(&mask.img);
+                let (mask, _) = original
+                    .transformed(&projection, self.resampling)
+                    .ok_or_else(too_large)?;
+                Some(mask)
+            }
+            None => None,
+        };
+        layer.img = img;
+        layer.attr.pos = pos;
+        if let (Some(layer_mask), Some(mask)) = (&mut layer.mask, mask) {
+            layer_mask.img = mask;
+        }
+        utils::propagate_changes_up(&mut session.blender, &mut session.content, self.id)
+    }
+}
+
+impl IncrementalStep for LayerTransform {
+    type Increment = Position;
+
+    fn start(&self, session: &mut Engine) -> Result<(), EngineError> {
+        self.transformation.check()?;
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        match layer.flag {
+            LayerFlag::Pixel => {}
+            LayerFlag::Text(_) => {
+                return Err(EngineError::user_error(
+                    "Text layers have to be rasterized before they can be transformed",
+                ))
+            }
+            _ => {
+                return Err(EngineError::user_error(
+                    "Only pixel layers can be transformed",
+                ))
+            }
+        }
+        let bounds = layer.rectangle();
+        layer.zombie = Some(layer.img.clone());
+        if let Some(mask) = &mut layer.mask {
+            mask.zombie = Some(mask.img.clone());
+        }
+        session.context.transform = Some(PendingTransform {
+            bounds: bounds.clone(),
+            grab: None,
+        });
+        session.context.pending_step = Some(Step::LayerTransform(self.clone()));
+        if let Err(err) = self.render(session, &bounds) {
+            self.finish(session)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn extend(&self, session: &mut Engine, data: &Position) -> Result<(), EngineError> {
+        let Some(Step::LayerTransform(step)) = &session.context.pending_step else {
+            return Err(EngineError::user_error(
+                "Can't call expand without having initialized",
+            ));
+        };
+        let Some(pending) = session.context.transform.as_mut() else {
+            return Err(EngineError::application_error(
+                "Pending transformation without original bounds",
+            ));
+        };
+        let point = Point::center_of(data);
+        let Some((grab, initial)) = &pending.grab else {
+            pending.grab = Some((point, step.transformation.clone()));
+            return Ok(());
+        };
+        let transformation = initial.dragged(&pending.bounds, *grab, point);
+        let bounds = pending.bounds.clone();
+        let mut dragged = step.clone();
+        dragged.transformation = transformation;
+        // dragging through an impossible transformation keeps the last possible one
+        if dragged.transformation.check().is_err() || dragged.render(session, &bounds).is_err() {
+            return Ok(());
+        }
+        session.context.pending_step = Some(Step::LayerTransform(dragged));
+        Ok(())
+    }
+
+    fn finish(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let layer = session
+            .content
+            .value_mut(self.id)
+            .map_err(EngineError::from)?;
+        layer.zombie = None;
+        if let Some(mask) = &mut layer.mask {
+            mask.zombie = None;
+        }
+        session.context.pending_step = None;
+        session.context.transform = None;
+        Ok(())
+    }
+
+    fn break_up(&self) -> Vec<Position> {
+        // the transformation is rendered completely when starting
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Point};
+
+    use crate::{layer::LayerFlag, step::test_util::step, Engine};
+
+    use super::Transformation;
+
+    /// Engine with a layer at (2, 2), red on the two left columns and black on the others
+    fn transform_fixture(width: u32, height: u32) -> Engine {
+        let mut state = Engine::new(20, 20);
+        let json = format!(
+            r##"{{ "type": "layer/create/empty", "move_idx": null, "size": [{width}, {height}],
+            "position": [2, 2], "color": "#ff0000ff", "name": null }}"##
+        );
+        state.perform(&step(&json)).unwrap();
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [4, 0], "to": [20, 20] } }"#;
+        state.perform(&step(select)).unwrap();
+        let black = r#"{ "type": "effect/color/threshold", "id": 1, "level": 255 }"#;
+        state.perform(&step(black)).unwrap();
+        state
+    }
+
+    #[test]
+    fn quarter_turn_resizes_the_layer() {
+        let mut state = transform_fixture(4, 2);
+        let rotate = r#"{ "type": "layer/transform", "id": 1,
+            "transformation": { "type": "rotate", "angle": 90 }, "resampling": "bicubic" }"#;
+        state.perform(&step(rotate)).unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.rectangle(), (3, 1, 2, 4).into());
+        assert_eq!(layer.img.pixel(0, 0), Color::RED);
+        assert_eq!(layer.img.pixel(1, 3), Color::BLACK);
+        assert!(layer.zombie.is_none());
+        assert_eq!(state.content.root_value().img.pixel(4, 1), Color::RED);
+        assert_eq!(state.content.root_value().img.pixel(2, 2).a, 0);
+
+        let skew = r#"{ "type": "layer/transform", "id": 1,
+            "transformation": { "type": "skew", "x": 90, "y": 0 } }"#;
+        assert!(state.perform(&step(skew)).is_err());
+        let crossed = r#"{ "type": "layer/transform", "id": 1, "transformation": {
+            "type": "perspective", "corners": [[0, 0], [4, 4], [4, 0], [0, 4]] } }"#;
+        assert!(state.perform(&step(crossed)).is_err());
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.rectangle(), (3, 1, 2, 4).into());
+        assert!(layer.zombie.is_none());
+
+        let group = r#"{ "type": "layer/create/group", "move_idx": null }"#;
+        state.perform(&step(group)).unwrap();
+        let group_id = (state.content.traverse().into_iter())
+            .find(|idx| state.content.get_value(*idx).unwrap().flag == LayerFlag::Group)
+            .unwrap();
+        let rotate = format!(
+            r#"{{ "type": "layer/transform", "id": {group_id},
+            "transformation": {{ "type": "rotate", "angle": 90 }} }}"#
+        );
+        assert!(state.perform(&step(&rotate)).is_err());
+    }
+
+    #[test]
+    fn dragging_handles() {
+        let mut state = transform_fixture(5, 3);
+        let scale = r#"{ "type": "layer/transform", "id": 1,
+            "transformation": { "type": "scale", "x": 1, "y": 1 }, "resampling": "nearest" }"#;
+        state.start_step(&step(scale)).unwrap();
+        // grab 2 to the right and 1 below the center, then drag to three times that
+        state.extend_step(6.0, 4.0).unwrap();
+        state.extend_step(12.0, 9.0).unwrap();
+        state.extend_step(10.0, 6.0).unwrap();
+        state.finish_step().unwrap();
+        let scaled = state.content.get_value(1).unwrap().clone();
+        assert_eq!(scaled.rectangle(), (-3, -1, 15, 9).into());
+        assert_eq!(scaled.img.pixel(5, 8), Color::RED);
+        assert_eq!(scaled.img.pixel(6, 0), Color::BLACK);
+        assert!(state.context.pending_step.is_none());
+
+        // the history holds the result of the drag
+        state.undo().unwrap();
+        let layer = state.content.get_value(1).unwrap();
+        assert_eq!(layer.rectangle(), (2, 2, 5, 3).into());
+        state.redo().unwrap();
+        assert_eq!(state.content.get_value(1).unwrap().img, scaled.img);
+
+        let bounds = (0, 0, 4, 4).into();
+        let rotate = Transformation::Rotate { angle: 0.0 };
+        let dragged = rotate.dragged(&bounds, Point::new(4.0, 2.0), Point::new(2.0, 4.0));
+        assert!(matches!(dragged, Transformation::Rotate { angle } if (angle - 90.0).abs() < 1e-9));
+    }
+}
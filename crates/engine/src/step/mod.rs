@@ -1,28 +1,95 @@
 use common::{Position, Size};
+use imagine::{Compositing, Depth, WorkingSpace};
 use serde::{Deserialize, Serialize};
 
+mod canvas_crop;
+mod canvas_flip;
+mod canvas_resize;
+mod canvas_rotate;
+mod canvas_trim;
 mod compound;
+mod draw_arrow;
+mod draw_clone;
+mod draw_ellipse;
+mod draw_fill;
+mod draw_gradient;
 mod draw_lines;
+mod draw_path;
+mod draw_pencil;
+mod draw_polygon;
+mod draw_polyline;
+mod draw_rectangle;
+mod draw_rounded_rectangle;
+mod draw_shape;
+mod effect_blur_box;
+mod effect_blur_gaussian;
+mod effect_blur_motion;
+mod effect_blur_radial;
+mod effect_color_balance;
+mod effect_color_brightness_contrast;
+mod effect_color_curves;
+mod effect_color_exposure;
 mod effect_color_grayscale;
+mod effect_color_hue_saturation;
+mod effect_color_invert;
+mod effect_color_levels;
+mod effect_color_posterize;
+mod effect_color_threshold;
+mod effect_color_vibrance;
+mod effect_convolve;
+mod effect_edge_detect;
 mod effect_noise_gaussian;
+mod effect_noise_median;
+mod effect_sharpen_unsharp;
+mod layer_adjustment;
 mod layer_attributes;
+mod layer_create_adjustment;
 mod layer_create_empty;
 mod layer_create_fromdata;
 mod layer_create_group;
+mod layer_create_text;
 mod layer_duplicate;
 mod layer_flip;
+mod layer_mask;
 mod layer_merge_down;
 mod layer_move;
 mod layer_move_relative;
+mod layer_rasterize;
 mod layer_remove;
+mod layer_text;
+mod layer_transform;
+mod selection_create;
+mod selection_modify;
+mod symmetry;
 
 use crate::{error::EngineError, Engine};
 
+pub(crate) use self::{draw_pencil::PendingPencil, layer_transform::PendingTransform};
 pub use self::{
-    compound::Compound, draw_lines::DrawLine, effect_noise_gaussian::EffectNoiseGaussian,
-    layer_attributes::LayerAttributes, layer_create_empty::LayerCreateEmpty,
-    layer_create_fromdata::LayerCreateFromData, layer_create_group::LayerCreateGroup,
-    layer_move_relative::LayerMoveRelative, layer_remove::LayerRemove,
+    canvas_crop::CanvasCrop, canvas_flip::CanvasFlip, canvas_resize::CanvasResize,
+    canvas_rotate::CanvasRotate, canvas_trim::CanvasTrim, compound::Compound,
+    draw_arrow::DrawArrow, draw_clone::DrawClone, draw_ellipse::DrawEllipse, draw_fill::DrawFill,
+    draw_gradient::DrawGradient, draw_lines::DrawLine, draw_path::DrawPath,
+    draw_pencil::DrawPencil, draw_polygon::DrawPolygon, draw_polyline::DrawPolyline,
+    draw_rectangle::DrawRectangle, draw_rounded_rectangle::DrawRoundedRectangle,
+    effect_blur_box::EffectBlurBox, effect_blur_gaussian::EffectBlurGaussian,
+    effect_blur_motion::EffectBlurMotion, effect_blur_radial::EffectBlurRadial,
+    effect_color_balance::EffectColorBalance,
+    effect_color_brightness_contrast::EffectColorBrightnessContrast,
+    effect_color_curves::EffectColorCurves, effect_color_exposure::EffectColorExposure,
+    effect_color_hue_saturation::EffectColorHueSaturation, effect_color_invert::EffectColorInvert,
+    effect_color_levels::EffectColorLevels, effect_color_posterize::EffectColorPosterize,
+    effect_color_threshold::EffectColorThreshold, effect_color_vibrance::EffectColorVibrance,
+    effect_convolve::EffectConvolve, effect_edge_detect::EffectEdgeDetect,
+    effect_noise_gaussian::EffectNoiseGaussian, effect_noise_median::EffectNoiseMedian,
+    effect_sharpen_unsharp::EffectSharpenUnsharp, layer_adjustment::LayerAdjustment,
+    layer_attributes::LayerAttributes, layer_create_adjustment::LayerCreateAdjustment,
+    layer_create_empty::LayerCreateEmpty, layer_create_fromdata::LayerCreateFromData,
+    layer_create_group::LayerCreateGroup, layer_create_text::LayerCreateText,
+    layer_mask::LayerMaskModify, layer_move_relative::LayerMoveRelative,
+    layer_rasterize::LayerRasterize, layer_remove::LayerRemove, layer_text::LayerText,
+    layer_transform::LayerTransform, selection_create::SelectionCreate,
+    selection_modify::SelectionModify,
 };
 use self::{
     effect_color_grayscale::EffectColorGrayscale, layer_duplicate::LayerDuplicate,
@@ -48,14 +115,42 @@ pub trait IStep {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum Step {
-    /// Initializes a new project
+    /// Initializes a new project, compositing, depth and working space are kept for the whole session
     #[serde(rename = "project/create")]
-    ProjectCreate { size: Size },
+    ProjectCreate {
+        size: Size,
+        #[serde(default)]
+        compositing: Compositing,
+        #[serde(default)]
+        depth: Depth,
+        #[serde(default)]
+        working_space: WorkingSpace,
+    },
 
     /// Represents multiple steps that should be performed as one in history
     #[serde(rename = "compound")]
     Compound(Compound),
 
+    /// Changes the size of the canvas around an anchor
+    #[serde(rename = "canvas/resize")]
+    CanvasResize(CanvasResize),
+
+    /// Crops the canvas to a rectangle or to the selection
+    #[serde(rename = "canvas/crop")]
+    CanvasCrop(CanvasCrop),
+
+    /// Crops the canvas to its content without transparent or uniform borders
+    #[serde(rename = "canvas/trim")]
+    CanvasTrim(CanvasTrim),
+
+    /// Rotates the whole image by a multiple of 90 degrees
+    #[serde(rename = "canvas/rotate")]
+    CanvasRotate(CanvasRotate),
+
+    /// Flips the whole image
+    #[serde(rename = "canvas/flip")]
+    CanvasFlip(CanvasFlip),
+
     /// Creates a new empty layer
     #[serde(rename = "layer/create/empty")]
     LayerCreateEmpty(LayerCreateEmpty),
@@ -68,6 +163,30 @@ pub enum Step {
     #[serde(rename = "layer/create/group")]
     LayerCreateGroup(LayerCreateGroup),
 
+    /// Creates a new text layer
+    #[serde(rename = "layer/create/text")]
+    LayerCreateText(LayerCreateText),
+
+    /// Edits the text of a text layer
+    #[serde(rename = "layer/text")]
+    LayerText(LayerText),
+
+    /// Turns a text layer into a pixel layer
+    #[serde(rename = "layer/rasterize")]
+    LayerRasterize(LayerRasterize),
+
+    /// Creates a new adjustment layer
+    #[serde(rename = "layer/create/adjustment")]
+    LayerCreateAdjustment(LayerCreateAdjustment),
+
+    /// Changes the effect of an adjustment layer
+    #[serde(rename = "layer/adjustment")]
+    LayerAdjustment(LayerAdjustment),
+
+    /// Adds, changes or removes the mask of a layer
+    #[serde(rename = "layer/mask")]
+    LayerMaskModify(LayerMaskModify),
+
     /// Removes layers by id
     #[serde(rename = "layer/remove")]
     LayerRemove(LayerRemove),
@@ -96,17 +215,145 @@ pub enum Step {
     #[serde(rename = "layer/attr")]
     LayerAttributes(LayerAttributes),
 
+    /// Scales, rotates, skews or distorts the perspective of a layer
+    #[serde(rename = "layer/transform")]
+    LayerTransform(LayerTransform),
+
     /// Gaussian noise
     #[serde(rename = "effect/noise/gaussian")]
     EffectNoiseGaussian(EffectNoiseGaussian),
 
-    /// Gaussian noise
+    /// Grayscale by luminance, average or a single channel
     #[serde(rename = "effect/color/grayscale")]
     EffectNoiseGrayscale(EffectColorGrayscale),
 
+    /// Shifts the colors of shadows, midtones and highlights
+    #[serde(rename = "effect/color/balance")]
+    EffectColorBalance(EffectColorBalance),
+
+    /// Brightness and contrast
+    #[serde(rename = "effect/color/brightness_contrast")]
+    EffectColorBrightnessContrast(EffectColorBrightnessContrast),
+
+    /// Tone curves through control points
+    #[serde(rename = "effect/color/curves")]
+    EffectColorCurves(EffectColorCurves),
+
+    /// Exposure, offset and gamma
+    #[serde(rename = "effect/color/exposure")]
+    EffectColorExposure(EffectColorExposure),
+
+    /// Hue, saturation and lightness
+    #[serde(rename = "effect/color/hue_saturation")]
+    EffectColorHueSaturation(EffectColorHueSaturation),
+
+    /// Inverts the colors
+    #[serde(rename = "effect/color/invert")]
+    EffectColorInvert(EffectColorInvert),
+
+    /// Levels of all channels and of the single channels
+    #[serde(rename = "effect/color/levels")]
+    EffectColorLevels(EffectColorLevels),
+
+    /// Posterize
+    #[serde(rename = "effect/color/posterize")]
+    EffectColorPosterize(EffectColorPosterize),
+
+    /// Black and white by a threshold
+    #[serde(rename = "effect/color/threshold")]
+    EffectColorThreshold(EffectColorThreshold),
+
+    /// Vibrance
+    #[serde(rename = "effect/color/vibrance")]
+    EffectColorVibrance(EffectColorVibrance),
+
+    /// Gaussian blur
+    #[serde(rename = "effect/blur/gaussian")]
+    EffectBlurGaussian(EffectBlurGaussian),
+
+    /// Box blur
+    #[serde(rename = "effect/blur/box")]
+    EffectBlurBox(EffectBlurBox),
+
+    /// Blur along a line at an angle
+    #[serde(rename = "effect/blur/motion")]
+    EffectBlurMotion(EffectBlurMotion),
+
+    /// Spin or zoom blur around a center
+    #[serde(rename = "effect/blur/radial")]
+    EffectBlurRadial(EffectBlurRadial),
+
+    /// Unsharp mask
+    #[serde(rename = "effect/sharpen/unsharp_mask")]
+    EffectSharpenUnsharp(EffectSharpenUnsharp),
+
+    /// Median filter
+    #[serde(rename = "effect/noise/median")]
+    EffectNoiseMedian(EffectNoiseMedian),
+
+    /// Sobel or Laplacian edge detection
+    #[serde(rename = "effect/edge")]
+    EffectEdgeDetect(EffectEdgeDetect),
+
+    /// Convolution with a user defined kernel
+    #[serde(rename = "effect/convolve")]
+    EffectConvolve(EffectConvolve),
+
+    /// Selects an area or combines it with the current selection
+    #[serde(rename = "selection/create")]
+    SelectionCreate(SelectionCreate),
+
+    /// Changes the current selection
+    #[serde(rename = "selection/modify")]
+    SelectionModify(SelectionModify),
+
     // Lines
     #[serde(rename = "draw/line")]
     DrawLine(DrawLine),
+
+    /// Clone stamp and healing brush
+    #[serde(rename = "draw/clone")]
+    DrawClone(DrawClone),
+
+    /// Hard edged pencil for pixel art
+    #[serde(rename = "draw/pencil")]
+    DrawPencil(DrawPencil),
+
+    /// Rectangle
+    #[serde(rename = "draw/rectangle")]
+    DrawRectangle(DrawRectangle),
+
+    /// Rectangle with rounded corners
+    #[serde(rename = "draw/rectangle/rounded")]
+    DrawRoundedRectangle(DrawRoundedRectangle),
+
+    /// Ellipse
+    #[serde(rename = "draw/ellipse")]
+    DrawEllipse(DrawEllipse),
+
+    /// Closed polygon
+    #[serde(rename = "draw/polygon")]
+    DrawPolygon(DrawPolygon),
+
+    /// Open line through multiple points
+    #[serde(rename = "draw/polyline")]
+    DrawPolyline(DrawPolyline),
+
+    /// Arrow
+    #[serde(rename = "draw/arrow")]
+    DrawArrow(DrawArrow),
+
+    /// Fill and stroke of a vector path
+    #[serde(rename = "draw/path")]
+    DrawPath(DrawPath),
+
+    /// Paint bucket
+    #[serde(rename = "draw/fill")]
+    DrawFill(DrawFill),
+
+    /// Gradient over the whole layer
+    #[serde(rename = "draw/gradient")]
+    DrawGradient(DrawGradient),
 }
 
 /// A IncrementalStep is a way for steps to be incrementally build without having to perform another step.
@@ -155,25 +402,78 @@ impl Step {
             Step::LayerAttributes(s) => Box::new(s),
             Step::EffectNoiseGaussian(s) => Box::new(s),
             Step::DrawLine(s) => Box::new(s),
+            Step::DrawClone(s) => Box::new(s),
+            Step::DrawPencil(s) => Box::new(s),
+            Step::DrawRectangle(s) => Box::new(s),
+            Step::DrawRoundedRectangle(s) => Box::new(s),
+            Step::DrawEllipse(s) => Box::new(s),
+            Step::DrawPolygon(s) => Box::new(s),
+            Step::DrawPolyline(s) => Box::new(s),
+            Step::DrawArrow(s) => Box::new(s),
+            Step::DrawPath(s) => Box::new(s),
+            Step::DrawFill(s) => Box::new(s),
+            Step::DrawGradient(s) => Box::new(s),
             Step::Compound(s) => Box::new(s),
             Step::LayerCreateGroup(s) => Box::new(s),
+            Step::LayerCreateText(s) => Box::new(s),
+            Step::LayerText(s) => Box::new(s),
+            Step::LayerRasterize(s) => Box::new(s),
+            Step::LayerCreateAdjustment(s) => Box::new(s),
+            Step::LayerAdjustment(s) => Box::new(s),
+            Step::LayerMaskModify(s) => Box::new(s),
+            Step::SelectionCreate(s) => Box::new(s),
+            Step::SelectionModify(s) => Box::new(s),
             Step::LayerMoveRelative(s) => Box::new(s),
             Step::EffectNoiseGrayscale(s) => Box::new(s),
+            Step::EffectColorBalance(s) => Box::new(s),
+            Step::EffectColorBrightnessContrast(s) => Box::new(s),
+            Step::EffectColorCurves(s) => Box::new(s),
+            Step::EffectColorExposure(s) => Box::new(s),
+            Step::EffectColorHueSaturation(s) => Box::new(s),
+            Step::EffectColorInvert(s) => Box::new(s),
+            Step::EffectColorLevels(s) => Box::new(s),
+            Step::EffectColorPosterize(s) => Box::new(s),
+            Step::EffectColorThreshold(s) => Box::new(s),
+            Step::EffectColorVibrance(s) => Box::new(s),
+            Step::EffectBlurGaussian(s) => Box::new(s),
+            Step::EffectBlurBox(s) => Box::new(s),
+            Step::EffectBlurMotion(s) => Box::new(s),
+            Step::EffectBlurRadial(s) => Box::new(s),
+            Step::EffectSharpenUnsharp(s) => Box::new(s),
+            Step::EffectNoiseMedian(s) => Box::new(s),
+            Step::EffectEdgeDetect(s) => Box::new(s),
+            Step::EffectConvolve(s) => Box::new(s),
             Step::LayerFlip(s) => Box::new(s),
+            Step::LayerTransform(s) => Box::new(s),
             Step::LayerMergeDown(s) => Box::new(s),
             Step::LayerDuplicate(s) => Box::new(s),
-            Step::ProjectCreate { size: _size } => panic!(),
+            Step::CanvasResize(s) => Box::new(s),
+            Step::CanvasCrop(s) => Box::new(s),
+            Step::CanvasTrim(s) => Box::new(s),
+            Step::CanvasRotate(s) => Box::new(s),
+            Step::CanvasFlip(s) => Box::new(s),
+            Step::ProjectCreate { .. } => panic!(),
         }
     }
 
     pub fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
-        return self.as_step().perform_on(session);
+        self.as_step().perform_on(session)
     }
 
     pub fn as_extendable(&self) -> Option<Box<dyn IncrementalStep<Increment = Position>>> {
         match self {
             Step::DrawLine(s) => Some(Box::new(s.clone())),
+            Step::DrawClone(s) => Some(Box::new(s.clone())),
+            Step::DrawPencil(s) => Some(Box::new(s.clone())),
+            Step::DrawRectangle(s) => Some(Box::new(s.clone())),
+            Step::DrawRoundedRectangle(s) => Some(Box::new(s.clone())),
+            Step::DrawEllipse(s) => Some(Box::new(s.clone())),
+            Step::DrawPolygon(s) => Some(Box::new(s.clone())),
+            Step::DrawPolyline(s) => Some(Box::new(s.clone())),
+            Step::DrawArrow(s) => Some(Box::new(s.clone())),
+            Step::DrawGradient(s) => Some(Box::new(s.clone())),
             Step::LayerMoveRelative(s) => Some(Box::new(s.clone())),
+            Step::LayerTransform(s) => Some(Box::new(s.clone())),
             _ => None,
         }
     }
@@ -187,3 +487,35 @@ impl Step {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use common::{Color, Position, Size};
+
+    use crate::{step::LayerCreateEmpty, Engine, Step};
+
+    /// Parses a step from its JSON representation
+    pub(crate) fn step(json: &str) -> Step {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Engine with an empty layer covering the whole canvas
+    pub(crate) fn engine_with_layer(width: u32, height: u32) -> Engine {
+        let mut state = Engine::new(width, height);
+        let json = r#"{ "type": "layer/create/empty", "move_idx": null, "size": null,
+            "position": null, "color": null, "name": null }"#;
+        state.perform(&step(json)).unwrap();
+        state
+    }
+
+    /// Creates a layer of the given size at the position, filled with the color if there is one
+    pub(crate) fn create_empty_layer(color: Option<Color>, size: Size, position: Position) -> Step {
+        Step::LayerCreateEmpty(LayerCreateEmpty {
+            move_idx: None,
+            size: Some(size),
+            position: Some(position),
+            color,
+            name: None,
+        })
+    }
+}
@@ -0,0 +1,291 @@
+use common::{Color, FillRule, Path, Point, Position, TOLERANCE};
+use imagine::{ColorDistance, Mask};
+use serde::{Deserialize, Serialize};
+
+use crate::{Engine, EngineError};
+
+use super::{draw_fill::FillSample, IStep};
+
+/// Area to select, in global coordinates
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum SelectionShape {
+    /// Rectangle spanned by two corner pixels
+    #[serde(rename = "rectangle")]
+    Rectangle { from: Position, to: Position },
+
+    /// Ellipse within the rectangle spanned by two corner pixels
+    #[serde(rename = "ellipse")]
+    Ellipse { from: Position, to: Position },
+
+    /// Polygon lasso through the centers of the given pixels
+    #[serde(rename = "polygon")]
+    Polygon { points: Vec<Position> },
+
+    /// Freehand lasso along the track, closed from its end back to its start
+    #[serde(rename = "lasso")]
+    Lasso { track: Vec<Position> },
+
+    /// Magic wand, region of similar color around a position as for [DrawFill](super::DrawFill)
+    #[serde(rename = "wand")]
+    Wand {
+        id: usize,
+        position: Position,
+        tolerance: f64,
+        #[serde(default)]
+        distance: ColorDistance,
+        contiguous: bool,
+        #[serde(default)]
+        sample: FillSample,
+    },
+
+    /// Every pixel that differs at most by the tolerance from the color
+    #[serde(rename = "color_range")]
+    ColorRange {
+        id: usize,
+        color: Color,
+        tolerance: f64,
+        #[serde(default)]
+        distance: ColorDistance,
+        #[serde(default)]
+        sample: FillSample,
+    },
+}
+
+/// How the new area is combined with the current selection
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum SelectionOperation {
+    #[default]
+    #[serde(rename = "replace")]
+    Replace,
+
+    #[serde(rename = "add")]
+    Add,
+
+    #[serde(rename = "subtract")]
+    Subtract,
+
+    #[serde(rename = "intersect")]
+    Intersect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SelectionCreate {
+    pub shape: SelectionShape,
+    #[serde(default)]
+    pub operation: SelectionOperation,
+    #[serde(default)]
+    pub antialias: bool,
+}
+
+impl SelectionShape {
+    /// Selected area on a canvas of the given size
+    fn area(&self, session: &Engine, antialias: bool) -> Result<Mask, EngineError> {
+        let size = session.size();
+        let corners = |a: &Position, b: &Position| {
+            (
+                Point::new(a.x.min(b.x) as f64, a.y.min(b.y) as f64),
+                Point::new(a.x.max(b.x) as f64 + 1.0, a.y.max(b.y) as f64 + 1.0),
+            )
+        };
+        let path = match self {
+            SelectionShape::Rectangle { from, to } => {
+                let (min, max) = corners(from, to);
+                Path::rectangle(min, max)
+            }
+            SelectionShape::Ellipse { from, to } => {
+                let (min, max) = corners(from, to);
+                let radius = (max - min) * 0.5;
+                Path::ellipse(min + radius, radius.x, radius.y)
+            }
+            SelectionShape::Polygon { points: track } | SelectionShape::Lasso { track } => {
+                let points: Vec<Point> = track.iter().map(Point::center_of).collect();
+                Path::polyline(&points, true)
+            }
+            SelectionShape::Wand {
+                id,
+                position,
+                tolerance,
+                distance,
+                contiguous,
+                sample,
+            } => {
+                let (img, origin) = sample.image(session, *id)?;
+                let region =
+                    img.similar_region(&(*position - origin), *tolerance, *distance, *contiguous);
+                return Ok(smoothed(region, antialias).translated(origin, size));
+            }
+            SelectionShape::ColorRange {
+                id,
+                color,
+                tolerance,
+                distance,
+                sample,
+            } => {
+                let (img, origin) = sample.image(session, *id)?;
+                let region = img.color_range(color, *tolerance, *distance);
+                return Ok(smoothed(region, antialias).translated(origin, size));
+            }
+        };
+        let contours: Vec<Vec<Point>> = path
+            .flatten(TOLERANCE)
+            .into_iter()
+            .map(|polyline| polyline.points)
+            .collect();
+        let mut area = Mask::new(size.width, size.height);
+        area.fill_polygons(&contours, FillRule::NonZero, antialias);
+        Ok(area)
+    }
+}
+
+fn smoothed(region: Mask, antialias: bool) -> Mask {
+    if antialias {
+        region.antialiased()
+    } else {
+        region
+    }
+}
+
+impl IStep for SelectionCreate {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let (width, height) = session.size().into();
+        let area = self.shape.area(session, self.antialias)?;
+        let mut selection = session
+            .selection
+            .take()
+            .unwrap_or_else(|| Mask::new(width, height));
+        match self.operation {
+            SelectionOperation::Replace => selection = area,
+            SelectionOperation::Add => selection.add(&area),
+            SelectionOperation::Subtract => selection.subtract(&area),
+            SelectionOperation::Intersect => selection.intersect(&area),
+        }
+        session.selection = Some(selection);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{
+        step::test_util::{engine_with_layer, step},
+        Engine,
+    };
+
+    const FILL: &str = r##"{ "type": "draw/rectangle", "id": 1, "track": [[0, 0], [19, 19]],
+        "style": { "stroke_width": 0, "stroke": null, "fill": "#ff0000ff", "antialias": false, "mode": "alpha" } }"##;
+
+    #[test]
+    fn drawing_is_limited_to_selection() {
+        let mut state = engine_with_layer(20, 20);
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [2, 2], "to": [9, 9] } }"#;
+        state.perform(&step(select)).unwrap();
+        let subtract = r#"{ "type": "selection/create", "operation": "subtract",
+            "shape": { "type": "polygon", "points": [[5, 0], [5, 19], [19, 19], [19, 0]] } }"#;
+        state.perform(&step(subtract)).unwrap();
+        state.perform(&step(FILL)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(2, 2), Color::RED);
+        assert_eq!(img.pixel(4, 9), Color::RED);
+        assert_eq!(img.pixel(1, 1), Color::TRANSPARENT);
+        assert_eq!(img.pixel(6, 6), Color::TRANSPARENT);
+
+        // the selection is part of the history
+        state.undo().unwrap();
+        state.undo().unwrap();
+        state.perform(&step(FILL)).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(2, 2), Color::RED);
+        assert_eq!(img.pixel(6, 6), Color::RED);
+        assert_eq!(img.pixel(15, 15), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn preview_and_effects_are_limited_to_selection() {
+        let mut state = engine_with_layer(20, 20);
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "ellipse", "from": [0, 0], "to": [9, 9] } }"#;
+        state.perform(&step(select)).unwrap();
+        state.start_step(&step(FILL)).unwrap();
+        state.extend_step(0.0, 0.0).unwrap();
+        state.extend_step(19.0, 19.0).unwrap();
+        let img = &state.content.root_value().img;
+        assert_eq!(img.pixel(5, 5), Color::RED);
+        assert_eq!(img.pixel(0, 0), Color::TRANSPARENT);
+        assert_eq!(img.pixel(12, 12), Color::TRANSPARENT);
+        state.finish_step().unwrap();
+
+        let invert = r#"{ "type": "selection/modify", "modification": { "type": "invert" } }"#;
+        state.perform(&step(invert)).unwrap();
+        let noise = r#"{ "type": "effect/noise/gaussian", "id": 1, "mean": 100.0, "stddev": 1.0, "seed": 1 }"#;
+        state.perform(&step(noise)).unwrap();
+        let img = &state.content.get_value(1).unwrap().img;
+        assert_eq!(img.pixel(5, 5), Color::RED);
+        assert_ne!(img.pixel(15, 15), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn select_by_color() {
+        let mut state = Engine::new(20, 20);
+        let json = r##"{ "type": "layer/create/empty", "move_idx": null, "size": [6, 6],
+            "position": [10, 10], "color": "#0000ffff", "name": null }"##;
+        state.perform(&step(json)).unwrap();
+        let wand = r#"{ "type": "selection/create", "shape": { "type": "wand", "id": 1,
+            "position": [12, 12], "tolerance": 0, "contiguous": true } }"#;
+        state.perform(&step(wand)).unwrap();
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (10, 10, 6, 6).into()
+        );
+
+        // the transparent area around the layer in the composite
+        let range = r##"{ "type": "selection/create", "operation": "intersect",
+            "shape": { "type": "color_range", "id": 1, "color": "#00000000", "tolerance": 0,
+            "sample": "composite" } }"##;
+        state.perform(&step(range)).unwrap();
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (0, 0, 0, 0).into()
+        );
+        let wand = r#"{ "type": "selection/create", "shape": { "type": "wand", "id": 1,
+            "position": [0, 0], "tolerance": 0, "contiguous": false, "sample": "composite" } }"#;
+        state.perform(&step(wand)).unwrap();
+        let selection = state.selection.as_ref().unwrap();
+        assert_eq!(selection.bounds(), (0, 0, 20, 20).into());
+        assert_eq!(selection.get(&Position::new(12, 12)), 0);
+    }
+
+    #[test]
+    fn modifications() {
+        let mut state = engine_with_layer(20, 20);
+        let modify = |state: &mut Engine, modification: &str| {
+            let json =
+                format!(r#"{{ "type": "selection/modify", "modification": {modification} }}"#);
+            state.perform(&step(&json)).unwrap();
+        };
+        let select = r#"{ "type": "selection/create", "antialias": true,
+            "shape": { "type": "lasso", "track": [[5, 5], [5, 14], [14, 14], [14, 5]] } }"#;
+        state.perform(&step(select)).unwrap();
+        modify(&mut state, r#"{ "type": "grow", "amount": 2 }"#);
+        let selection = state.selection.as_ref().unwrap();
+        // the lasso runs through the centers of the edge pixels
+        assert_eq!(selection.get(&Position::new(4, 9)), 255);
+        assert_eq!(selection.get(&Position::new(3, 9)), 128);
+        assert_eq!(selection.get(&Position::new(2, 9)), 0);
+        modify(&mut state, r#"{ "type": "shrink", "amount": 4 }"#);
+        modify(&mut state, r#"{ "type": "feather", "radius": 2.0 }"#);
+        let selection = state.selection.as_ref().unwrap();
+        let edge = selection.get(&Position::new(7, 9));
+        assert!(edge > 0 && edge < 255);
+        modify(&mut state, r#"{ "type": "all" }"#);
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (0, 0, 20, 20).into()
+        );
+        modify(&mut state, r#"{ "type": "none" }"#);
+        assert!(state.selection.is_none());
+    }
+}
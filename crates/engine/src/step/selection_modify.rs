@@ -0,0 +1,129 @@
+use imagine::Mask;
+use serde::{Deserialize, Serialize};
+
+use crate::{Engine, EngineError};
+
+use super::IStep;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum SelectionModification {
+    /// Selects the whole canvas
+    #[serde(rename = "all")]
+    All,
+
+    /// Removes the selection, such that nothing limits drawing and effects anymore
+    #[serde(rename = "none")]
+    None,
+
+    /// Selects everything that isn't selected
+    #[serde(rename = "invert")]
+    Invert,
+
+    /// Softens the edges of the selection
+    #[serde(rename = "feather")]
+    Feather { radius: f32 },
+
+    /// Extends the selection by the amount of pixels
+    #[serde(rename = "grow")]
+    Grow { amount: u32 },
+
+    /// Reduces the selection by the amount of pixels
+    #[serde(rename = "shrink")]
+    Shrink { amount: u32 },
+}
+
+/// Changes the current selection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SelectionModify {
+    pub modification: SelectionModification,
+}
+
+impl IStep for SelectionModify {
+    fn perform_on(&self, session: &mut Engine) -> Result<(), EngineError> {
+        let (width, height) = session.size().into();
+        // beyond the diagonal the selection only fades or grows further out
+        let diagonal = (width as f32).hypot(height as f32);
+        match self.modification {
+            SelectionModification::Feather { radius } if !(0.0..=diagonal).contains(&radius) => {
+                return Err(EngineError::user_error(
+                    "The feather radius has to be between 0 and the diagonal of the canvas",
+                ));
+            }
+            SelectionModification::Grow { amount } | SelectionModification::Shrink { amount }
+                if amount as f32 > diagonal =>
+            {
+                return Err(EngineError::user_error(
+                    "A selection can't grow or shrink by more than the diagonal of the canvas",
+                ));
+            }
+            _ => {}
+        }
+        // without a selection nothing is selected
+        let mut selection = session
+            .selection
+            .take()
+            .unwrap_or_else(|| Mask::new(width, height));
+        match self.modification {
+            SelectionModification::All => selection = Mask::full(width, height),
+            SelectionModification::None => return Ok(()),
+            SelectionModification::Invert => selection.invert(),
+            SelectionModification::Feather { radius } => selection.feather(radius),
+            SelectionModification::Grow { amount } => selection.grow(amount),
+            SelectionModification::Shrink { amount } => selection.shrink(amount),
+        }
+        session.selection = Some(selection);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{step::test_util::step, Engine};
+
+    #[test]
+    fn feather_radius_is_bounded() {
+        let mut state = Engine::new(10, 10);
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [2, 2], "to": [7, 7] } }"#;
+        state.perform(&step(select)).unwrap();
+        for radius in ["1e30", "-1"] {
+            let feather = format!(
+                r#"{{ "type": "selection/modify",
+                    "modification": {{ "type": "feather", "radius": {radius} }} }}"#
+            );
+            assert!(state.perform(&step(&feather)).is_err());
+        }
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (2, 2, 6, 6).into()
+        );
+
+        let feather = r#"{ "type": "selection/modify",
+            "modification": { "type": "feather", "radius": 2.0 } }"#;
+        state.perform(&step(feather)).unwrap();
+        assert!(state.selection.as_ref().unwrap().bounds().size.width > 6);
+    }
+
+    #[test]
+    fn grow_and_shrink_are_bounded() {
+        let mut state = Engine::new(10, 10);
+        let select = r#"{ "type": "selection/create",
+            "shape": { "type": "rectangle", "from": [2, 2], "to": [7, 7] } }"#;
+        state.perform(&step(select)).unwrap();
+        for modification in ["grow", "shrink"] {
+            let json = format!(
+                r#"{{ "type": "selection/modify",
+                    "modification": {{ "type": "{modification}", "amount": 4000000000 }} }}"#
+            );
+            assert!(state.perform(&step(&json)).is_err());
+        }
+        let grow = r#"{ "type": "selection/modify",
+            "modification": { "type": "grow", "amount": 14 } }"#;
+        state.perform(&step(grow)).unwrap();
+        assert_eq!(
+            state.selection.as_ref().unwrap().bounds(),
+            (0, 0, 10, 10).into()
+        );
+    }
+}
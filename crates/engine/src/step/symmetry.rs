@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+
+use common::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::EngineError;
+
+/// Most segments of a radial symmetry, every segment draws the whole track once more
+const MAX_SEGMENTS: u32 = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum SymmetryMode {
+    /// Mirrors left and right of the vertical axis through the center
+    #[serde(rename = "horizontal")]
+    Horizontal,
+
+    /// Mirrors above and below the horizontal axis through the center
+    #[serde(rename = "vertical")]
+    Vertical,
+
+    /// Mirrors along both axes through the center
+    #[serde(rename = "both")]
+    Both,
+
+    /// Repeats rotated around the center in `segments` equal segments, from 1 (no repetition) to 64
+    #[serde(rename = "radial")]
+    Radial { segments: u32 },
+}
+
+/// Describes how a single input track of a draw step is repeated.
+/// It is part of the step itself such that replaying the step produces exactly the same result.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Symmetry {
+    pub mode: SymmetryMode,
+    /// Center of the symmetry in global coordinates
+    pub center: Position,
+}
+
+impl Symmetry {
+    /// Explains what is wrong with the symmetry if it can't be drawn
+    pub fn check(&self) -> Result<(), EngineError> {
+        match self.mode {
+            SymmetryMode::Radial { segments } if !(1..=MAX_SEGMENTS).contains(&segments) => Err(
+                EngineError::user_error("A radial symmetry needs from 1 to 64 segments"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// All positions that correspond to the given one, starting with the position itself.
+    pub fn apply(&self, pos: &Position) -> Vec<Position> {
+        let Position { x: cx, y: cy } = self.center;
+        let mirror_x = Position::new(2 * cx - pos.x, pos.y);
+        let mirror_y = Position::new(pos.x, 2 * cy - pos.y);
+        match self.mode {
+            SymmetryMode::Horizontal => vec![*pos, mirror_x],
+            SymmetryMode::Vertical => vec![*pos, mirror_y],
+            SymmetryMode::Both => vec![
+                *pos,
+                mirror_x,
+                mirror_y,
+                Position::new(mirror_x.x, mirror_y.y),
+            ],
+            SymmetryMode::Radial { segments } => {
+                let segments = segments.max(1);
+                let (dx, dy) = ((pos.x - cx) as f64, (pos.y - cy) as f64);
+                (0..segments)
+                    .map(|segment| 2. * PI * segment as f64 / segments as f64)
+                    .map(|angle| {
+                        let (sin, cos) = angle.sin_cos();
+                        Position::new(
+                            cx + (dx * cos - dy * sin).round() as i32,
+                            cy + (dx * sin + dy * cos).round() as i32,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Number of positions [Symmetry::apply] produces.
+    pub fn copies(&self) -> usize {
+        match self.mode {
+            SymmetryMode::Horizontal | SymmetryMode::Vertical => 2,
+            SymmetryMode::Both => 4,
+            SymmetryMode::Radial { segments } => segments.max(1) as usize,
+        }
+    }
+}
+
+/// Splits the given track into one track per symmetric copy, the original track being the first one.
+pub(super) fn mirror_track(symmetry: &Option<Symmetry>, track: &[Position]) -> Vec<Vec<Position>> {
+    match symmetry {
+        None => vec![track.to_vec()],
+        Some(symmetry) => {
+            let mut result = vec![vec![]; symmetry.copies()];
+            for pos in track {
+                for (copy, mirrored) in result.iter_mut().zip(symmetry.apply(pos)) {
+                    copy.push(mirrored);
+                }
+            }
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Position;
+
+    use super::{Symmetry, SymmetryMode};
+
+    #[test]
+    fn mirror_both_axes() {
+        let symmetry = Symmetry {
+            mode: SymmetryMode::Both,
+            center: Position::new(10, 10),
+        };
+        let expected: Vec<Position> = vec![
+            (3, 4).into(),
+            (17, 4).into(),
+            (3, 16).into(),
+            (17, 16).into(),
+        ];
+        assert_eq!(symmetry.apply(&(3, 4).into()), expected);
+    }
+
+    #[test]
+    fn radial() {
+        let symmetry = Symmetry {
+            mode: SymmetryMode::Radial { segments: 4 },
+            center: Position::new(10, 10),
+        };
+        let expected: Vec<Position> = vec![
+            (15, 10).into(),
+            (10, 15).into(),
+            (5, 10).into(),
+            (10, 5).into(),
+        ];
+        assert_eq!(symmetry.apply(&(15, 10).into()), expected);
+    }
+
+    #[test]
+    fn deserialize() {
+        let json = r#"{"mode": {"type": "radial", "segments": 6}, "center": [4, 2]}"#;
+        let symmetry: Symmetry = serde_json::from_str(json).unwrap();
+        assert_eq!(symmetry.mode, SymmetryMode::Radial { segments: 6 });
+        assert_eq!(symmetry.copies(), 6);
+        assert!(symmetry.check().is_ok());
+        for segments in [0, 65] {
+            let symmetry = Symmetry {
+                mode: SymmetryMode::Radial { segments },
+                ..symmetry.clone()
+            };
+            assert!(symmetry.check().is_err());
+        }
+    }
+}
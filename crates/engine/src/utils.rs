@@ -1,6 +1,8 @@
+use std::borrow::Cow;
+
 use baum::{Cursor, Tree};
-use common::{Position, Rectangle};
-use imagine::{Blender, Image};
+use common::{Position, Rectangle, Size};
+use imagine::{BlendMode, Blender, Image, Mask};
 
 use crate::{
     error::EngineError,
@@ -27,14 +29,28 @@ pub fn merge_ghost(
 ) -> Result<(), EngineError> {
     let mut cursor = Cursor::new(content, idx).map_err(EngineError::from)?;
     let layer = cursor.value_mut();
-    if let (Some(ghost), Some(zombie)) = (&layer.ghost, &layer.zombie) {
+    let area = Rectangle::of((0, 0).into(), layer.img.size());
+    if paint_ghost_into_mask(blender, layer, &area) {
+        log::debug!("Merging ghost of {} into its mask", layer.name);
+        if let Some(mask) = &mut layer.mask {
+            mask.zombie = None;
+        }
+        layer.ghost = None;
+        layer.zombie = None;
+        Ok(())
+    } else if let (Some(ghost), Some(zombie)) = (&layer.ghost, &layer.zombie) {
         log::debug!("Merging ghost of {}", layer.name);
         layer.img = blender.blend(
             ghost.mode,
             &layer.rectangle(),
-            (&ghost.img, layer.attr.pos, ghost.alpha as f64, None),
-            (&zombie, layer.attr.pos, 1.0, None),
+            (&ghost.img, layer.attr.pos, ghost.alpha as f64, None, None),
+            (zombie, layer.attr.pos, 1.0, None),
         );
+        if let Some(selection) = &ghost.selection {
+            layer
+                .img
+                .keep_changes_within(zombie, selection, layer.attr.pos, &area);
+        }
         layer.ghost = None;
         layer.zombie = None;
         Ok(())
@@ -56,83 +72,387 @@ pub fn propagate_changes_up(
     // merge ghost of current layer if needed
     let layer = cursor.value_mut();
 
-    if layer.flag == LayerFlag::Pixel {
+    if !layer.flag.is_group() {
         log::debug!("Propagate changes from '{}'", layer.name);
 
         // ghost
-        if let (Some(ghost), Some(zombie)) = (&layer.ghost, &layer.zombie) {
+        let area = Rectangle::of((0, 0).into(), layer.img.size());
+        if paint_ghost_into_mask(blender, layer, &area) {
+            // the pixels stay untouched
+        } else if let (Some(ghost), Some(zombie)) = (&layer.ghost, &layer.zombie) {
             layer.img = blender.blend(
                 ghost.mode,
                 &layer.rectangle(),
-                (&ghost.img, layer.attr.pos, ghost.alpha as f64, None),
-                (&zombie, layer.attr.pos, layer.attr.alpha as f64, None),
+                (&ghost.img, layer.attr.pos, ghost.alpha as f64, None, None),
+                (zombie, layer.attr.pos, 1.0, None),
             );
+            if let Some(selection) = &ghost.selection {
+                layer
+                    .img
+                    .keep_changes_within(zombie, selection, layer.attr.pos, &area);
+            }
         }
         cursor.go_up();
         let changed = cursor.index();
         propagate_changes_up(blender, content, changed)
     } else {
-        let rectangle = &layer.rectangle();
-        let arg = cursor
-            .children()
-            .iter()
-            .map(|(child, idx)| {
-                (
-                    child.attr.mode,
-                    &child.img,
-                    child.attr.pos,
-                    child.attr.alpha as f64,
-                    child.visible,
-                    Some(*idx),
-                )
-            })
-            .collect();
-        let result = blender.blend_all(rectangle, arg);
-        let layer = cursor.value_mut();
+        render_adjustments(blender, content, changed)?;
+        let children = children_of(content, changed)?;
+        let layer = content.get_value(changed).map_err(EngineError::from)?;
+        let result = composite(blender, content, &layer.rectangle(), None, &children)?;
+        let layer = content.value_mut(changed).map_err(EngineError::from)?;
         layer.img = result;
-        if cursor.is_on_root() {
+        if changed == content.root {
             Ok(())
         } else {
-            cursor.go_up();
-            let changed = cursor.index();
-            propagate_changes_up(blender, content, changed)
+            let parent = content.get_parent(changed).map_err(EngineError::from)?;
+            propagate_changes_up(blender, content, parent)
         }
     }
 }
 
-pub fn propagate_damage(
+/// Composites every group of the content again, the deepest groups first
+pub fn composite_all(
     blender: &mut Box<dyn Blender>,
     content: &mut Tree<Layer>,
-    changed: usize,
-    damage: &Rectangle,
 ) -> Result<(), EngineError> {
-    let (layer, children) = unsafe { get_layer_with_children(content, changed) };
-    let dest = &mut layer.img;
+    for idx in content.traverse().into_iter().rev() {
+        let layer = content.get_value(idx).map_err(EngineError::from)?;
+        if !layer.flag.is_group() {
+            continue;
+        }
+        render_adjustments(blender, content, idx)?;
+        let children = children_of(content, idx)?;
+        let layer = content.get_value(idx).map_err(EngineError::from)?;
+        let result = composite(blender, content, &layer.rectangle(), None, &children)?;
+        content.value_mut(idx).map_err(EngineError::from)?.img = result;
+    }
+    Ok(())
+}
+
+/// Largest width and height of the canvas
+const MAX_CANVAS_SIZE: u32 = 16384;
+
+/// Fails for canvas sizes that are empty or too large
+pub fn check_canvas_size(size: Size) -> Result<(), EngineError> {
+    if size.width == 0 || size.height == 0 {
+        return Err(EngineError::user_error("The canvas can't be empty"));
+    }
+    if size.width > MAX_CANVAS_SIZE || size.height > MAX_CANVAS_SIZE {
+        return Err(EngineError::user_error("The canvas would be too large"));
+    }
+    Ok(())
+}
+
+/// Changes the canvas to the given size with its former top left corner ending up at the offset.
+/// The pixels of the layers are kept, they are only moved along.
+pub fn reframe_canvas(
+    session: &mut Engine,
+    offset: Position,
+    size: Size,
+) -> Result<(), EngineError> {
+    for idx in session.content.traverse() {
+        let layer = session.content.value_mut(idx).map_err(EngineError::from)?;
+        if layer.flag.is_leaf() {
+            layer.attr.pos += offset;
+            continue;
+        }
+        // the root, groups and adjustment layers cover the canvas
+        layer.img = Image::new_with_depth(size.width, size.height, layer.img.depth());
+        if let Some(mask) = &mut layer.mask {
+            mask.img = mask.img.translated(offset, size);
+        }
+    }
+    session.selection = (session.selection.take()).map(|s| s.translated(offset, size));
+    composite_all(&mut session.blender, &mut session.content)
+}
 
-    // clean damaged
-    let relative_damage = damage - &layer.attr.pos;
-    dest.clean(&relative_damage);
+/// Quarter turns and flips of the whole canvas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reorientation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontally,
+    FlipVertically,
+}
+
+impl Reorientation {
+    fn image(&self, img: &mut Image) {
+        match self {
+            Reorientation::Rotate90 => img.rotate90(),
+            Reorientation::Rotate180 => img.rotate180(),
+            Reorientation::Rotate270 => img.rotate270(),
+            Reorientation::FlipHorizontally => img.flip_horizontally(),
+            Reorientation::FlipVertically => img.flip_vertically(),
+        }
+    }
+
+    fn mask(&self, mask: &mut Mask) {
+        match self {
+            Reorientation::Rotate90 => mask.rotate90(),
+            Reorientation::Rotate180 => mask.rotate180(),
+            Reorientation::Rotate270 => mask.rotate270(),
+            Reorientation::FlipHorizontally => mask.flip_horizontally(),
+            Reorientation::FlipVertically => mask.flip_vertically(),
+        }
+    }
+
+    /// Top left corner of the given area of a canvas of the given size once it is reoriented
+    fn position(&self, area: &Rectangle, canvas: Size) -> Position {
+        let (x, y) = (area.position.x, area.position.y);
+        let (w, h) = (area.size.width as i32, area.size.height as i32);
+        let (width, height) = (canvas.width as i32, canvas.height as i32);
+        match self {
+            Reorientation::Rotate90 => Position::new(height - y - h, x),
+            Reorientation::Rotate180 => Position::new(width - x - w, height - y - h),
+            Reorientation::Rotate270 => Position::new(y, width - x - w),
+            Reorientation::FlipHorizontally => Position::new(width - x - w, y),
+            Reorientation::FlipVertically => Position::new(x, height - y - h),
+        }
+    }
+}
+
+/// Rotates or flips the whole canvas, every layer is moved along with its pixels and its mask.
+/// Text layers have to be rasterized first, editing them would render the text upright again.
+pub fn reorient_canvas(
+    session: &mut Engine,
+    reorientation: Reorientation,
+) -> Result<(), EngineError> {
+    let text = session
+        .content
+        .traverse()
+        .into_iter()
+        .filter_map(|idx| session.content.get_value(idx).ok())
+        .find(|layer| matches!(layer.flag, LayerFlag::Text(_)));
+    if let Some(text) = text {
+        return Err(EngineError::user_error(&format!(
+            "Text layer \"{}\" has to be rasterized before the canvas can be rotated or flipped",
+            text.name
+        )));
+    }
+    let canvas = session.size();
+    for idx in session.content.traverse() {
+        let layer = session.content.value_mut(idx).map_err(EngineError::from)?;
+        layer.attr.pos = reorientation.position(&layer.rectangle(), canvas);
+        reorientation.image(&mut layer.img);
+        if let Some(mask) = &mut layer.mask {
+            reorientation.mask(&mut mask.img);
+        }
+    }
+    if let Some(selection) = &mut session.selection {
+        reorientation.mask(selection);
+    }
+    composite_all(&mut session.blender, &mut session.content)
+}
+
+/// Children of the layer (from bottom to top) together with their indices
+fn children_of(content: &Tree<Layer>, idx: usize) -> Result<Vec<(&Layer, usize)>, EngineError> {
+    content
+        .get_children(idx)
+        .map_err(EngineError::from)?
+        .into_iter()
+        .map(|child| Ok((content.get_value(child).map_err(EngineError::from)?, child)))
+        .collect()
+}
+
+/// Blends the children (from bottom to top) into one image that fits the destination.
+/// The children of pass-through groups blend directly with what lies beneath the group.
+fn composite(
+    blender: &mut Box<dyn Blender>,
+    content: &Tree<Layer>,
+    destination: &Rectangle,
+    beneath: Option<Image>,
+    children: &[(&Layer, usize)],
+) -> Result<Image, EngineError> {
+    let masks = compositing_masks(&children.iter().map(|(c, _)| *c).collect::<Vec<_>>(), None);
+    let mut result = beneath;
+    let mut start = 0;
+    for (i, (child, idx)) in children.iter().enumerate() {
+        if !child.is_pass_through() {
+            continue;
+        }
+        let below = blend_run(
+            blender,
+            destination,
+            result.as_ref(),
+            &children[start..i],
+            &masks[start..i],
+        );
+        start = i + 1;
+        if !child.visible {
+            result = Some(below);
+            continue;
+        }
+        let grandchildren = children_of(content, *idx)?;
+        let mut passed = composite(
+            blender,
+            content,
+            destination,
+            Some(below.clone()),
+            &grandchildren,
+        )?;
+        if let Some(coverage) = child.pass_through_coverage(destination) {
+            let area = Rectangle::of((0, 0).into(), destination.size);
+            passed.keep_changes_within(&below, &coverage, Position::new(0, 0), &area);
+        }
+        result = Some(passed);
+    }
+    Ok(blend_run(
+        blender,
+        destination,
+        result.as_ref(),
+        &children[start..],
+        &masks[start..],
+    ))
+}
+
+/// Blends the children on top of what lies beneath them
+fn blend_run(
+    blender: &mut Box<dyn Blender>,
+    destination: &Rectangle,
+    beneath: Option<&Image>,
+    children: &[(&Layer, usize)],
+    masks: &[Option<Cow<Mask>>],
+) -> Image {
+    let beneath = beneath.map(|img| {
+        (
+            BlendMode::Alpha,
+            img,
+            destination.position,
+            1.0,
+            true,
+            None,
+            None,
+        )
+    });
+    let arg = beneath
+        .into_iter()
+        .chain(
+            children
+                .iter()
+                .zip(masks.iter())
+                .map(|((child, idx), mask)| {
+                    (
+                        child.attr.mode,
+                        &child.img,
+                        child.attr.pos,
+                        child.attr.alpha as f64,
+                        child.visible,
+                        Some(*idx),
+                        mask.as_deref(),
+                    )
+                }),
+        )
+        .collect();
+    blender.blend_all(destination, arg)
+}
 
-    for child in children {
+/// Blends the damaged area of the children on top of the destination like [composite]
+fn composite_damaged(
+    blender: &mut Box<dyn Blender>,
+    content: &Tree<Layer>,
+    (dest, pos): (&mut Image, Position),
+    children: &[(&Layer, usize)],
+    damage: &Rectangle,
+) -> Result<(), EngineError> {
+    let masks = compositing_masks(
+        &children.iter().map(|(c, _)| *c).collect::<Vec<_>>(),
+        Some(damage),
+    );
+    for ((child, idx), mask) in children.iter().zip(masks.iter()) {
         if !child.visible {
             continue;
         }
+        if child.is_pass_through() {
+            let below = dest.clone();
+            let grandchildren = children_of(content, *idx)?;
+            composite_damaged(blender, content, (dest, pos), &grandchildren, damage)?;
+            let destination = Rectangle::of(pos, dest.size());
+            if let Some(coverage) = child.pass_through_coverage(&destination) {
+                dest.keep_changes_within(&below, &coverage, Position::new(0, 0), &(damage - &pos));
+            }
+            continue;
+        }
         blender.blend_damaged(
             child.attr.mode,
-            (dest, layer.attr.pos, layer.attr.alpha as f64),
-            (&child.img, child.attr.pos, child.attr.alpha as f64),
+            (dest, pos, 1.0),
+            (
+                &child.img,
+                child.attr.pos,
+                child.attr.alpha as f64,
+                mask.as_deref(),
+            ),
             damage,
         )
     }
+    Ok(())
+}
 
-    if let (Some(ghost), Some(zombie)) = (&mut layer.ghost, &mut layer.zombie) {
-        blender.blend_damaged_into(
-            ghost.mode,
-            damage,
-            (dest, layer.attr.pos),
-            (&ghost.img, layer.attr.pos, ghost.alpha as f64),
-            (zombie, layer.attr.pos, 1.0),
-        )
+/// Renders the adjustment layers among the children of the layer. Each one holds its effect applied
+/// to the composite of its siblings beneath, which is then blended like any other layer.
+fn render_adjustments(
+    blender: &mut Box<dyn Blender>,
+    content: &mut Tree<Layer>,
+    idx: usize,
+) -> Result<(), EngineError> {
+    let destination = content
+        .get_value(idx)
+        .map_err(EngineError::from)?
+        .rectangle();
+    let children = content.get_children(idx).map_err(EngineError::from)?;
+    for (i, child) in children.iter().enumerate() {
+        let LayerFlag::Adjustment(adjustment) =
+            &content.get_value(*child).map_err(EngineError::from)?.flag
+        else {
+            continue;
+        };
+        let adjustment = adjustment.clone();
+        let below = children_of(content, idx)?;
+        let mut img = composite(blender, content, &destination, None, &below[..i])?;
+        adjustment.apply(&mut img);
+        let layer = content.value_mut(*child).map_err(EngineError::from)?;
+        layer.img = img;
+        layer.attr.pos = destination.position;
+    }
+    Ok(())
+}
+
+pub fn propagate_damage(
+    blender: &mut Box<dyn Blender>,
+    content: &mut Tree<Layer>,
+    changed: usize,
+    damage: &Rectangle,
+) -> Result<(), EngineError> {
+    render_adjustments(blender, content, changed)?;
+    let layer = content.value_mut(changed).map_err(EngineError::from)?;
+    let relative_damage = damage - &layer.attr.pos;
+
+    if !paint_ghost_into_mask(blender, layer, &relative_damage) {
+        // the image is taken out of the tree while the children are blended into it
+        let pos = layer.attr.pos;
+        let mut dest = std::mem::replace(&mut layer.img, Image::new(0, 0));
+
+        // clean damaged
+        dest.clean(&relative_damage);
+
+        let children = children_of(content, changed)?;
+        composite_damaged(blender, content, (&mut dest, pos), &children, damage)?;
+
+        let layer = content.value_mut(changed).map_err(EngineError::from)?;
+        if let (Some(ghost), Some(zombie)) = (&layer.ghost, &layer.zombie) {
+            blender.blend_damaged_into(
+                ghost.mode,
+                damage,
+                (&mut dest, pos),
+                (&ghost.img, pos, ghost.alpha as f64),
+                (zombie, pos, 1.0),
+            );
+            if let Some(selection) = &ghost.selection {
+                dest.keep_changes_within(zombie, selection, pos, &relative_damage);
+            }
+        }
+        layer.img = dest;
     }
 
     let parent_idx = {
@@ -148,6 +468,97 @@ pub fn propagate_damage(
     }
 }
 
+/// Masks the children (from bottom to top) are composited with. Clipped children only show up
+/// where the nearest non-clipped sibling below them covers, the bottom one can't be clipped.
+/// Their masks are only computed within the damaged area if there is one.
+fn compositing_masks<'a>(
+    children: &[&'a Layer],
+    damage: Option<&Rectangle>,
+) -> Vec<Option<Cow<'a, Mask>>> {
+    let mut base: Option<&Layer> = None;
+    children
+        .iter()
+        .map(|child| match base.filter(|_| child.attr.clipped) {
+            Some(base) => Some(Cow::Owned(child.clipped_by(base, damage))),
+            None => {
+                base = Some(child);
+                child.active_mask().map(Cow::Borrowed)
+            }
+        })
+        .collect()
+}
+
+/// Paints the ghost of the layer into its mask within the area (in image coordinates) if painting targets the mask,
+/// the luminance of the painted result becomes the new mask. Returns whether that was the case.
+fn paint_ghost_into_mask(
+    blender: &mut Box<dyn Blender>,
+    layer: &mut Layer,
+    area: &Rectangle,
+) -> bool {
+    let (Some(ghost), Some(mask)) = (&layer.ghost, &mut layer.mask) else {
+        return false;
+    };
+    if !mask.edit {
+        return false;
+    }
+    let pos = layer.attr.pos;
+    let before = mask
+        .zombie
+        .get_or_insert_with(|| mask.img.clone())
+        .to_image();
+    let mut painted = before.clone();
+    blender.blend_damaged_into(
+        ghost.mode,
+        &(area + &pos),
+        (&mut painted, pos),
+        (&ghost.img, pos, ghost.alpha as f64),
+        (&before, pos, 1.0),
+    );
+    if let Some(selection) = &ghost.selection {
+        painted.keep_changes_within(&before, selection, pos, area);
+    }
+    mask.img.set_luminance(&painted, area);
+    true
+}
+
+/// Text layers are rendered from their text, their pixels can only be changed once they are rasterized
+pub fn check_pixels_editable(layer: &Layer) -> Result<(), EngineError> {
+    match layer.flag {
+        LayerFlag::Text(_) => Err(EngineError::user_error(
+            "Text layers have to be rasterized before their pixels can be changed",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Like [check_pixels_editable] for painting with a ghost, which goes into the mask while it is edited
+pub fn check_paintable(layer: &Layer) -> Result<(), EngineError> {
+    match &layer.mask {
+        Some(mask) if mask.edit => Ok(()),
+        _ => check_pixels_editable(layer),
+    }
+}
+
+/// Applies the effect to the image of the layer, limited to the selection if there is one
+pub fn apply_effect(
+    selection: Option<&Mask>,
+    layer: &mut Layer,
+    effect: impl FnOnce(&mut Image),
+) -> Result<(), EngineError> {
+    check_pixels_editable(layer)?;
+    let Some(selection) = selection else {
+        effect(&mut layer.img);
+        return Ok(());
+    };
+    let before = layer.img.clone();
+    effect(&mut layer.img);
+    let area = Rectangle::of((0, 0).into(), layer.img.size());
+    layer
+        .img
+        .keep_changes_within(&before, selection, layer.attr.pos, &area);
+    Ok(())
+}
+
 pub fn add_layer(
     state: &mut Engine,
     parent: usize,
@@ -160,12 +571,12 @@ pub fn add_layer(
         Some(position) => *position,
         None => Position { x: 0, y: 0 },
     };
-    // create layer
-    let mut layer = Layer::from_content(content);
+    // create layer, the content is kept in the depth of the document
+    let mut layer = Layer::from_content(content.into_depth(state.depth()));
     layer.attr.pos = position;
     let mut cursor = Cursor::new(&mut state.content, parent).map_err(EngineError::from)?;
     let current = cursor.value();
-    if current.flag == LayerFlag::Pixel {
+    if !current.flag.is_group() {
         Err(EngineError::user_error(
             "Can't create sub layer on pixel layer",
         ))
@@ -200,20 +611,3 @@ pub fn move_layer(session: &mut Engine, id: usize, move_idx: isize) -> Result<()
         .move_node(id, move_idx)
         .map_err(EngineError::from)
 }
-
-unsafe fn get_layer_with_children(
-    content: &mut Tree<Layer>,
-    idx: usize,
-) -> (&mut Layer, Vec<&Layer>) {
-    let nodes = &mut content.nodes;
-    let ptr = nodes.as_mut_ptr();
-    let node = ptr.add(idx).as_mut().unwrap();
-    let children: Vec<&Layer> = node
-        .children
-        .iter()
-        .map(|x| nodes.get(*x).unwrap())
-        .map(|node| &node.value)
-        .collect();
-    let layer = &mut node.value;
-    (layer, children)
-}
@@ -4,6 +4,8 @@ use baum::{Cursor, Tree};
 use common::Size;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use imagine::Depth;
+
 use crate::{moment::Moment, Engine, EngineError, Step};
 
 #[wasm_bindgen(start)]
@@ -19,6 +21,27 @@ impl Engine {
         Self::new(width, height)
     }
 
+    #[wasm_bindgen(js_name = with_settings)]
+    pub fn _with_settings(
+        width: u32,
+        height: u32,
+        compositing: JsValue,
+        depth: JsValue,
+        working_space: JsValue,
+    ) -> Result<Engine, EngineError> {
+        let compositing = serde_wasm_bindgen::from_value(compositing).map_err(EngineError::from)?;
+        let depth = serde_wasm_bindgen::from_value(depth).map_err(EngineError::from)?;
+        let working_space =
+            serde_wasm_bindgen::from_value(working_space).map_err(EngineError::from)?;
+        Ok(Self::with_settings(
+            width,
+            height,
+            compositing,
+            depth,
+            working_space,
+        ))
+    }
+
     #[wasm_bindgen(js_name = reconstruct)]
     pub fn _reconstruct(point: usize, val: JsValue) -> Result<Engine, EngineError> {
         let mut history: Tree<Moment> =
@@ -31,7 +54,7 @@ impl Engine {
             steps.push(cursor.value().data.clone());
         }
         steps.reverse();
-        let mut engine = Engine::reconstruct(&steps, HashMap::new())?;
+        let mut engine = Engine::reconstruct(&steps, HashMap::new(), HashMap::new())?;
         // super danger
         engine.history = history;
         engine.current = point;
@@ -47,8 +70,13 @@ impl Engine {
     #[no_mangle]
     #[wasm_bindgen(getter)]
     pub fn pointer(&mut self) -> u32 {
-        let root = self.content.root_value();
-        let ptr = root.img.into_array().as_ptr();
+        let root = &self.content.root_value().img;
+        let ptr = if root.depth() == Depth::Eight {
+            root.into_array().as_ptr()
+        } else {
+            let display = self.context.display.insert(root.to_depth(Depth::Eight));
+            display.into_array().as_ptr()
+        };
         log::debug!("Accessing content pointer");
         ptr as u32
     }
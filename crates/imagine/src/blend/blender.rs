@@ -1,6 +1,6 @@
 use common::{Position, Rectangle};
 
-use crate::{BlendMode, Image};
+use crate::{BlendMode, Compositing, Image, Mask};
 
 /// A blender blends multiple images together (composition)
 pub trait Blender {
@@ -11,16 +11,23 @@ pub trait Blender {
     /// That is important for caching these images into texture if hardware acceleration is used.
     fn load(&mut self, marker: usize, img: &Image);
 
+    /// Color space the blender composites in
+    fn compositing(&self) -> Compositing;
+
+    /// Changes the color space the blender composites in
+    fn set_compositing(&mut self, compositing: Compositing);
+
     /// Cleans the cache and thus forgets all images that got loaded through the load method.
     fn clean(&mut self);
 
     /// Blends `overlay` on top of `base` with the given mode, positions and alphas.
+    /// The optional mask of the overlay shares its position and reduces its alpha per pixel.
     /// Creates a new image that is defined by `destination`.
     fn blend(
         &mut self,
         mode: BlendMode,
         destination: &Rectangle,
-        overlay: (&Image, Position, f64, Option<usize>),
+        overlay: (&Image, Position, f64, Option<usize>, Option<&Mask>),
         base: (&Image, Position, f64, Option<usize>),
     ) -> Image;
 
@@ -35,12 +42,13 @@ pub trait Blender {
         base: (&Image, Position, f64),
     );
 
-    /// Blends the active image on top of the base image by mutating the passed reference
+    /// Blends the active image on top of the base image by mutating the passed reference.
+    /// The optional mask of the overlay shares its position and reduces its alpha per pixel.
     fn blend_damaged(
         &mut self,
         mode: BlendMode,
         base: (&mut Image, Position, f64),
-        overlay: (&Image, Position, f64),
+        overlay: (&Image, Position, f64, Option<&Mask>),
         damage: &Rectangle,
     );
 
@@ -48,14 +56,14 @@ pub trait Blender {
     fn blend_all(&mut self, destination: &Rectangle, children: Vec<BlendLayer>) -> Image {
         let (width, height) = (destination.size.width, destination.size.height);
         let mut result = Image::new(width, height);
-        for (mode, img, pos, alpha, visible, marker) in children {
+        for (mode, img, pos, alpha, visible, marker, mask) in children {
             if !visible {
                 continue;
             }
             result = self.blend(
                 mode,
                 destination,
-                (img, pos, alpha, marker),
+                (img, pos, alpha, marker, mask),
                 (&result, destination.position, 1.0, None),
             );
         }
@@ -63,4 +71,12 @@ pub trait Blender {
     }
 }
 
-pub type BlendLayer<'a> = (BlendMode, &'a Image, Position, f64, bool, Option<usize>);
+pub type BlendLayer<'a> = (
+    BlendMode,
+    &'a Image,
+    Position,
+    f64,
+    bool,
+    Option<usize>,
+    Option<&'a Mask>,
+);
@@ -7,6 +7,7 @@ mod webgl_blender;
 
 pub use blender::Blender;
 pub use software_blender::SoftwareBlender;
+pub(crate) use software_blender::{from_linear, to_linear};
 #[cfg(feature = "wasm")]
 pub use webgl_blender::WebGlBlender;
 
@@ -31,6 +32,119 @@ pub enum BlendMode {
     /// screen
     #[serde(rename = "screen")]
     Screen,
+
+    /// multiply, darkens by multiplying the colors
+    #[serde(rename = "multiply")]
+    Multiply,
+
+    /// overlay, multiplies dark and screens light areas of the base
+    #[serde(rename = "overlay")]
+    Overlay,
+
+    /// soft-light, a softer version of hard light
+    #[serde(rename = "soft_light")]
+    SoftLight,
+
+    /// hard-light, multiplies dark and screens light areas of the overlay
+    #[serde(rename = "hard_light")]
+    HardLight,
+
+    /// color-dodge, brightens the base to reflect the overlay
+    #[serde(rename = "color_dodge")]
+    ColorDodge,
+
+    /// color-burn, darkens the base to reflect the overlay
+    #[serde(rename = "color_burn")]
+    ColorBurn,
+
+    /// linear burn, adds the colors and subtracts white
+    #[serde(rename = "linear_burn")]
+    LinearBurn,
+
+    /// linear dodge, adds the colors
+    #[serde(rename = "linear_dodge")]
+    LinearDodge,
+
+    /// difference, subtracts the darker from the lighter color
+    #[serde(rename = "difference")]
+    Difference,
+
+    /// exclusion, like difference with lower contrast
+    #[serde(rename = "exclusion")]
+    Exclusion,
+
+    /// subtract, subtracts the overlay from the base
+    #[serde(rename = "subtract")]
+    Subtract,
+
+    /// divide, divides the base by the overlay
+    #[serde(rename = "divide")]
+    Divide,
+
+    /// hue of the overlay with saturation and luminosity of the base
+    #[serde(rename = "hue")]
+    Hue,
+
+    /// saturation of the overlay with hue and luminosity of the base
+    #[serde(rename = "saturation")]
+    Saturation,
+
+    /// hue and saturation of the overlay with luminosity of the base
+    #[serde(rename = "color")]
+    Color,
+
+    /// luminosity of the overlay with hue and saturation of the base
+    #[serde(rename = "luminosity")]
+    Luminosity,
+
+    /// source (copy), replaces the base with the overlay
+    #[serde(rename = "source")]
+    Source,
+
+    /// source-in, shows the overlay only where the base is
+    #[serde(rename = "source_in")]
+    SourceIn,
+
+    /// source-out, shows the overlay only where the base isn't
+    #[serde(rename = "source_out")]
+    SourceOut,
+
+    /// source-atop, puts the overlay on top of the base, but only where the base is
+    #[serde(rename = "source_atop")]
+    SourceAtop,
+
+    /// destination-over, puts the overlay beneath the base
+    #[serde(rename = "destination_over")]
+    DestinationOver,
+
+    /// destination-in, keeps the base only where the overlay is
+    #[serde(rename = "destination_in")]
+    DestinationIn,
+
+    /// destination-atop, puts the base on top of the overlay, but only where the overlay is
+    #[serde(rename = "destination_atop")]
+    DestinationAtop,
+
+    /// xor, shows overlay and base where they don't overlap
+    #[serde(rename = "xor")]
+    Xor,
+
+    /// clear, clears everything
+    #[serde(rename = "clear")]
+    Clear,
+}
+
+/// Color space the blender composites in
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum Compositing {
+    /// Blends the 8-bit sRGB values with straight alpha as they are stored
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+
+    /// Converts to linear light with premultiplied alpha, blends and converts back
+    #[serde(rename = "linear")]
+    Linear,
 }
 
 impl BlendMode {
@@ -41,6 +155,31 @@ impl BlendMode {
             BlendMode::Darken => "darken",
             BlendMode::Lighten => "lighten",
             BlendMode::Screen => "screen",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Overlay => "overlay",
+            BlendMode::SoftLight => "soft-light",
+            BlendMode::HardLight => "hard-light",
+            BlendMode::ColorDodge => "color-dodge",
+            BlendMode::ColorBurn => "color-burn",
+            BlendMode::LinearDodge => "lighter",
+            BlendMode::Difference => "difference",
+            BlendMode::Exclusion => "exclusion",
+            BlendMode::Hue => "hue",
+            BlendMode::Saturation => "saturation",
+            BlendMode::Color => "color",
+            BlendMode::Luminosity => "luminosity",
+            BlendMode::Source => "copy",
+            BlendMode::SourceIn => "source-in",
+            BlendMode::SourceOut => "source-out",
+            BlendMode::SourceAtop => "source-atop",
+            BlendMode::DestinationOver => "destination-over",
+            BlendMode::DestinationIn => "destination-in",
+            BlendMode::DestinationAtop => "destination-atop",
+            BlendMode::Xor => "xor",
+            // no counterpart on the canvas
+            BlendMode::LinearBurn | BlendMode::Subtract | BlendMode::Divide | BlendMode::Clear => {
+                "source-over"
+            }
         };
         s.to_string()
     }
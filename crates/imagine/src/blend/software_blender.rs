@@ -1,9 +1,11 @@
 use common::{Position, Rectangle};
-use image::{ImageBuffer, Rgba};
+use image::Rgba;
 
-use crate::{BlendMode, Blender, Image};
+use crate::{image::to_eight, BlendMode, Blender, Compositing, Depth, Image, Mask};
 
-pub struct SoftwareBlender {}
+pub struct SoftwareBlender {
+    compositing: Compositing,
+}
 
 /// SoftwareBlender is a basic reference [Blender] that isn't hardware-accelerated.
 impl Blender for SoftwareBlender {
@@ -15,52 +17,38 @@ impl Blender for SoftwareBlender {
         &mut self,
         mode: BlendMode,
         destination: &Rectangle,
-        (overlay_img, overlay_pos, overlay_alpha, _overlay_id): (
+        (overlay_img, overlay_pos, overlay_alpha, _overlay_id, overlay_mask): (
             &Image,
             Position,
             f64,
             Option<usize>,
+            Option<&Mask>,
         ),
         (base_img, base_pos, base_alpha, _base_id): (&Image, Position, f64, Option<usize>),
     ) -> Image {
-        let mut buf = ImageBuffer::new(destination.size.width, destination.size.height);
-        let blending_function = |b: &Rgba<u8>, a: &Rgba<u8>| -> Rgba<u8> {
-            Self::blend_pixel(mode, b, a, base_alpha, overlay_alpha)
-        };
-        let (x_base, y_base) = base_pos.into();
-        let (x_overlay, y_overlay) = overlay_pos.into();
+        let depth = base_img.depth().max(overlay_img.depth());
+        let (width, height) = destination.size.into();
+        let mut result = Image::new_with_depth(width, height, depth);
+        let compositing = self.compositing;
         for position in destination.points() {
-            let (x, y) = position.into();
             // transform into base/active coordinates
-            let b_x = x - x_base;
-            let b_y = y - y_base;
-            let a_x = x - x_overlay;
-            let a_y = y - y_overlay;
-            let i_x = x - destination.position.x;
-            let i_y = y - destination.position.y;
+            let b = position - base_pos;
+            let a = position - overlay_pos;
+            let i = position - destination.position;
             // blend
-            let base_pixel = if b_x >= 0
-                && b_x < base_img.width() as i32
-                && b_y >= 0
-                && b_y < base_img.height() as i32
-            {
-                base_img.buf.get_pixel(b_x as u32, b_y as u32)
-            } else {
-                &Rgba([0, 0, 0, 0])
-            };
-            let active_pixel = if a_x >= 0
-                && a_x < overlay_img.width() as i32
-                && a_y >= 0
-                && a_y < overlay_img.height() as i32
-            {
-                overlay_img.buf.get_pixel(a_x as u32, a_y as u32)
-            } else {
-                &Rgba([0, 0, 0, 0])
-            };
-            let blended_pixel = blending_function(base_pixel, active_pixel);
-            buf.put_pixel(i_x as u32, i_y as u32, blended_pixel);
+            let opacity = overlay_mask.map_or(1.0, |m| m.opacity(&a));
+            let blended_pixel = Self::blend_texel(
+                depth,
+                compositing,
+                mode,
+                texel(base_img, &b),
+                texel(overlay_img, &a),
+                base_alpha,
+                overlay_alpha * opacity,
+            );
+            result.set(i.x as u32, i.y as u32, blended_pixel);
         }
-        Image { buf }
+        result
     }
 
     fn blend_damaged_into(
@@ -71,27 +59,28 @@ impl Blender for SoftwareBlender {
         (overlay_img, overlay_pos, overlay_alpha): (&Image, Position, f64),
         (base_img, base_pos, base_alpha): (&Image, Position, f64),
     ) {
-        let blending_function = |b: &Rgba<u8>, a: &Rgba<u8>| -> Rgba<u8> {
-            Self::blend_pixel(mode, b, a, base_alpha, overlay_alpha)
-        };
-
-        let active_area = Rectangle::of(overlay_pos, overlay_img.size());
+        let depth = destination_img.depth();
+        let compositing = self.compositing;
         let base_area = Rectangle::of(base_pos, base_img.size());
         let dest_area = Rectangle::of(destination_pos, destination_img.size());
 
-        let damage = &Rectangle::intersectn(&[damage, &base_area, &active_area, &dest_area]);
+        // operators like source-in also change the base where there is no overlay
+        let damage = &Rectangle::intersectn(&[damage, &base_area, &dest_area]);
 
         for position in damage.points() {
             let b = position - base_pos;
             let a = position - overlay_pos;
             let i = position - destination_pos;
-            let blended_pixel = blending_function(
-                base_img.buf.get_pixel(b.x as u32, b.y as u32),
-                overlay_img.buf.get_pixel(a.x as u32, a.y as u32),
+            let blended_pixel = Self::blend_texel(
+                depth,
+                compositing,
+                mode,
+                texel(base_img, &b),
+                texel(overlay_img, &a),
+                base_alpha,
+                overlay_alpha,
             );
-            destination_img
-                .buf
-                .put_pixel(i.x as u32, i.y as u32, blended_pixel);
+            destination_img.set(i.x as u32, i.y as u32, blended_pixel);
         }
     }
 
@@ -99,43 +88,31 @@ impl Blender for SoftwareBlender {
         &mut self,
         mode: BlendMode,
         (base_img, base_pos, base_alpha): (&mut Image, Position, f64),
-        (overlay_img, overlay_pos, overlay_alpha): (&Image, Position, f64),
+        (overlay_img, overlay_pos, overlay_alpha, overlay_mask): (
+            &Image,
+            Position,
+            f64,
+            Option<&Mask>,
+        ),
         damage: &Rectangle,
     ) {
-        let blending_function = |b: &Rgba<u8>, a: &Rgba<u8>| -> Rgba<u8> {
-            Self::blend_pixel(mode, b, a, base_alpha, overlay_alpha)
-        };
-        let (x_base, y_base) = base_pos.into();
-        let (x_overlay, y_overlay) = overlay_pos.into();
+        let depth = base_img.depth();
+        let compositing = self.compositing;
         for position in damage.points() {
-            let (x, y) = position.into();
             // transform into base/active coordinates
-            let b_x = x - x_base;
-            let b_y = y - y_base;
-            let a_x = x - x_overlay;
-            let a_y = y - y_overlay;
-
-            let base_pixel = if b_x >= 0
-                && b_x < base_img.width() as i32
-                && b_y >= 0
-                && b_y < base_img.height() as i32
-            {
-                base_img.buf.get_pixel(b_x as u32, b_y as u32)
-            } else {
-                &Rgba([0, 0, 0, 0])
-            };
-            let active_pixel = if a_x >= 0
-                && a_x < overlay_img.width() as i32
-                && a_y >= 0
-                && a_y < overlay_img.height() as i32
-            {
-                overlay_img.buf.get_pixel(a_x as u32, a_y as u32)
-            } else {
-                &Rgba([0, 0, 0, 0])
-            };
-            // let active_pixel = active.0.get_pixel(a_x as u32, a_y as u32);
-            let blended_pixel = blending_function(base_pixel, active_pixel);
-            base_img.put_pixel(b_x as u32, b_y as u32, (&blended_pixel).into());
+            let b = position - base_pos;
+            let a = position - overlay_pos;
+            let opacity = overlay_mask.map_or(1.0, |m| m.opacity(&a));
+            let blended_pixel = Self::blend_texel(
+                depth,
+                compositing,
+                mode,
+                texel(base_img, &b),
+                texel(overlay_img, &a),
+                base_alpha,
+                overlay_alpha * opacity,
+            );
+            base_img.set(b.x as u32, b.y as u32, blended_pixel);
         }
     }
 
@@ -143,6 +120,14 @@ impl Blender for SoftwareBlender {
         // not needed
     }
 
+    fn compositing(&self) -> Compositing {
+        self.compositing
+    }
+
+    fn set_compositing(&mut self, compositing: Compositing) {
+        self.compositing = compositing;
+    }
+
     fn clean(&mut self) {
         // not needed
     }
@@ -156,7 +141,65 @@ impl Default for SoftwareBlender {
 
 impl SoftwareBlender {
     pub fn new() -> Self {
-        SoftwareBlender {}
+        Self::with_compositing(Compositing::default())
+    }
+
+    pub fn with_compositing(compositing: Compositing) -> Self {
+        SoftwareBlender { compositing }
+    }
+
+    /// Blends a single pixel in the color space of the given compositing
+    pub fn blend_pixel_in(
+        compositing: Compositing,
+        blend_mode: BlendMode,
+        b: &Rgba<u8>,
+        a: &Rgba<u8>,
+        alpha_base: f64,
+        alpha_active: f64,
+    ) -> Rgba<u8> {
+        match (compositing, blend_mode) {
+            // only reduces the alpha of the base and keeps its color
+            (Compositing::Srgb, _) | (_, BlendMode::Remove) => {
+                Self::blend_pixel(blend_mode, b, a, alpha_base, alpha_active)
+            }
+            (Compositing::Linear, mode) => {
+                Self::blend_pixel_linear(mode, b, a, alpha_base, alpha_active)
+            }
+        }
+    }
+
+    /// Blends a single pixel in linear light with premultiplied alpha
+    fn blend_pixel_linear(
+        mode: BlendMode,
+        b: &Rgba<u8>,
+        a: &Rgba<u8>,
+        alpha_base: f64,
+        alpha_active: f64,
+    ) -> Rgba<u8> {
+        let (b, a) = (b.0.map(|c| c as f64 / 255.0), a.0.map(|c| c as f64 / 255.0));
+        Rgba(blend_units(Compositing::Linear, mode, b, a, alpha_base, alpha_active).map(to_eight))
+    }
+
+    /// Blends a single pixel with channels from 0 to 1 for an image of the given depth,
+    /// 8-bit images are blended exactly like [SoftwareBlender::blend_pixel_in]
+    pub(crate) fn blend_texel(
+        depth: Depth,
+        compositing: Compositing,
+        mode: BlendMode,
+        b: [f64; 4],
+        a: [f64; 4],
+        alpha_base: f64,
+        alpha_active: f64,
+    ) -> [f64; 4] {
+        match depth {
+            Depth::Eight => {
+                let (b, a) = (Rgba(b.map(to_eight)), Rgba(a.map(to_eight)));
+                let result =
+                    Self::blend_pixel_in(compositing, mode, &b, &a, alpha_base, alpha_active);
+                result.0.map(|c| c as f64 / 255.0)
+            }
+            _ => blend_units(compositing, mode, b, a, alpha_base, alpha_active),
+        }
     }
 
     pub fn blend_pixel(
@@ -191,59 +234,232 @@ impl SoftwareBlender {
                 };
                 result
             }
-            BlendMode::Darken => {
+            mode => {
                 let alpha_a = (a[3] as f64 / 255.0) * alpha_active;
                 let alpha_b = (b[3] as f64 / 255.0) * alpha_base;
-                let mut result = *b;
-                for i in 0..3 {
-                    let c_a = a[i] as f64 / 255.0;
-                    let c_b = b[i] as f64 / 255.0;
-                    let factor = f64::min(c_a * alpha_b * alpha_a, c_b * alpha_a * alpha_b)
-                        + alpha_a * c_a * (1. - alpha_b)
-                        + alpha_b * c_b * (1. - alpha_a);
-                    result[i] = (factor * 255.) as u8;
+                let (f_a, f_b) = operator(mode, alpha_a, alpha_b);
+                let alpha = alpha_a * f_a + alpha_b * f_b;
+                if alpha <= 0.0 {
+                    return Rgba([0, 0, 0, 0]);
                 }
-                result[3] = ((alpha_a + alpha_b - alpha_a * alpha_b) * 255.) as u8;
-                result
-            }
-            BlendMode::Lighten => {
-                let alpha_a = (a[3] as f64 / 255.0) * alpha_active;
-                let alpha_b = (b[3] as f64 / 255.0) * alpha_base;
-                let mut result = *b;
+                let c_a = [a[0], a[1], a[2]].map(|c| c as f64 / 255.0);
+                let c_b = [b[0], b[1], b[2]].map(|c| c as f64 / 255.0);
+                let mixed = mix(mode, c_b, c_a);
+                let mut result = Rgba([0, 0, 0, (alpha * 255.0).round() as u8]);
                 for i in 0..3 {
-                    let c_a = a[i] as f64 / 255.0;
-                    let c_b = b[i] as f64 / 255.0;
-                    let factor = f64::max(c_a * alpha_b * alpha_a, c_b * alpha_a * alpha_b)
-                        + alpha_a * c_a * (1. - alpha_b)
-                        + alpha_b * c_b * (1. - alpha_a);
-                    result[i] = (factor * 255.) as u8;
+                    // the overlay turns into the mixed color where the base is
+                    let c_s = (1.0 - alpha_b) * c_a[i] + alpha_b * mixed[i];
+                    let c = c_s * alpha_a * f_a + c_b[i] * alpha_b * f_b;
+                    result[i] = (c / alpha * 255.0).round().clamp(0.0, 255.0) as u8;
                 }
-                result[3] = ((alpha_a + alpha_b - alpha_a * alpha_b) * 255.) as u8;
                 result
             }
-            BlendMode::Screen => {
-                let mut result = *b;
-                let sa = alpha_active;
-                let da = alpha_base;
-                for i in 0..3 {
-                    let sc = a[i] as f64 / 255.0;
-                    let dc = b[i] as f64 / 255.0;
-                    let sca = sc * sa;
-                    let dca = dc * da;
-                    result[i] = ((sca + dca - sca * dca) * 255.) as u8;
+        }
+    }
+}
+
+/// Channels of the pixel at the position (in image coordinates), transparent outside of the image
+fn texel(img: &Image, pos: &Position) -> [f64; 4] {
+    if img.contains(pos) {
+        img.get(pos.x as u32, pos.y as u32)
+    } else {
+        [0.0; 4]
+    }
+}
+
+/// Blends channels from 0 to 1 with premultiplied alpha, in linear light if the compositing asks for it.
+/// Removing only reduces the alpha of the base and keeps its color.
+fn blend_units(
+    compositing: Compositing,
+    mode: BlendMode,
+    b: [f64; 4],
+    a: [f64; 4],
+    alpha_base: f64,
+    alpha_active: f64,
+) -> [f64; 4] {
+    let alpha_a = a[3] * alpha_active;
+    let alpha_b = b[3] * alpha_base;
+    if mode == BlendMode::Remove {
+        return [b[0], b[1], b[2], (alpha_b - alpha_a).max(0.0)];
+    }
+    let (f_a, f_b) = operator(mode, alpha_a, alpha_b);
+    let alpha = alpha_a * f_a + alpha_b * f_b;
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let linear = compositing == Compositing::Linear;
+    let decode = |c: f64| if linear { to_linear(c) } else { c };
+    let encode = |c: f64| if linear { from_linear(c) } else { c };
+    let c_a = [a[0], a[1], a[2]].map(decode);
+    let c_b = [b[0], b[1], b[2]].map(decode);
+    let mixed = mix(mode, c_b, c_a);
+    let mut result = [0.0, 0.0, 0.0, alpha];
+    for i in 0..3 {
+        // the overlay turns into the mixed color where the base is
+        let p_s = alpha_a * ((1.0 - alpha_b) * c_a[i] + alpha_b * mixed[i]);
+        let p_b = alpha_b * c_b[i];
+        result[i] = encode(((p_s * f_a + p_b * f_b) / alpha).clamp(0.0, 1.0));
+    }
+    result
+}
+
+/// Decodes an sRGB channel into linear light
+pub(crate) fn to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light into an sRGB channel
+pub(crate) fn from_linear(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Porter-Duff factors the overlay and the base contribute with, blend modes are composited source-over
+fn operator(mode: BlendMode, alpha_a: f64, alpha_b: f64) -> (f64, f64) {
+    match mode {
+        BlendMode::Source => (1.0, 0.0),
+        BlendMode::SourceIn => (alpha_b, 0.0),
+        BlendMode::SourceOut => (1.0 - alpha_b, 0.0),
+        BlendMode::SourceAtop => (alpha_b, 1.0 - alpha_a),
+        BlendMode::DestinationOver => (1.0 - alpha_b, 1.0),
+        BlendMode::DestinationIn => (0.0, alpha_a),
+        BlendMode::DestinationAtop => (1.0 - alpha_b, alpha_a),
+        BlendMode::Xor => (1.0 - alpha_b, 1.0 - alpha_a),
+        BlendMode::Clear => (0.0, 0.0),
+        _ => (1.0, 1.0 - alpha_a),
+    }
+}
+
+/// Blend function B(cb, cs) of the W3C compositing specification, mixing the color of the base and the source.
+/// Porter-Duff operators keep the source.
+fn mix(mode: BlendMode, cb: [f64; 3], cs: [f64; 3]) -> [f64; 3] {
+    match mode {
+        BlendMode::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+        BlendMode::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+        BlendMode::Color => set_lum(cs, lum(cb)),
+        BlendMode::Luminosity => set_lum(cb, lum(cs)),
+        mode => [0, 1, 2].map(|i| separable(mode, cb[i], cs[i])),
+    }
+}
+
+/// Blend function of the separable modes, applied to each channel on its own
+fn separable(mode: BlendMode, cb: f64, cs: f64) -> f64 {
+    let hard_light = |cb: f64, cs: f64| {
+        if cs <= 0.5 {
+            cb * 2.0 * cs
+        } else {
+            let cs = 2.0 * cs - 1.0;
+            cb + cs - cb * cs
+        }
+    };
+    match mode {
+        BlendMode::Darken => cb.min(cs),
+        BlendMode::Lighten => cb.max(cs),
+        BlendMode::Screen => cb + cs - cb * cs,
+        BlendMode::Multiply => cb * cs,
+        BlendMode::Overlay => hard_light(cs, cb),
+        BlendMode::HardLight => hard_light(cb, cs),
+        BlendMode::SoftLight => {
+            if cs <= 0.5 {
+                cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+            } else {
+                let d = if cb <= 0.25 {
+                    ((16.0 * cb - 12.0) * cb + 4.0) * cb
+                } else {
+                    cb.sqrt()
+                };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+        }
+        BlendMode::ColorDodge => {
+            if cb == 0.0 {
+                0.0
+            } else if cs == 1.0 {
+                1.0
+            } else {
+                (cb / (1.0 - cs)).min(1.0)
+            }
+        }
+        BlendMode::ColorBurn => {
+            if cb == 1.0 {
+                1.0
+            } else if cs == 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - cb) / cs).min(1.0)
+            }
+        }
+        BlendMode::LinearBurn => (cb + cs - 1.0).max(0.0),
+        BlendMode::LinearDodge => (cb + cs).min(1.0),
+        BlendMode::Difference => (cb - cs).abs(),
+        BlendMode::Exclusion => cb + cs - 2.0 * cb * cs,
+        BlendMode::Subtract => (cb - cs).max(0.0),
+        BlendMode::Divide => {
+            if cs == 0.0 {
+                if cb == 0.0 {
+                    0.0
+                } else {
+                    1.0
                 }
-                result[3] = ((sa + da - sa * da) * 255.) as u8;
-                result
+            } else {
+                (cb / cs).min(1.0)
             }
         }
+        _ => cs,
+    }
+}
+
+fn lum([r, g, b]: [f64; 3]) -> f64 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(c: [f64; 3]) -> [f64; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    c.map(|c| {
+        let mut c = c;
+        if n < 0.0 {
+            c = l + (c - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            c = l + (c - l) * (1.0 - l) / (x - l);
+        }
+        c
+    })
+}
+
+fn set_lum(c: [f64; 3], l: f64) -> [f64; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|c| c + d))
+}
+
+fn sat(c: [f64; 3]) -> f64 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f64; 3], s: f64) -> [f64; 3] {
+    let (max, min) = (c[0].max(c[1]).max(c[2]), c[0].min(c[1]).min(c[2]));
+    if max > min {
+        c.map(|c| (c - min) * s / (max - min))
+    } else {
+        [0.0; 3]
     }
 }
 
 #[cfg(test)]
 mod test {
+    use common::Color;
     use image::Rgba;
 
-    use crate::{Blender, Image, SoftwareBlender};
+    use crate::{Blender, Compositing, Image, Mask, SoftwareBlender};
 
     use super::BlendMode;
 
@@ -289,4 +505,156 @@ mod test {
         let expected = Image::new_four_pixels("#ffffffff", "#ffffffff", "#00000000", "#00ffffff");
         assert_eq!(dest, expected);
     }
+
+    #[test]
+    fn mask_reduces_alpha_of_overlay() {
+        let overlay = Image::new_four_pixels("#ff0000ff", "#ff0000ff", "#ff0000ff", "#ff0000ff");
+        let mut mask = Mask::full(2, 2);
+        mask.set(1, 0, 0);
+        mask.set(0, 1, 128);
+        let mut base = Image::new(3, 2);
+        let mut blender = SoftwareBlender::new();
+        blender.blend_damaged(
+            BlendMode::Alpha,
+            (&mut base, (0, 0).into(), 1.0),
+            (&overlay, (1, 0).into(), 1.0, Some(&mask)),
+            &(0, 0, 3, 2).into(),
+        );
+        assert_eq!(base.pixel(1, 0).a, 255);
+        assert_eq!(base.pixel(2, 0).a, 0);
+        assert_eq!(base.pixel(1, 1).a, 128);
+    }
+
+    /// Base (0.8, 0.4, 0.2) with the overlay (0.4, 0.6, 0.8), opaque and with half of its alpha
+    #[test]
+    fn blend_mode_vectors() {
+        let vectors = [
+            ("multiply", [82, 61, 41], [143, 82, 46]),
+            ("overlay", [194, 122, 82], [199, 112, 66]),
+            ("soft_light", [196, 114, 89], [200, 108, 70]),
+            ("hard_light", [163, 133, 173], [184, 117, 112]),
+            ("color_dodge", [255, 255, 255], [230, 179, 153]),
+            ("color_burn", [128, 0, 0], [166, 51, 25]),
+            ("linear_burn", [51, 0, 0], [127, 51, 25]),
+            ("linear_dodge", [255, 255, 255], [230, 179, 153]),
+            ("difference", [102, 51, 153], [153, 76, 102]),
+            ("exclusion", [143, 133, 173], [173, 117, 112]),
+            ("subtract", [102, 0, 0], [153, 51, 25]),
+            ("divide", [255, 170, 64], [230, 136, 57]),
+            ("hue", [65, 142, 218], [134, 122, 135]),
+            ("saturation", [178, 110, 76], [191, 106, 64]),
+            ("color", [86, 137, 188], [145, 119, 120]),
+            ("luminosity", [220, 118, 67], [212, 110, 59]),
+            ("darken", [102, 102, 51], [153, 102, 51]),
+            ("lighten", [204, 153, 204], [204, 128, 128]),
+            ("screen", [224, 194, 214], [214, 148, 133]),
+        ];
+        let base = Rgba([204, 102, 51, 255]);
+        for (name, opaque, half) in vectors {
+            let mode: BlendMode = serde_json::from_str(&format!("\"{name}\"")).unwrap();
+            let [r, g, b] = opaque;
+            let overlay = Rgba([102, 153, 204, 255]);
+            let result = SoftwareBlender::blend_pixel(mode, &base, &overlay, 1.0, 1.0);
+            assert_eq!(result, Rgba([r, g, b, 255]), "{name}");
+            let [r, g, b] = half;
+            let overlay = Rgba([102, 153, 204, 128]);
+            let result = SoftwareBlender::blend_pixel(mode, &base, &overlay, 1.0, 1.0);
+            assert_eq!(result, Rgba([r, g, b, 255]), "{name} with half alpha");
+            // nothing to blend with
+            let empty = Rgba([0, 0, 0, 0]);
+            let result = SoftwareBlender::blend_pixel(mode, &empty, &overlay, 1.0, 1.0);
+            assert_eq!(result, overlay, "{name} on transparent");
+        }
+    }
+
+    #[test]
+    fn porter_duff_vectors() {
+        let vectors = [
+            ("source", [102, 153, 204, 191]),
+            ("source_in", [102, 153, 204, 96]),
+            ("source_out", [102, 153, 204, 95]),
+            ("source_atop", [128, 140, 166, 128]),
+            ("destination_over", [161, 124, 116, 223]),
+            ("destination_in", [204, 102, 51, 96]),
+            ("destination_atop", [153, 127, 127, 191]),
+            ("xor", [128, 140, 165, 127]),
+            ("clear", [0, 0, 0, 0]),
+        ];
+        let base = Rgba([204, 102, 51, 128]);
+        let overlay = Rgba([102, 153, 204, 191]);
+        for (name, expected) in vectors {
+            let mode: BlendMode = serde_json::from_str(&format!("\"{name}\"")).unwrap();
+            let result = SoftwareBlender::blend_pixel(mode, &base, &overlay, 1.0, 1.0);
+            assert_eq!(result, Rgba(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn operators_change_base_outside_of_overlay() {
+        let base = Image::new_four_pixels("#ffffffff", "#ffffffff", "#ffffffff", "#ffffffff");
+        let overlay = Image::new_four_pixels("#ff0000ff", "#ff0000ff", "#ff0000ff", "#ff0000ff");
+        let mut dest = Image::new(2, 2);
+        let mut blender = SoftwareBlender::new();
+        blender.blend_damaged_into(
+            BlendMode::DestinationIn,
+            &(0, 0, 2, 2).into(),
+            (&mut dest, (0, 0).into()),
+            (&overlay, (1, 0).into(), 1.0),
+            (&base, (0, 0).into(), 1.0),
+        );
+        assert_eq!(dest.pixel(0, 0).a, 0);
+        assert_eq!(dest.pixel(1, 1).a, 255);
+        let mut base = Image::new_four_pixels("#ffffffff", "#ffffffff", "#ffffffff", "#ffffffff");
+        blender.blend_damaged(
+            BlendMode::SourceIn,
+            (&mut base, (0, 0).into(), 1.0),
+            (&overlay, (1, 0).into(), 1.0, None),
+            &(0, 0, 2, 2).into(),
+        );
+        assert_eq!(base.pixel(0, 1).a, 0);
+        assert_eq!(base.pixel(1, 0), Color::RED);
+    }
+
+    #[test]
+    fn linear_compositing() {
+        for c in 0..=255 {
+            let linear = super::to_linear(c as f64 / 255.0);
+            assert_eq!(super::to_eight(super::from_linear(linear)), c);
+        }
+        let red = Rgba([255, 0, 0, 255]);
+        let green = Rgba([0, 255, 0, 255]);
+        let srgb = SoftwareBlender::blend_pixel_in(
+            Compositing::Srgb,
+            BlendMode::Alpha,
+            &green,
+            &red,
+            1.0,
+            0.5,
+        );
+        let linear = SoftwareBlender::blend_pixel_in(
+            Compositing::Linear,
+            BlendMode::Alpha,
+            &green,
+            &red,
+            1.0,
+            0.5,
+        );
+        assert!(srgb[0] <= 128 && srgb[1] <= 128);
+        // half of the light of both, brighter than the average of the encoded values
+        assert!(linear[0] >= 187 && linear[1] >= 187);
+        assert_eq!(linear[3], 255);
+
+        // no dark fringes when blending onto transparency
+        let mut blender = SoftwareBlender::with_compositing(Compositing::Linear);
+        let base = Image::new(1, 1);
+        let overlay = Image::new_four_pixels("#ff000080", "#ff000080", "#ff000080", "#ff000080");
+        let result = blender.blend(
+            BlendMode::Multiply,
+            &(0, 0, 1, 1).into(),
+            (&overlay, (0, 0).into(), 1.0, None, None),
+            (&base, (0, 0).into(), 1.0, None),
+        );
+        assert_eq!(result.pixel(0, 0).r, 255);
+        assert_eq!(result.pixel(0, 0).a, 128);
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::{BlendMode, Blender, Image, SoftwareBlender};
+use crate::{image::Buffer, BlendMode, Blender, Compositing, Depth, Image, Mask, SoftwareBlender};
 use common::{Position, Rectangle};
 use image::RgbaImage;
 use js_sys::Map;
@@ -15,7 +15,7 @@ use super::blender::BlendLayer;
 // TODO Code is really rough right now with lots of unwraps
 
 /// WebGlBlender is a [Blender] that relies on WebGl.
-/// POC can only perform alpha-blending on full-image blending right now. Uses [SoftwareBlender] as a fallback otherwise.
+/// POC can only perform alpha-blending of 8-bit images in sRGB without masks on full-image blending right now. Uses [SoftwareBlender] as a fallback otherwise.
 pub struct WebGlBlender {
     fallback: SoftwareBlender,
     program: WebGlProgram,
@@ -73,14 +73,14 @@ impl WebGlBlender {
     fn blend_all_fallback(&mut self, destination: &Rectangle, children: Vec<BlendLayer>) -> Image {
         let (width, height) = (destination.size.width, destination.size.height);
         let mut result = Image::new(width, height);
-        for (mode, img, pos, alpha, visible, marker) in children {
+        for (mode, img, pos, alpha, visible, marker, mask) in children {
             if !visible {
                 continue;
             }
             result = self.blend(
                 mode,
                 destination,
-                (img, pos, alpha, marker),
+                (img, pos, alpha, marker, mask),
                 (&result, destination.position, 1.0, None),
             );
         }
@@ -97,19 +97,31 @@ impl Blender for WebGlBlender {
         &mut self,
         mode: BlendMode,
         destination: &Rectangle,
-        (overlay_img, overlay_pos, overlay_alpha, overlay_id): (
+        (overlay_img, overlay_pos, overlay_alpha, overlay_id, overlay_mask): (
             &Image,
             Position,
             f64,
             Option<usize>,
+            Option<&Mask>,
         ),
         (base_img, base_pos, base_alpha, base_id): (&Image, Position, f64, Option<usize>),
     ) -> Image {
-        if mode != BlendMode::Alpha {
+        if mode != BlendMode::Alpha
+            || overlay_mask.is_some()
+            || self.fallback.compositing() != Compositing::Srgb
+            || base_img.depth() != Depth::Eight
+            || overlay_img.depth() != Depth::Eight
+        {
             return self.fallback.blend(
                 mode,
                 destination,
-                (overlay_img, overlay_pos, overlay_alpha, overlay_id),
+                (
+                    overlay_img,
+                    overlay_pos,
+                    overlay_alpha,
+                    overlay_id,
+                    overlay_mask,
+                ),
                 (base_img, base_pos, base_alpha, base_id),
             );
         }
@@ -226,7 +238,9 @@ impl Blender for WebGlBlender {
         .unwrap();
 
         let buf = RgbaImage::from_raw(w, h, result).unwrap();
-        Image { buf }
+        Image {
+            buf: Buffer::Eight(buf),
+        }
     }
 
     fn blend_damaged_into(
@@ -245,7 +259,7 @@ impl Blender for WebGlBlender {
         &mut self,
         mode: BlendMode,
         base: (&mut Image, Position, f64),
-        overlay: (&Image, Position, f64),
+        overlay: (&Image, Position, f64, Option<&Mask>),
         damage: &Rectangle,
     ) {
         self.fallback.blend_damaged(mode, base, overlay, damage);
@@ -267,17 +281,25 @@ impl Blender for WebGlBlender {
         self.cache.insert(marker, texture);
     }
 
+    fn compositing(&self) -> Compositing {
+        self.fallback.compositing()
+    }
+
+    fn set_compositing(&mut self, compositing: Compositing) {
+        self.fallback.set_compositing(compositing);
+    }
+
     fn clean(&mut self) {
         self.cache = HashMap::new();
         self.ping_pong = None;
     }
 
-    fn blend_all(
-        &mut self,
-        destination: &Rectangle,
-        children: Vec<(BlendMode, &Image, Position, f64, bool, Option<usize>)>,
-    ) -> Image {
-        if !children.iter().all(|x| x.0 == BlendMode::Alpha) {
+    fn blend_all(&mut self, destination: &Rectangle, children: Vec<BlendLayer>) -> Image {
+        if self.fallback.compositing() != Compositing::Srgb
+            || !children
+                .iter()
+                .all(|x| x.0 == BlendMode::Alpha && x.6.is_none() && x.1.depth() == Depth::Eight)
+        {
             return self.blend_all_fallback(destination, children);
         }
         let (w, h) = destination.size.into();
@@ -402,7 +424,7 @@ impl Blender for WebGlBlender {
 
         let mut ping_pong = true;
 
-        for (_mode, img, position, alpha, visible, marker) in children {
+        for (_mode, img, position, alpha, visible, marker, _mask) in children {
             if !visible {
                 continue;
             }
@@ -450,7 +472,9 @@ impl Blender for WebGlBlender {
         .unwrap();
 
         let buf = RgbaImage::from_raw(w, h, result).unwrap();
-        Image { buf }
+        Image {
+            buf: Buffer::Eight(buf),
+        }
     }
 }
 
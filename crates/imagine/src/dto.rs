@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
 
-use super::Image;
+use super::{Font, Image, WorkingSpace};
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
 pub enum ImageSource {
@@ -25,18 +26,70 @@ pub enum DtoTransformError {
     Base64Decode,
     Network,
     NoSuchPart,
+    InvalidFont,
 }
 
 impl ImageDto {
-    pub fn to_image(&self, context: &HashMap<String, Image>) -> Result<Image, DtoTransformError> {
+    /// The image converted into the working space, images of the context have been decoded into sRGB
+    pub fn to_image(
+        &self,
+        context: &HashMap<String, Image>,
+        space: WorkingSpace,
+    ) -> Result<Image, DtoTransformError> {
         match self.src {
             ImageSource::Base64Png => {
-                Image::from_base64(&self.data).map_err(|_| DtoTransformError::Base64Decode)
+                let bytes = STANDARD
+                    .decode(&self.data)
+                    .map_err(|_| DtoTransformError::Base64Decode)?;
+                Image::from_bytes_in(&bytes, space).map_err(|_| DtoTransformError::Base64Decode)
             }
-            ImageSource::Multipart => Ok(context
+            ImageSource::Multipart => {
+                let mut img = context
+                    .get(&self.data)
+                    .ok_or(DtoTransformError::NoSuchPart)?
+                    .clone();
+                WorkingSpace::Srgb
+                    .profile()
+                    .convert(&mut img, &space.profile());
+                Ok(img)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub enum FontSource {
+    /// The font that ships with the application, `data` is ignored
+    #[serde(rename = "bundled")]
+    Bundled,
+
+    #[serde(rename = "encode/base64")]
+    Base64,
+
+    #[serde(rename = "multipart")]
+    Multipart,
+}
+
+/// Represents a [Font] like [ImageDto] represents an image
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct FontDto {
+    pub src: FontSource,
+    #[serde(default)]
+    pub data: String,
+}
+
+impl FontDto {
+    pub fn to_font(&self, context: &HashMap<String, Vec<u8>>) -> Result<Font, DtoTransformError> {
+        let bytes = match self.src {
+            FontSource::Bundled => return Ok(Font::bundled()),
+            FontSource::Base64 => STANDARD
+                .decode(&self.data)
+                .map_err(|_| DtoTransformError::Base64Decode)?,
+            FontSource::Multipart => context
                 .get(&self.data)
                 .ok_or(DtoTransformError::NoSuchPart)?
-                .clone()),
-        }
+                .clone(),
+        };
+        Font::from_bytes(bytes).ok_or(DtoTransformError::InvalidFont)
     }
 }
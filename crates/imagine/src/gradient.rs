@@ -0,0 +1,298 @@
+use std::f64::consts::PI;
+
+use common::{Color, Point, Rectangle};
+use serde::{Deserialize, Serialize};
+
+use crate::{Depth, Image};
+
+/// 4x4 Bayer matrix for ordered dithering
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum GradientShape {
+    /// Bands perpendicular to the line from start to end
+    #[serde(rename = "linear")]
+    Linear,
+
+    /// Circles around the start reaching the end
+    #[serde(rename = "radial")]
+    Radial,
+
+    /// Sweeps once around the start, beginning in the direction of the end
+    #[serde(rename = "conic")]
+    Conic,
+
+    /// Squares around the start with a corner at the end
+    #[serde(rename = "diamond")]
+    Diamond,
+
+    /// Linear gradient mirrored at the start
+    #[serde(rename = "reflected")]
+    Reflected,
+}
+
+/// What happens beyond the end of the gradient
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum GradientRepeat {
+    /// Continues with the color of the last stop
+    #[default]
+    #[serde(rename = "none")]
+    None,
+
+    /// Starts over
+    #[serde(rename = "repeat")]
+    Repeat,
+
+    /// Runs back and forth
+    #[serde(rename = "mirror")]
+    Mirror,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ColorStop {
+    /// Position on the gradient from 0 (start) to 1 (end)
+    pub offset: f64,
+    pub color: Color,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct AlphaStop {
+    /// Position on the gradient from 0 (start) to 1 (end)
+    pub offset: f64,
+    /// Opacity from 0 to 1, multiplied with the alpha of the color
+    pub alpha: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    pub colors: Vec<ColorStop>,
+    /// Opacity ramp independent of the color stops, fully opaque if empty
+    #[serde(default)]
+    pub alphas: Vec<AlphaStop>,
+    #[serde(default)]
+    pub repeat: GradientRepeat,
+    /// Ordered dithering against visible banding
+    #[serde(default)]
+    pub dither: bool,
+}
+
+impl Gradient {
+    /// Position on the gradient for the point, before repetition is applied
+    fn parameter(&self, p: Point, start: Point, end: Point) -> f64 {
+        let axis = end - start;
+        let length = axis.length();
+        let d = p - start;
+        // coordinates along and across the axis, in units of its length
+        let along = d.dot(&axis) / (length * length);
+        let across = axis.cross(&d) / (length * length);
+        match self.shape {
+            GradientShape::Linear => along,
+            GradientShape::Reflected => along.abs(),
+            GradientShape::Radial => d.length() / length,
+            GradientShape::Diamond => along.abs() + across.abs(),
+            GradientShape::Conic => (across.atan2(along) / (2.0 * PI)).rem_euclid(1.0),
+        }
+    }
+
+    /// Applies the repeat mode to the position on the gradient
+    fn repeated(&self, t: f64) -> f64 {
+        match self.repeat {
+            GradientRepeat::None => t.clamp(0.0, 1.0),
+            GradientRepeat::Repeat => t.rem_euclid(1.0),
+            GradientRepeat::Mirror => 1.0 - (t.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+}
+
+/// Stops sorted by their offset, with values from 0 to 1
+struct Ramp<const N: usize>(Vec<(f64, [f64; N])>);
+
+impl<const N: usize> Ramp<N> {
+    fn new(mut stops: Vec<(f64, [f64; N])>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ramp(stops)
+    }
+
+    /// Linear interpolation between the stops, `None` if there are no stops
+    fn at(&self, t: f64) -> Option<[f64; N]> {
+        let (first, last) = (self.0.first()?, self.0.last()?);
+        if t <= first.0 {
+            return Some(first.1);
+        }
+        if t >= last.0 {
+            return Some(last.1);
+        }
+        let (a, b) = self
+            .0
+            .iter()
+            .zip(self.0.iter().skip(1))
+            .find(|(a, b)| a.0 <= t && t <= b.0)?;
+        let f = if b.0 > a.0 {
+            (t - a.0) / (b.0 - a.0)
+        } else {
+            0.0
+        };
+        let mut result = a.1;
+        for (r, (from, to)) in result.iter_mut().zip(a.1.iter().zip(b.1.iter())) {
+            *r = from + (to - from) * f;
+        }
+        Some(result)
+    }
+}
+
+impl Image {
+    /// Replaces the content of the image with the gradient running from `start` to `end` (in image coordinates).
+    /// Nothing is drawn if start and end are the same.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn draw_gradient(&mut self, gradient: &Gradient, start: Point, end: Point) -> Rectangle {
+        if start == end {
+            return Rectangle::new(0, 0, 0, 0);
+        }
+        let colors = Ramp::new(
+            gradient
+                .colors
+                .iter()
+                .map(|s| {
+                    let c = s.color;
+                    (s.offset, [c.r, c.g, c.b, c.a].map(|v| v as f64 / 255.0))
+                })
+                .collect(),
+        );
+        let alphas = Ramp::new(
+            gradient
+                .alphas
+                .iter()
+                .map(|s| (s.offset, [s.alpha.clamp(0.0, 1.0)]))
+                .collect(),
+        );
+        let depth = self.depth();
+        for (x, y) in self.points() {
+            let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+            let t = gradient.repeated(gradient.parameter(center, start, end));
+            let mut color = colors.at(t).unwrap_or([0.0; 4]);
+            if let Some([alpha]) = alphas.at(t) {
+                color[3] *= alpha;
+            }
+            // banding only shows with 8-bit
+            if depth == Depth::Eight {
+                let offset = if gradient.dither {
+                    (BAYER[y as usize % 4][x as usize % 4] as f64 + 0.5) / 16.0 - 0.5
+                } else {
+                    0.0
+                };
+                color = color.map(|c| (c * 255.0 + offset).round().clamp(0.0, 255.0) / 255.0);
+            }
+            self.set(x, y, color);
+        }
+        Rectangle::of((0, 0).into(), self.size())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Point};
+
+    use super::{AlphaStop, ColorStop, Gradient, GradientRepeat, GradientShape};
+    use crate::Image;
+
+    fn gradient(shape: GradientShape, repeat: GradientRepeat) -> Gradient {
+        Gradient {
+            shape,
+            colors: vec![
+                ColorStop {
+                    offset: 0.0,
+                    color: Color::BLACK,
+                },
+                ColorStop {
+                    offset: 1.0,
+                    color: Color::RED,
+                },
+            ],
+            alphas: vec![],
+            repeat,
+            dither: false,
+        }
+    }
+
+    #[test]
+    fn linear_with_repeat_modes() {
+        let (start, end) = (Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let mut img = Image::new(30, 1);
+        img.draw_gradient(
+            &gradient(GradientShape::Linear, GradientRepeat::None),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(0, 0).r, 13);
+        assert_eq!(img.pixel(4, 0).r, 115);
+        assert_eq!(img.pixel(25, 0), Color::RED);
+        img.draw_gradient(
+            &gradient(GradientShape::Linear, GradientRepeat::Repeat),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(14, 0).r, 115);
+        img.draw_gradient(
+            &gradient(GradientShape::Linear, GradientRepeat::Mirror),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(15, 0).r, 115);
+    }
+
+    #[test]
+    fn shapes() {
+        let (start, end) = (Point::new(10.0, 10.0), Point::new(15.0, 10.0));
+        let mut img = Image::new(20, 20);
+        img.draw_gradient(
+            &gradient(GradientShape::Reflected, GradientRepeat::None),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(7, 3), img.pixel(12, 3));
+        img.draw_gradient(
+            &gradient(GradientShape::Radial, GradientRepeat::None),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(4, 9), img.pixel(9, 15));
+        img.draw_gradient(
+            &gradient(GradientShape::Diamond, GradientRepeat::None),
+            start,
+            end,
+        );
+        assert_eq!(img.pixel(13, 11), img.pixel(11, 13));
+        assert_eq!(img.pixel(16, 10), Color::RED);
+        img.draw_gradient(
+            &gradient(GradientShape::Conic, GradientRepeat::None),
+            start,
+            end,
+        );
+        assert!(img.pixel(15, 9).r > 250);
+        assert!(img.pixel(15, 10).r < 5);
+    }
+
+    #[test]
+    fn alpha_ramp_and_dithering() {
+        let mut g = gradient(GradientShape::Linear, GradientRepeat::None);
+        g.alphas = vec![
+            AlphaStop {
+                offset: 0.0,
+                alpha: 1.0,
+            },
+            AlphaStop {
+                offset: 1.0,
+                alpha: 0.0,
+            },
+        ];
+        let mut img = Image::new(10, 4);
+        img.draw_gradient(&g, Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        assert_eq!(img.pixel(5, 0).a, 115);
+        g.dither = true;
+        img.draw_gradient(&g, Point::new(0.0, 0.0), Point::new(1000.0, 0.0));
+        let reds: Vec<u8> = (0..4).map(|y| img.pixel(5, y).r).collect();
+        assert!(reds.iter().any(|r| *r != reds[0]));
+    }
+}
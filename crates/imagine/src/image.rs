@@ -1,19 +1,73 @@
 use std::{
     error::Error,
     fmt::Display,
-    io::{BufWriter, Cursor},
+    io::{Cursor, Seek, Write},
 };
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use common::{Color, Position, Rectangle, Size};
 #[allow(unused_imports)]
-use image::{EncodableLayout, ImageBuffer, ImageError, ImageFormat, Rgba, RgbaImage};
+use image::{
+    ColorType, DynamicImage, EncodableLayout, ImageBuffer, ImageError, ImageFormat,
+    ImageOutputFormat, Rgba, Rgba32FImage, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use tiff::{
+    encoder::{
+        colortype::{self, RGBA16, RGBA8},
+        TiffEncoder, TiffValue,
+    },
+    tags::Tag,
+    TiffResult,
+};
+
+use crate::profile::{embedded_profile, ICC_TAG};
+use crate::{BlendMode, Compositing, SoftwareBlender, WorkingSpace};
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::wasm_bindgen;
 #[cfg(feature = "wasm")]
 use web_sys::ImageData;
 
+/// Precision of the channels of an image
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Depth {
+    /// 8-bit integer per channel
+    #[default]
+    #[serde(rename = "u8")]
+    Eight,
+
+    /// 16-bit integer per channel
+    #[serde(rename = "u16")]
+    Sixteen,
+
+    /// 32-bit floating point per channel
+    #[serde(rename = "f32")]
+    Float,
+}
+
+/// Pixel storage of an [Image] in one of the depths
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum Buffer {
+    Eight(RgbaImage),
+    Sixteen(ImageBuffer<Rgba<u16>, Vec<u16>>),
+    Float(Rgba32FImage),
+}
+
+/// Evaluates the expression with the image buffer of whatever depth bound to the name
+macro_rules! each_buffer {
+    ($buf:expr, $b:ident => $e:expr) => {
+        match $buf {
+            Buffer::Eight($b) => $e,
+            Buffer::Sixteen($b) => $e,
+            Buffer::Float($b) => $e,
+        }
+    };
+}
+pub(crate) use each_buffer;
+
 /// This struct abstracts the implementation of the actual image away.
 /// It's currently using image-rs under the hood but that is an implementation detail.
 ///
@@ -22,7 +76,7 @@ use web_sys::ImageData;
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(PartialEq, Debug)]
 pub struct Image {
-    pub(crate) buf: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pub(crate) buf: Buffer,
 }
 
 impl Image {
@@ -39,21 +93,62 @@ impl Image {
     /// assert_eq!(h, 200);
     /// ```
     pub fn new(width: u32, height: u32) -> Self {
-        log::debug!("Creating image {} x {}", width, height);
-        let buf = ImageBuffer::new(width, height);
+        Self::new_with_depth(width, height, Depth::Eight)
+    }
+
+    /// Creates a new empty image with the given size and depth.
+    pub fn new_with_depth(width: u32, height: u32, depth: Depth) -> Self {
+        log::debug!("Creating image {} x {} ({:?})", width, height, depth);
+        let buf = match depth {
+            Depth::Eight => Buffer::Eight(ImageBuffer::new(width, height)),
+            Depth::Sixteen => Buffer::Sixteen(ImageBuffer::new(width, height)),
+            Depth::Float => Buffer::Float(ImageBuffer::new(width, height)),
+        };
         Image { buf }
     }
 
     pub fn new_from_color(width: u32, height: u32, color: &Color) -> Self {
         log::debug!("Creating image {} x {}", width, height);
-        let buf = ImageBuffer::from_pixel(width, height, (*color).into());
+        let buf = Buffer::Eight(ImageBuffer::from_pixel(width, height, (*color).into()));
         Image { buf }
     }
 
     pub fn size(&self) -> Size {
         Size {
-            width: self.buf.width(),
-            height: self.buf.height(),
+            width: self.width(),
+            height: self.height(),
+        }
+    }
+
+    pub fn depth(&self) -> Depth {
+        match self.buf {
+            Buffer::Eight(_) => Depth::Eight,
+            Buffer::Sixteen(_) => Depth::Sixteen,
+            Buffer::Float(_) => Depth::Float,
+        }
+    }
+
+    /// The image converted to the given depth, converting to a lower depth loses precision
+    pub fn to_depth(&self, depth: Depth) -> Self {
+        if depth == self.depth() {
+            return self.clone();
+        }
+        let (width, height) = self.size().into();
+        let mut result = Image::new_with_depth(width, height, depth);
+        for y in 0..height {
+            for x in 0..width {
+                result.set(x, y, self.get(x, y));
+            }
+        }
+        result
+    }
+
+    /// The image in the given depth, without copying it if it already has the depth
+    pub fn into_depth(self, depth: Depth) -> Self {
+        if depth == self.depth() {
+            self
+        } else {
+            self.to_depth(depth)
         }
     }
 
@@ -67,11 +162,11 @@ impl Image {
                 let dis = middle.distance_to(&(x as i32, y as i32).into());
 
                 if dis <= inner_radius {
-                    stamp.buf.put_pixel(x, y, color.with_alpha(255u8).into());
+                    stamp.put_pixel(x, y, color.with_alpha(255u8));
                 } else if dis <= radius {
                     let alpha = (1. - ((dis - inner_radius) / (radius - inner_radius))).powf(3.);
                     let alpha = (alpha * 255.) as u8;
-                    stamp.buf.put_pixel(x, y, color.with_alpha(alpha).into());
+                    stamp.put_pixel(x, y, color.with_alpha(alpha));
                 }
             }
         }
@@ -97,89 +192,260 @@ impl Image {
         buf.put_pixel(1, 0, b.into());
         buf.put_pixel(1, 1, c.into());
         buf.put_pixel(0, 1, d.into());
+        Image {
+            buf: Buffer::Eight(buf),
+        }
+    }
+
+    /// Keeps 16-bit and floating point channels, everything else becomes 8-bit
+    fn from_dynamic(img: DynamicImage) -> Self {
+        let buf = match img.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => Buffer::Float(img.into_rgba32f()),
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                Buffer::Sixteen(img.into_rgba16())
+            }
+            _ => Buffer::Eight(img.into_rgba8()),
+        };
         Image { buf }
     }
 
+    /// The image for encoding, floating point channels are encoded with 16-bit
+    fn to_dynamic(&self) -> DynamicImage {
+        match &self.buf {
+            Buffer::Eight(buf) => DynamicImage::ImageRgba8(buf.clone()),
+            Buffer::Sixteen(buf) => DynamicImage::ImageRgba16(buf.clone()),
+            Buffer::Float(buf) => DynamicImage::ImageRgba32F(buf.clone()).into_rgba16().into(),
+        }
+    }
+
+    fn write_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        format: ImageOutputFormat,
+    ) -> Result<(), ImageError> {
+        match &self.buf {
+            Buffer::Eight(buf) => buf.write_to(writer, format),
+            _ => self.to_dynamic().write_to(writer, format),
+        }
+    }
+
     /// Creates a new image with the content of the given image file.
     #[allow(dead_code)]
     pub fn from_file(path: &str) -> Result<Self, ImageError> {
         let img = image::open(path)?;
-        let buf = img.to_rgba8();
-        Ok(Image { buf })
+        Ok(Self::from_dynamic(img))
     }
 
+    /// Decodes the image file into sRGB, keeping the precision of 16-bit and floating point files.
     pub fn from_bytes(buffer: &[u8]) -> Result<Self, ImageError> {
-        let img = image::load_from_memory(buffer)?;
-        let buf = img.to_rgba8();
-        Ok(Image { buf })
+        Self::from_bytes_in(buffer, WorkingSpace::Srgb)
+    }
+
+    /// Decodes the image file and converts it from its embedded color profile into the working space.
+    /// Files without a profile are taken as sRGB.
+    pub fn from_bytes_in(buffer: &[u8], space: WorkingSpace) -> Result<Self, ImageError> {
+        let mut img = Self::from_dynamic(image::load_from_memory(buffer)?);
+        let source = embedded_profile(buffer).unwrap_or_else(|| WorkingSpace::Srgb.profile());
+        source.convert(&mut img, &space.profile());
+        Ok(img)
     }
 
-    pub fn into_array(&self) -> &Vec<u8> {
-        self.buf.as_raw()
+    /// Raw channel data in native byte order, the layout depends on the depth
+    pub fn into_array(&self) -> &[u8] {
+        each_buffer!(&self.buf, buf => buf.as_raw().as_bytes())
     }
 
+    /// Encodes the image as PNG tagged as sRGB, deeper images are encoded with 16-bit
     pub fn into_png_bytes(&self) -> Vec<u8> {
-        let mut buffer = BufWriter::new(Cursor::new(Vec::new()));
-        self.buf.write_to(&mut buffer, ImageFormat::Png).unwrap();
+        self.into_png_bytes_in(WorkingSpace::Srgb)
+    }
 
-        let bytes: Vec<u8> = buffer.into_inner().unwrap().into_inner();
+    /// Encodes the image as PNG with the profile of the working space embedded
+    pub fn into_png_bytes_in(&self, space: WorkingSpace) -> Vec<u8> {
+        let (width, height) = self.size().into();
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        let data = match &self.buf {
+            Buffer::Eight(buf) => buf.to_vec(),
+            _ => {
+                encoder.set_depth(png::BitDepth::Sixteen);
+                let deep = self.to_dynamic().into_rgba16();
+                deep.iter().flat_map(|c| c.to_be_bytes()).collect()
+            }
+        };
+        if space == WorkingSpace::Srgb {
+            encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        if space != WorkingSpace::Srgb {
+            // name of the profile, compression method and the compressed profile
+            let mut chunk = b"ICC profile\0\0".to_vec();
+            chunk.extend(compress_to_vec_zlib(&space.icc(), 6));
+            writer.write_chunk(png::chunk::iCCP, &chunk).unwrap();
+        }
+        writer.write_image_data(&data).unwrap();
+        writer.finish().unwrap();
         bytes
     }
 
+    /// Encodes the image as TIFF tagged as sRGB, deeper images are encoded with 16-bit
+    pub fn into_tiff_bytes(&self) -> Vec<u8> {
+        self.into_tiff_bytes_in(WorkingSpace::Srgb)
+    }
+
+    /// Encodes the image as TIFF with the profile of the working space embedded
+    pub fn into_tiff_bytes_in(&self, space: WorkingSpace) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        let (size, icc) = (self.size().into(), space.icc());
+        match &self.buf {
+            Buffer::Eight(buf) => write_tiff::<RGBA8, _>(&mut encoder, size, buf, &icc),
+            _ => {
+                let deep = self.to_dynamic().into_rgba16();
+                write_tiff::<RGBA16, _>(&mut encoder, size, &deep, &icc)
+            }
+        }
+        .unwrap();
+        buffer.into_inner()
+    }
+
     pub fn from_base64(base64: &str) -> Result<Self, Box<dyn Error>> {
         let buf = STANDARD.decode(base64)?;
         let img = image::load_from_memory(&buf)?;
-        let buf = img.to_rgba8();
-        Ok(Image { buf })
+        Ok(Self::from_dynamic(img))
     }
 
     #[allow(dead_code)]
     pub fn save(&self, path: &str) -> Result<(), ImageError> {
-        self.buf.save(path)
+        match &self.buf {
+            Buffer::Eight(buf) => buf.save(path),
+            _ => self.to_dynamic().save(path),
+        }
     }
 
     pub fn encode_base64(&self) -> Result<String, ImageError> {
         let mut buf: Vec<u8> = Vec::new();
-        self.buf
-            .write_to(&mut Cursor::new(&mut buf), image::ImageOutputFormat::Png)?;
+        self.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
         let encoded = STANDARD.encode(&buf);
         Ok(encoded)
     }
 
     pub fn height(&self) -> u32 {
-        self.buf.height()
+        each_buffer!(&self.buf, buf => buf.height())
     }
 
     pub fn width(&self) -> u32 {
-        self.buf.width()
+        each_buffer!(&self.buf, buf => buf.width())
     }
 
+    /// The pixels as 8-bit RGBA, whatever the depth of the image
     pub fn into_bytes(&self) -> Vec<u8> {
-        self.buf.to_vec()
+        match &self.buf {
+            Buffer::Eight(buf) => buf.to_vec(),
+            _ => self.to_depth(Depth::Eight).into_bytes(),
+        }
     }
 
+    /// The pixel rounded to 8-bit
     pub fn pixel(&self, x: u32, y: u32) -> Color {
-        self.buf.get_pixel(x, y).into()
+        match &self.buf {
+            Buffer::Eight(buf) => buf.get_pixel(x, y).into(),
+            _ => (&Rgba(self.get(x, y).map(to_eight))).into(),
+        }
+    }
+
+    /// Coordinates of all pixels, row by row
+    pub(crate) fn points(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (width, height) = self.size().into();
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    /// Whether the given position lies within the image (in image coordinates)
+    pub fn contains(&self, pos: &Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width() as i32 && pos.y < self.height() as i32
     }
 
     pub fn put_pixel(&mut self, x: u32, y: u32, g: Color) {
-        self.buf.put_pixel(x, y, g.into());
+        match &mut self.buf {
+            Buffer::Eight(buf) => buf.put_pixel(x, y, g.into()),
+            _ => self.set(x, y, [g.r, g.g, g.b, g.a].map(|c| c as f64 / 255.0)),
+        }
+    }
+
+    /// Channels of the pixel from 0 to 1 with straight alpha, whatever the depth of the image
+    pub fn get(&self, x: u32, y: u32) -> [f64; 4] {
+        match &self.buf {
+            Buffer::Eight(buf) => buf.get_pixel(x, y).0.map(|c| c as f64 / 255.0),
+            Buffer::Sixteen(buf) => buf.get_pixel(x, y).0.map(|c| c as f64 / 65535.0),
+            Buffer::Float(buf) => buf.get_pixel(x, y).0.map(|c| c as f64),
+        }
+    }
+
+    /// Sets the channels of the pixel from 0 to 1, rounded to the depth of the image
+    pub fn set(&mut self, x: u32, y: u32, pixel: [f64; 4]) {
+        match &mut self.buf {
+            Buffer::Eight(buf) => buf.put_pixel(x, y, Rgba(pixel.map(to_eight))),
+            Buffer::Sixteen(buf) => buf.put_pixel(
+                x,
+                y,
+                Rgba(pixel.map(|c| (c * 65535.0).round().clamp(0.0, 65535.0) as u16)),
+            ),
+            Buffer::Float(buf) => buf.put_pixel(x, y, Rgba(pixel.map(|c| c as f32))),
+        }
+    }
+
+    /// Draws the pixel with channels from 0 to 1 on top of the pixel at the position
+    pub(crate) fn draw_pixel(&mut self, x: u32, y: u32, pixel: [f64; 4]) {
+        let existing = self.get(x, y);
+        let result = SoftwareBlender::blend_texel(
+            self.depth(),
+            Compositing::Srgb,
+            BlendMode::Alpha,
+            existing,
+            pixel,
+            1.0,
+            1.0,
+        );
+        self.set(x, y, result);
     }
 
     pub fn clean(&mut self, area: &Rectangle) {
         for i in area.points() {
-            if i.x.is_positive()
-                && i.x < self.width() as i32
-                && i.y.is_positive()
-                && i.y < self.height() as i32
-            {
-                self.buf
-                    .put_pixel(i.x as u32, i.y as u32, Color::TRANSPARENT.into());
+            if self.contains(&i) {
+                self.put_pixel(i.x as u32, i.y as u32, Color::TRANSPARENT);
             }
         }
     }
 }
 
+fn write_tiff<C, W>(
+    encoder: &mut TiffEncoder<W>,
+    (width, height): (u32, u32),
+    data: &[C::Inner],
+    icc: &[u8],
+) -> TiffResult<()>
+where
+    C: colortype::ColorType,
+    [C::Inner]: TiffValue,
+    W: Write + Seek,
+{
+    let mut image = encoder.new_image::<C>(width, height)?;
+    image.encoder().write_tag(Tag::Unknown(ICC_TAG), icc)?;
+    image.write_data(data)
+}
+
+/// Channels of the color from 0 to 1, with the given alpha from 0 to 1 instead of its own
+pub(crate) fn with_alpha(color: &Color, alpha: f64) -> [f64; 4] {
+    let [r, g, b] = [color.r, color.g, color.b].map(|c| c as f64 / 255.0);
+    [r, g, b, alpha]
+}
+
+/// Rounds a channel from 0 to 1 to 8-bit
+pub(crate) fn to_eight(c: f64) -> u8 {
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
 impl Clone for Image {
     fn clone(&self) -> Self {
         if log::log_enabled!(log::Level::Debug) {
@@ -193,7 +459,7 @@ impl Clone for Image {
 
 impl Display for Image {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[image {}x{}]", self.buf.width(), self.buf.height())
+        write!(f, "[image {}x{}]", self.width(), self.height())
     }
 }
 
@@ -201,7 +467,7 @@ impl Display for Image {
 impl From<&Image> for ImageData {
     fn from(value: &Image) -> ImageData {
         ImageData::new_with_u8_clamped_array_and_sh(
-            wasm_bindgen::Clamped(value.buf.as_bytes()),
+            wasm_bindgen::Clamped(&value.into_bytes()),
             value.width(),
             value.height(),
         )
@@ -215,6 +481,46 @@ impl From<ImageData> for Image {
         let converted: Option<ImageBuffer<Rgba<u8>, Vec<u8>>> =
             RgbaImage::from_raw(value.width(), value.height(), value.data().to_vec());
         let buf = converted.expect("Oh no");
-        Image { buf }
+        Image {
+            buf: Buffer::Eight(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use super::{Depth, Image};
+
+    #[test]
+    fn encoding_keeps_precision() {
+        let mut img = Image::new_with_depth(2, 1, Depth::Sixteen);
+        img.set(0, 0, [1000.0 / 65535.0, 0.5, 1.0, 1.0]);
+        for bytes in [img.into_png_bytes(), img.into_tiff_bytes()] {
+            let decoded = Image::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.depth(), Depth::Sixteen);
+            assert_eq!(decoded, img);
+        }
+        let eight = img.to_depth(Depth::Eight);
+        assert_eq!(eight.pixel(0, 0), img.pixel(0, 0));
+        assert_ne!(eight.to_depth(Depth::Sixteen), img);
+        assert_eq!(Image::from_bytes(&eight.into_png_bytes()).unwrap(), eight);
+    }
+
+    #[test]
+    fn drawing_at_depth() {
+        let mut img = Image::new_with_depth(1, 1, Depth::Float);
+        img.put_pixel(0, 0, Color::BLACK);
+        let gray = Color {
+            r: 1,
+            g: 1,
+            b: 1,
+            a: 255,
+        };
+        img.draw_pixel(0, 0, super::with_alpha(&gray, 0.5));
+        let [r, _, _, a] = img.get(0, 0);
+        assert!((r - 0.5 / 255.0).abs() < 1e-6);
+        assert_eq!(a, 1.0);
     }
 }
@@ -1,11 +1,30 @@
 mod blend;
 mod dto;
+mod gradient;
 mod image;
+mod mask;
 mod processing;
+mod profile;
+mod raster;
+mod region;
 mod serde;
+mod text;
+mod transform;
 
 pub use self::blend::*;
 pub use self::dto::DtoTransformError;
+pub use self::dto::FontDto;
+pub use self::dto::FontSource;
 pub use self::dto::ImageDto;
 pub use self::dto::ImageSource;
-pub use self::image::Image;
+pub use self::gradient::{AlphaStop, ColorStop, Gradient, GradientRepeat, GradientShape};
+pub use self::image::{Depth, Image};
+pub use self::mask::Mask;
+pub use self::processing::{
+    Channels, ColorBalance, Convolution, Curves, EdgeMode, EdgeOperator, GrayscaleMethod, Level,
+    Levels, RadialBlurKind,
+};
+pub use self::profile::{ColorProfile, WorkingSpace};
+pub use self::region::ColorDistance;
+pub use self::text::{Font, TextAlign, TextStyle};
+pub use self::transform::{Projection, Resampling};
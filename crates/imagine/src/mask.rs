@@ -0,0 +1,382 @@
+use common::{Color, Position, Rectangle, Size};
+use image::{imageops, GrayImage, Luma};
+use imageproc::filter::gaussian_blur_f32;
+
+use crate::Image;
+
+/// Coverage of each pixel of an image, from 0 (not covered) to 255 (fully covered).
+#[derive(PartialEq, Debug, Clone)]
+pub struct Mask {
+    pub(crate) buf: GrayImage,
+}
+
+impl Mask {
+    /// Creates a mask of the given size that covers nothing
+    pub fn new(width: u32, height: u32) -> Self {
+        Mask {
+            buf: GrayImage::new(width, height),
+        }
+    }
+
+    /// Creates a mask of the given size that covers everything
+    pub fn full(width: u32, height: u32) -> Self {
+        Mask {
+            buf: GrayImage::from_pixel(width, height, Luma([255])),
+        }
+    }
+
+    /// Creates a mask with the coverage given by the function for every pixel
+    pub fn from_fn(width: u32, height: u32, f: impl Fn(u32, u32) -> u8) -> Self {
+        Mask {
+            buf: GrayImage::from_fn(width, height, |x, y| Luma([f(x, y)])),
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width(), self.height())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.buf.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.buf.height()
+    }
+
+    /// Coverage at the position, nothing is covered outside of the mask
+    pub fn get(&self, pos: &Position) -> u8 {
+        if self.contains(pos) {
+            self.buf.get_pixel(pos.x as u32, pos.y as u32)[0]
+        } else {
+            0
+        }
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: u8) {
+        self.buf.put_pixel(x, y, Luma([value]));
+    }
+
+    /// Opacity from 0 to 1 the mask lends the pixel at the position, pixels outside of the mask stay opaque
+    pub fn opacity(&self, pos: &Position) -> f64 {
+        if self.contains(pos) {
+            self.get(pos) as f64 / 255.0
+        } else {
+            1.0
+        }
+    }
+
+    /// The mask as an opaque grayscale image
+    pub fn to_image(&self) -> Image {
+        let mut result = Image::new(self.width(), self.height());
+        for (x, y, value) in self.buf.enumerate_pixels() {
+            let v = value[0];
+            result.put_pixel(
+                x,
+                y,
+                Color {
+                    r: v,
+                    g: v,
+                    b: v,
+                    a: 255,
+                },
+            );
+        }
+        result
+    }
+
+    /// Takes over the luminance of the image within the area, transparent pixels uncover.
+    /// Both must have the same size.
+    pub fn set_luminance(&mut self, img: &Image, area: &Rectangle) {
+        let area = Rectangle::intersect(area, &Rectangle::of((0, 0).into(), self.size()));
+        for pos in area.points() {
+            let (x, y) = (pos.x as u32, pos.y as u32);
+            let p = img.get(x, y);
+            let luminance = 0.299 * p[0] + 0.587 * p[1] + 0.114 * p[2];
+            self.set(x, y, (luminance * p[3] * 255.0).round() as u8);
+        }
+    }
+
+    pub fn flip_horizontally(&mut self) {
+        imageops::flip_horizontal_in_place(&mut self.buf);
+    }
+
+    pub fn flip_vertically(&mut self) {
+        imageops::flip_vertical_in_place(&mut self.buf);
+    }
+
+    /// Rotates the mask by a quarter turn clockwise, width and height swap
+    pub fn rotate90(&mut self) {
+        self.buf = imageops::rotate90(&self.buf);
+    }
+
+    pub fn rotate180(&mut self) {
+        imageops::rotate180_in_place(&mut self.buf);
+    }
+
+    /// Rotates the mask by a quarter turn counterclockwise, width and height swap
+    pub fn rotate270(&mut self) {
+        self.buf = imageops::rotate270(&self.buf);
+    }
+
+    /// The mask moved by the offset within a mask of the given size
+    pub fn translated(&self, offset: Position, size: Size) -> Self {
+        let mut result = Mask::new(size.width, size.height);
+        let area = Rectangle::intersect(
+            &(&self.bounds() + &offset),
+            &Rectangle::of((0, 0).into(), size),
+        );
+        for p in area.points() {
+            result.set(p.x as u32, p.y as u32, self.get(&(p - offset)));
+        }
+        result
+    }
+
+    /// Whether the given position lies within the mask
+    pub fn contains(&self, pos: &Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width() as i32 && pos.y < self.height() as i32
+    }
+
+    /// Smallest area containing all covered pixels, empty if nothing is covered
+    pub fn bounds(&self) -> Rectangle {
+        let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+        for (x, y, _) in self.buf.enumerate_pixels().filter(|(_, _, p)| p[0] > 0) {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1));
+        }
+        if x0 >= x1 {
+            return Rectangle::new(0, 0, 0, 0);
+        }
+        Rectangle::new(x0 as i32, y0 as i32, x1 - x0, y1 - y0)
+    }
+
+    /// The mask with soft edges: pixels next to covered ones get partially covered,
+    /// covered pixels stay covered.
+    pub fn antialiased(&self) -> Self {
+        let mut result = self.clone();
+        for (x, y, pixel) in result.buf.enumerate_pixels_mut() {
+            let center = Position::new(x as i32, y as i32);
+            let sum: u32 = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| Position::new(dx, dy)))
+                .map(|d| self.get(&(center + d)) as u32)
+                .sum();
+            pixel[0] = pixel[0].max((sum / 9) as u8);
+        }
+        result
+    }
+
+    /// Covers everything covered by either mask, both must have the same size
+    pub fn add(&mut self, other: &Mask) {
+        self.combine(other, |a, b| a.max(b));
+    }
+
+    /// Uncovers everything covered by the other mask, both must have the same size
+    pub fn subtract(&mut self, other: &Mask) {
+        self.combine(other, |a, b| a.min(255 - b));
+    }
+
+    /// Only keeps what is covered by both masks, both must have the same size
+    pub fn intersect(&mut self, other: &Mask) {
+        self.combine(other, |a, b| a.min(b));
+    }
+
+    fn combine(&mut self, other: &Mask, f: impl Fn(u8, u8) -> u8) {
+        for (a, b) in self.buf.pixels_mut().zip(other.buf.pixels()) {
+            a[0] = f(a[0], b[0]);
+        }
+    }
+
+    pub fn invert(&mut self) {
+        self.buf.pixels_mut().for_each(|p| p[0] = 255 - p[0]);
+    }
+
+    /// Softens the edges with a gaussian blur of the given radius
+    pub fn feather(&mut self, radius: f32) {
+        if radius > 0.0 {
+            self.buf = gaussian_blur_f32(&self.buf, radius / 2.0);
+        }
+    }
+
+    /// Extends the covered area by the given number of pixels
+    pub fn grow(&mut self, amount: u32) {
+        self.morph(amount, u8::max);
+    }
+
+    /// Reduces the covered area by the given number of pixels.
+    /// The border of the mask doesn't count as uncovered.
+    pub fn shrink(&mut self, amount: u32) {
+        self.morph(amount, u8::min);
+    }
+
+    /// Combines every pixel with all pixels within a circle of the given radius around it
+    fn morph(&mut self, radius: u32, f: fn(u8, u8) -> u8) {
+        // circles wider than the diagonal reach every pixel either way
+        let diagonal = (self.width() as f64).hypot(self.height() as f64).ceil() as i64;
+        let r = (radius as i64).min(diagonal);
+        let offsets: Vec<Position> = (-r..=r)
+            .flat_map(|dx| (-r..=r).map(move |dy| (dx, dy)))
+            .filter(|(dx, dy)| dx * dx + dy * dy <= r * r)
+            .map(|(dx, dy)| Position::new(dx as i32, dy as i32))
+            .collect();
+        let source = self.clone();
+        for (x, y, pixel) in self.buf.enumerate_pixels_mut() {
+            let center = Position::new(x as i32, y as i32);
+            pixel[0] = offsets
+                .iter()
+                .map(|d| center + *d)
+                .filter(|p| source.contains(p))
+                .map(|p| source.get(&p))
+                .fold(pixel[0], f);
+        }
+    }
+}
+
+impl Image {
+    /// Reverts the changes made to the image since `before` where the mask doesn't cover them,
+    /// partially covered pixels are mixed. Image position `p` corresponds to `p + offset` in the mask.
+    ///
+    /// Only the given area (in image coordinates) is considered.
+    pub fn keep_changes_within(
+        &mut self,
+        before: &Image,
+        mask: &Mask,
+        offset: Position,
+        area: &Rectangle,
+    ) {
+        let area = Rectangle::intersect(area, &Rectangle::of((0, 0).into(), self.size()));
+        for pos in area.points() {
+            let (x, y) = (pos.x as u32, pos.y as u32);
+            let coverage = mask.get(&(pos + offset));
+            if coverage == 255 {
+                continue;
+            }
+            let (old, new) = (before.get(x, y), self.get(x, y));
+            let pixel = mix(old, new, coverage as f64 / 255.0);
+            self.set(x, y, pixel);
+        }
+    }
+
+    /// Multiplies the alpha of every pixel with the opacity the mask lends it, both share the same origin
+    pub fn apply_mask(&mut self, mask: &Mask) {
+        for (x, y) in self.points() {
+            let mut pixel = self.get(x, y);
+            pixel[3] *= mask.opacity(&Position::new(x as i32, y as i32));
+            self.set(x, y, pixel);
+        }
+    }
+}
+
+/// Linear interpolation between two pixels with premultiplied alpha
+fn mix(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    let (alpha_a, alpha_b) = (a[3], b[3]);
+    let alpha = alpha_a + (alpha_b - alpha_a) * t;
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let channel = |i: usize| {
+        let (ca, cb) = (a[i] * alpha_a, b[i] * alpha_b);
+        ((ca + (cb - ca) * t) / alpha).clamp(0.0, 1.0)
+    };
+    [channel(0), channel(1), channel(2), alpha]
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position, Rectangle};
+
+    use super::Mask;
+    use crate::Image;
+
+    fn square() -> Mask {
+        let mut mask = Mask::new(9, 9);
+        for pos in Rectangle::new(3, 3, 3, 3).points() {
+            mask.set(pos.x as u32, pos.y as u32, 255);
+        }
+        mask
+    }
+
+    #[test]
+    fn grow_and_shrink() {
+        let mut mask = square();
+        mask.grow(1);
+        assert_eq!(mask.bounds(), Rectangle::new(2, 2, 5, 5));
+        assert_eq!(mask.get(&Position::new(2, 2)), 0);
+        mask.shrink(1);
+        assert_eq!(mask, square());
+        let mut full = Mask::full(4, 4);
+        full.shrink(2);
+        assert_eq!(full, Mask::full(4, 4));
+    }
+
+    #[test]
+    fn translated() {
+        let moved = square().translated(Position::new(-4, 2), (4, 9).into());
+        assert_eq!(moved.size(), (4, 9).into());
+        assert_eq!(moved.bounds(), Rectangle::new(0, 5, 2, 3));
+    }
+
+    #[test]
+    fn rotate() {
+        let mut mask = Mask::new(4, 2);
+        mask.set(0, 0, 255);
+        mask.rotate90();
+        assert_eq!(mask.size(), (2, 4).into());
+        assert_eq!(mask.bounds(), Rectangle::new(1, 0, 1, 1));
+        mask.rotate180();
+        assert_eq!(mask.bounds(), Rectangle::new(0, 3, 1, 1));
+        mask.rotate270();
+        mask.rotate270();
+        assert_eq!(mask.bounds(), Rectangle::new(1, 0, 1, 1));
+        mask.rotate270();
+        assert_eq!(mask, {
+            let mut expected = Mask::new(4, 2);
+            expected.set(0, 0, 255);
+            expected
+        });
+    }
+
+    #[test]
+    fn combine() {
+        let mut mask = square();
+        let mut other = Mask::new(9, 9);
+        other.set(0, 0, 255);
+        other.set(4, 4, 255);
+        mask.subtract(&other);
+        assert_eq!(mask.get(&Position::new(4, 4)), 0);
+        mask.add(&other);
+        assert_eq!(mask, {
+            let mut expected = square();
+            expected.set(0, 0, 255);
+            expected
+        });
+        mask.intersect(&other);
+        assert_eq!(mask.bounds(), Rectangle::new(0, 0, 5, 5));
+        mask.invert();
+        assert_eq!(mask.get(&Position::new(4, 4)), 0);
+        assert_eq!(mask.get(&Position::new(1, 1)), 255);
+    }
+
+    #[test]
+    fn keep_changes_within() {
+        let before = Image::new(9, 9);
+        let mut img = Image::new_from_color(9, 9, &Color::RED);
+        let mut mask = square();
+        mask.set(0, 0, 128);
+        img.keep_changes_within(
+            &before,
+            &mask,
+            Position::new(1, 0),
+            &Rectangle::new(0, 0, 9, 9),
+        );
+        assert_eq!(img.pixel(2, 3), Color::RED);
+        assert_eq!(img.pixel(5, 3), Color::TRANSPARENT);
+        assert_eq!(img.pixel(0, 0), Color::TRANSPARENT);
+        let mut img = Image::new_from_color(9, 9, &Color::RED);
+        img.keep_changes_within(
+            &before,
+            &mask,
+            Position::new(0, 0),
+            &Rectangle::new(0, 0, 9, 9),
+        );
+        assert_eq!(img.pixel(0, 0), Color::RED.with_alpha(128));
+    }
+}
@@ -0,0 +1,455 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{from_linear, to_linear, Image};
+
+/// How the colors of a pixel are turned into a single gray
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum GrayscaleMethod {
+    /// Perceived brightness, weighted as in Rec. 709
+    #[default]
+    #[serde(rename = "luminance")]
+    Luminance,
+
+    /// Mean of the three channels
+    #[serde(rename = "average")]
+    Average,
+
+    #[serde(rename = "red")]
+    Red,
+
+    #[serde(rename = "green")]
+    Green,
+
+    #[serde(rename = "blue")]
+    Blue,
+}
+
+/// Input range and midtone gamma of a channel, black and white are given on the 8-bit scale
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Level {
+    pub black: f64,
+    pub white: f64,
+    pub gamma: f64,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level {
+            black: 0.0,
+            white: 255.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Level {
+    fn apply(&self, c: f64) -> f64 {
+        let range = (self.white - self.black).max(f64::EPSILON);
+        ((c * 255.0 - self.black) / range)
+            .clamp(0.0, 1.0)
+            .powf(1.0 / self.gamma)
+    }
+}
+
+/// Levels of the single channels, applied before the levels of all channels
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct Levels {
+    #[serde(default)]
+    pub rgb: Level,
+    #[serde(default)]
+    pub red: Level,
+    #[serde(default)]
+    pub green: Level,
+    #[serde(default)]
+    pub blue: Level,
+}
+
+impl Levels {
+    /// Whether all gammas are positive
+    pub fn is_valid(&self) -> bool {
+        [self.rgb, self.red, self.green, self.blue]
+            .iter()
+            .all(|level| level.gamma > 0.0)
+    }
+}
+
+/// Tone curves through control points `[input, output]` on the 8-bit scale, channels without points are kept.
+/// The curves of the single channels are applied before the curve of all channels.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct Curves {
+    #[serde(default)]
+    pub rgb: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub red: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub green: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub blue: Vec<(f64, f64)>,
+}
+
+/// Shifts of the tonal ranges from -1 to 1 towards red, green and blue,
+/// negative values shift towards cyan, magenta and yellow instead
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct ColorBalance {
+    #[serde(default)]
+    pub shadows: [f64; 3],
+    #[serde(default)]
+    pub midtones: [f64; 3],
+    #[serde(default)]
+    pub highlights: [f64; 3],
+    #[serde(default)]
+    pub preserve_luminosity: bool,
+}
+
+/// Monotone cubic interpolation through the control points, flat before the first and after the last one
+struct Spline {
+    points: Vec<(f64, f64)>,
+    tangents: Vec<f64>,
+}
+
+impl Spline {
+    fn new(points: &[(f64, f64)]) -> Self {
+        let mut points: Vec<(f64, f64)> = points
+            .iter()
+            .map(|(x, y)| (x / 255.0, y / 255.0))
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.dedup_by(|a, b| a.0 == b.0);
+        let slopes: Vec<f64> = points
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect();
+        let mut tangents: Vec<f64> = (0..points.len())
+            .map(|i| match (i.checked_sub(1).map(|i| slopes[i]), slopes.get(i)) {
+                (Some(a), Some(b)) if a * b > 0.0 => (a + b) / 2.0,
+                (Some(_), Some(_)) | (None, None) => 0.0,
+                (Some(a), None) => a,
+                (None, Some(b)) => *b,
+            })
+            .collect();
+        // Fritsch-Carlson, limits the tangents so the curve doesn't overshoot between the points
+        for (i, slope) in slopes.iter().enumerate() {
+            if *slope == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (tangents[i] / slope, tangents[i + 1] / slope);
+            let length = (a * a + b * b).sqrt();
+            if length > 3.0 {
+                tangents[i] = 3.0 * a / length * slope;
+                tangents[i + 1] = 3.0 * b / length * slope;
+            }
+        }
+        Spline { points, tangents }
+    }
+
+    fn apply(&self, c: f64) -> f64 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return c;
+        };
+        if c <= first.0 {
+            return first.1;
+        }
+        if c >= last.0 {
+            return last.1;
+        }
+        let i = self.points.partition_point(|p| p.0 <= c) - 1;
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        let h = x1 - x0;
+        let t = (c - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * self.tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * self.tangents[i + 1]
+    }
+}
+
+impl Image {
+    /// Replaces the color of every pixel that is not fully transparent, alpha stays as it is
+    fn map_colors(&mut self, f: impl Fn([f64; 3]) -> [f64; 3]) {
+        for (x, y) in self.points() {
+            let [r, g, b, a] = self.get(x, y);
+            if a > 0.0 {
+                let [r, g, b] = f([r, g, b]);
+                self.set(x, y, [r, g, b, a]);
+            }
+        }
+    }
+
+    pub fn grayscale(&mut self, method: GrayscaleMethod) {
+        self.map_colors(|c| {
+            let gray = match method {
+                GrayscaleMethod::Luminance => luminance(c),
+                GrayscaleMethod::Average => (c[0] + c[1] + c[2]) / 3.0,
+                GrayscaleMethod::Red => c[0],
+                GrayscaleMethod::Green => c[1],
+                GrayscaleMethod::Blue => c[2],
+            };
+            [gray; 3]
+        });
+    }
+
+    /// Brightness and contrast from -1 to 1, full contrast turns every channel into black or white
+    pub fn brightness_contrast(&mut self, brightness: f64, contrast: f64) {
+        let slope = ((contrast.clamp(-1.0, 1.0) + 1.0) * PI / 4.0).tan();
+        self.map_colors(|c| {
+            c.map(|c| {
+                let c = if brightness < 0.0 {
+                    c * (1.0 + brightness)
+                } else {
+                    c + (1.0 - c) * brightness
+                };
+                (c - 0.5) * slope + 0.5
+            })
+        });
+    }
+
+    pub fn levels(&mut self, levels: &Levels) {
+        let channels = [levels.red, levels.green, levels.blue];
+        self.map_colors(|c| [0, 1, 2].map(|i| levels.rgb.apply(channels[i].apply(c[i]))));
+    }
+
+    pub fn curves(&mut self, curves: &Curves) {
+        let rgb = Spline::new(&curves.rgb);
+        let channels = [&curves.red, &curves.green, &curves.blue].map(|points| Spline::new(points));
+        self.map_colors(|c| [0, 1, 2].map(|i| rgb.apply(channels[i].apply(c[i]))));
+    }
+
+    /// Rotates the hue by degrees, saturation and lightness are changed from -1 to 1
+    pub fn hue_saturation(&mut self, hue: f64, saturation: f64, lightness: f64) {
+        self.map_colors(|c| {
+            let (h, s, l) = to_hsl(c);
+            let s = (s * (1.0 + saturation)).clamp(0.0, 1.0);
+            let c = from_hsl(((h + hue) % 360.0 + 360.0) % 360.0, s, l);
+            c.map(|c| {
+                if lightness < 0.0 {
+                    c * (1.0 + lightness)
+                } else {
+                    c + (1.0 - c) * lightness
+                }
+            })
+        });
+    }
+
+    pub fn color_balance(&mut self, balance: &ColorBalance) {
+        self.map_colors(|c| {
+            let (_, _, l) = to_hsl(c);
+            // weights of the tonal ranges, as in GIMP
+            let (a, b, scale) = (0.25, 0.333, 0.7);
+            let shadows = ((l - b) / -a + 0.5).clamp(0.0, 1.0) * scale;
+            let midtones = ((l - b) / a + 0.5).clamp(0.0, 1.0)
+                * ((l + b - 1.0) / -a + 0.5).clamp(0.0, 1.0)
+                * scale;
+            let highlights = ((l + b - 1.0) / a + 0.5).clamp(0.0, 1.0) * scale;
+            let balanced = [0, 1, 2].map(|i| {
+                let shift = shadows * balance.shadows[i]
+                    + midtones * balance.midtones[i]
+                    + highlights * balance.highlights[i];
+                (c[i] + shift).clamp(0.0, 1.0)
+            });
+            if balance.preserve_luminosity {
+                let (h, s, _) = to_hsl(balanced);
+                from_hsl(h, s, l)
+            } else {
+                balanced
+            }
+        });
+    }
+
+    pub fn invert(&mut self) {
+        self.map_colors(|c| c.map(|c| 1.0 - c));
+    }
+
+    /// Reduces every channel to the given number of evenly spaced values, at least 2
+    pub fn posterize(&mut self, levels: u32) {
+        let steps = levels.max(2) as f64 - 1.0;
+        self.map_colors(|c| c.map(|c| (c.clamp(0.0, 1.0) * steps).round() / steps));
+    }
+
+    /// Turns pixels white whose luminance reaches the level on the 8-bit scale, black otherwise
+    pub fn threshold(&mut self, level: f64) {
+        self.map_colors(|c| [(luminance(c) * 255.0 >= level) as u8 as f64; 3]);
+    }
+
+    /// Saturates muted colors more than colors that are saturated already, from -1 to 1
+    pub fn vibrance(&mut self, amount: f64) {
+        self.map_colors(|c| {
+            let max = c[0].max(c[1]).max(c[2]);
+            let min = c[0].min(c[1]).min(c[2]);
+            let factor = 1.0 + amount * (1.0 - (max - min).clamp(0.0, 1.0));
+            let gray = luminance(c);
+            c.map(|c| gray + (c - gray) * factor)
+        });
+    }
+
+    /// Scales the light by `2^exposure` and adds the offset in linear light, then applies the gamma
+    pub fn exposure(&mut self, exposure: f64, offset: f64, gamma: f64) {
+        let scale = 2f64.powf(exposure);
+        self.map_colors(|c| {
+            c.map(|c| {
+                let light = (to_linear(c) * scale + offset).max(0.0);
+                from_linear(light).powf(1.0 / gamma)
+            })
+        });
+    }
+}
+
+fn luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Hue in degrees, saturation and lightness
+fn to_hsl(c: [f64; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = c;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d <= 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs()).max(f64::EPSILON);
+    let h = if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h * 60.0, s, l)
+}
+
+fn from_hsl(h: f64, s: f64, l: f64) -> [f64; 3] {
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let [r, g, b] = match h as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    let m = l - chroma / 2.0;
+    [r + m, g + m, b + m]
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{Depth, Image};
+
+    use super::{from_hsl, to_hsl, ColorBalance, Curves, GrayscaleMethod, Level, Levels};
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    #[test]
+    fn grayscale_keeps_alpha() {
+        let mut img = Image::new_from_color(2, 2, &color(255, 0, 0, 128));
+        img.grayscale(GrayscaleMethod::Luminance);
+        assert_eq!(img.pixel(0, 0), color(54, 54, 54, 128));
+        let mut img = Image::new_from_color(2, 2, &color(30, 60, 90, 255));
+        img.grayscale(GrayscaleMethod::Average);
+        assert_eq!(img.pixel(1, 1), color(60, 60, 60, 255));
+        img = Image::new_from_color(1, 1, &color(30, 60, 90, 255));
+        img.grayscale(GrayscaleMethod::Blue);
+        assert_eq!(img.pixel(0, 0), color(90, 90, 90, 255));
+    }
+
+    #[test]
+    fn tone_adjustments() {
+        let gray = Image::new_from_color(1, 1, &color(64, 128, 192, 255));
+
+        let mut img = gray.clone();
+        img.levels(&Levels {
+            rgb: Level {
+                black: 64.0,
+                white: 192.0,
+                gamma: 1.0,
+            },
+            ..Default::default()
+        });
+        assert_eq!(img.pixel(0, 0), color(0, 128, 255, 255));
+
+        // a curve through the diagonal keeps the image
+        let mut img = gray.clone();
+        let diagonal = vec![(0.0, 0.0), (128.0, 128.0), (255.0, 255.0)];
+        img.curves(&Curves {
+            rgb: diagonal,
+            ..Default::default()
+        });
+        assert_eq!(img, gray);
+        img.curves(&Curves {
+            red: vec![(0.0, 255.0), (255.0, 0.0)],
+            ..Default::default()
+        });
+        assert_eq!(img.pixel(0, 0), color(191, 128, 192, 255));
+
+        let mut img = gray.clone();
+        img.brightness_contrast(0.0, 1.0);
+        assert_eq!(img.pixel(0, 0), color(0, 255, 255, 255));
+
+        let mut img = gray.clone();
+        img.exposure(1.0, 0.0, 1.0);
+        assert!(img.pixel(0, 0).r > 64);
+        let mut img = gray.clone();
+        img.posterize(2);
+        assert_eq!(img.pixel(0, 0), color(0, 255, 255, 255));
+        img.invert();
+        assert_eq!(img.pixel(0, 0), color(255, 0, 0, 255));
+        let mut img = gray.clone();
+        img.threshold(128.0);
+        assert_eq!(img.pixel(0, 0), color(0, 0, 0, 255));
+    }
+
+    #[test]
+    fn color_adjustments() {
+        let mut img = Image::new_from_color(1, 1, &Color::RED);
+        img.hue_saturation(120.0, 0.0, 0.0);
+        assert_eq!(img.pixel(0, 0), color(0, 255, 0, 255));
+        img.hue_saturation(0.0, -1.0, 0.0);
+        assert_eq!(img.pixel(0, 0), color(128, 128, 128, 255));
+
+        let mut muted = Image::new_from_color(1, 1, &color(140, 120, 120, 255));
+        muted.vibrance(1.0);
+        let mut saturated = Image::new_from_color(1, 1, &color(250, 20, 20, 255));
+        saturated.vibrance(1.0);
+        // muted colors gain more than saturated ones
+        let gain = muted.pixel(0, 0).r - 140;
+        let loss = 20 - saturated.pixel(0, 0).g;
+        assert!(gain > 10 && gain > 2 * loss);
+
+        let mut img = Image::new_from_color(1, 1, &color(128, 128, 128, 255));
+        img.color_balance(&ColorBalance {
+            midtones: [0.5, 0.0, -0.5],
+            ..Default::default()
+        });
+        let balanced = img.pixel(0, 0);
+        assert!(balanced.r > 128 && balanced.g == 128 && balanced.b < 128);
+
+        for c in [[0.2, 0.4, 0.6], [0.9, 0.1, 0.3], [0.5, 0.5, 0.5]] {
+            let (h, s, l) = to_hsl(c);
+            let back = from_hsl(h, s, l);
+            assert!(c.iter().zip(back).all(|(a, b)| (a - b).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn adjustments_at_depth() {
+        let mut img = Image::new_with_depth(1, 1, Depth::Float);
+        img.set(0, 0, [2.0, 0.5, 0.25, 0.5]);
+        img.exposure(-1.0, 0.0, 1.0);
+        let [r, _, _, a] = img.get(0, 0);
+        // floating point channels keep the light beyond white
+        assert!(r > 1.0 && r < 2.0);
+        assert_eq!(a, 0.5);
+    }
+}
@@ -0,0 +1,281 @@
+use image::{ImageBuffer, Rgba, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+
+use crate::Image;
+
+/// Longest side of a kernel
+const MAX_KERNEL_SIZE: usize = 63;
+
+/// Where a kernel takes the pixels from that lie outside of the image
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum EdgeMode {
+    /// Repeats the pixels at the border
+    #[default]
+    #[serde(rename = "clamp")]
+    Clamp,
+
+    /// Continues at the opposite side
+    #[serde(rename = "wrap")]
+    Wrap,
+
+    /// Fully transparent pixels
+    #[serde(rename = "transparent")]
+    Transparent,
+}
+
+impl EdgeMode {
+    fn locate(&self, x: i64, y: i64, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (w, h) = (width as i64, height as i64);
+        match self {
+            EdgeMode::Clamp => Some((x.clamp(0, w - 1) as u32, y.clamp(0, h - 1) as u32)),
+            EdgeMode::Wrap => Some((x.rem_euclid(w) as u32, y.rem_euclid(h) as u32)),
+            EdgeMode::Transparent if (0..w).contains(&x) && (0..h).contains(&y) => {
+                Some((x as u32, y as u32))
+            }
+            EdgeMode::Transparent => None,
+        }
+    }
+}
+
+/// Channels that take the result of a kernel, the others are kept
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Channels {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Channels {
+            red: true,
+            green: true,
+            blue: true,
+            alpha: true,
+        }
+    }
+}
+
+impl Channels {
+    fn selected(&self) -> [bool; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+}
+
+/// User defined kernel given as rows of weights, the center of the kernel lies on the pixel.
+/// Kernels of even size have their center right and below of the middle.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Convolution {
+    pub kernel: Vec<Vec<f64>>,
+    /// Divides the weighted sum, defaults to the sum of the weights or 1 if they add up to 0
+    #[serde(default)]
+    pub divisor: Option<f64>,
+    /// Added to the result on the 8-bit scale
+    #[serde(default)]
+    pub offset: f64,
+    #[serde(default)]
+    pub edge: EdgeMode,
+    #[serde(default)]
+    pub channels: Channels,
+}
+
+impl Convolution {
+    /// Explains what is wrong with the parameters if they can't be applied
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let width = self.kernel.first().map_or(0, Vec::len);
+        if width == 0 {
+            return Err("A kernel needs at least one weight");
+        }
+        if self.kernel.iter().any(|row| row.len() != width) {
+            return Err("All rows of a kernel need the same length");
+        }
+        if width > MAX_KERNEL_SIZE || self.kernel.len() > MAX_KERNEL_SIZE {
+            return Err("A kernel can't be larger than 63 x 63");
+        }
+        if !self.kernel.iter().flatten().all(|w| w.is_finite()) || !self.offset.is_finite() {
+            return Err("The weights and the offset of a kernel have to be finite numbers");
+        }
+        if matches!(self.divisor, Some(d) if d == 0.0 || !d.is_finite()) {
+            return Err("The divisor of a kernel can't be 0");
+        }
+        if !self.channels.selected().contains(&true) {
+            return Err("A kernel has to be applied to at least one channel");
+        }
+        Ok(())
+    }
+
+    fn divisor(&self) -> f64 {
+        let sum: f64 = self.kernel.iter().flatten().sum();
+        match self.divisor {
+            Some(divisor) => divisor,
+            None if sum.abs() > f64::EPSILON => sum,
+            None => 1.0,
+        }
+    }
+}
+
+impl Image {
+    /// Convolves the image with the kernel, invalid kernels leave the image as it is.
+    ///
+    /// Works on colors with premultiplied alpha so transparent pixels don't bleed in.
+    /// If the alpha isn't selected the colors are weighted by the alpha of the pixel they end up in.
+    pub fn convolve(&mut self, convolution: &Convolution) {
+        if convolution.validate().is_err() {
+            return;
+        }
+        let buf = self.premultiplied();
+        let (width, height) = buf.dimensions();
+        let (center_x, center_y) = (
+            convolution.kernel[0].len() as i64 / 2,
+            convolution.kernel.len() as i64 / 2,
+        );
+        let divisor = convolution.divisor();
+        let convolved: Rgba32FImage = ImageBuffer::from_fn(width, height, |x, y| {
+            let mut sum = [0.0; 4];
+            for (j, row) in convolution.kernel.iter().enumerate() {
+                for (i, weight) in row.iter().enumerate() {
+                    let sx = x as i64 + i as i64 - center_x;
+                    let sy = y as i64 + j as i64 - center_y;
+                    let Some((sx, sy)) = convolution.edge.locate(sx, sy, width, height) else {
+                        continue;
+                    };
+                    let pixel = buf.get_pixel(sx, sy).0;
+                    for (s, c) in sum.iter_mut().zip(pixel) {
+                        *s += weight * c as f64;
+                    }
+                }
+            }
+            Rgba(sum.map(|s| (s / divisor) as f32))
+        });
+
+        let selected = convolution.channels.selected();
+        let offset = convolution.offset / 255.0;
+        for (x, y) in self.points() {
+            let original = self.get(x, y);
+            if !selected[3] && original[3] == 0.0 {
+                continue;
+            }
+            let sum = convolved.get_pixel(x, y).0.map(f64::from);
+            let alpha = if selected[3] { sum[3] } else { original[3] };
+            let pixel = [0, 1, 2, 3].map(|i| match (selected[i], i) {
+                (false, _) => original[i],
+                (true, 3) => alpha + offset,
+                (true, _) if alpha > 0.0 => sum[i] / alpha + offset,
+                (true, _) => offset,
+            });
+            self.set(x, y, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::{Depth, Image};
+
+    use super::{Channels, Convolution, EdgeMode};
+
+    const WHITE: Color = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 255,
+    };
+
+    fn kernel(kernel: Vec<Vec<f64>>) -> Convolution {
+        Convolution {
+            kernel,
+            divisor: None,
+            offset: 0.0,
+            edge: EdgeMode::Clamp,
+            channels: Channels::default(),
+        }
+    }
+
+    fn row(depth: Depth) -> Image {
+        let mut img = Image::new_with_depth(3, 1, depth);
+        img.put_pixel(0, 0, Color::RED);
+        img.put_pixel(1, 0, Color::BLACK);
+        img.put_pixel(2, 0, WHITE);
+        img
+    }
+
+    #[test]
+    fn edge_modes() {
+        for depth in [Depth::Eight, Depth::Float] {
+            // moves every pixel to the right
+            let mut shift = kernel(vec![vec![1.0, 0.0, 0.0]]);
+            let mut img = row(depth);
+            img.convolve(&shift);
+            assert_eq!(img.pixel(0, 0), Color::RED);
+            assert_eq!(img.pixel(1, 0), Color::RED);
+            assert_eq!(img.pixel(2, 0), Color::BLACK);
+
+            shift.edge = EdgeMode::Wrap;
+            let mut img = row(depth);
+            img.convolve(&shift);
+            assert_eq!(img.pixel(0, 0), WHITE);
+
+            shift.edge = EdgeMode::Transparent;
+            let mut img = row(depth);
+            img.convolve(&shift);
+            assert_eq!(img.pixel(0, 0).a, 0);
+            assert_eq!(img.pixel(2, 0), Color::BLACK);
+        }
+
+        // transparent surroundings fade a mean out without darkening it
+        let mut mean = kernel(vec![vec![1.0; 3]; 3]);
+        mean.edge = EdgeMode::Transparent;
+        let mut img = Image::new_from_color(5, 5, &Color::RED);
+        img.convolve(&mean);
+        let (r, g, b, a) = (255, 0, 0, 113);
+        assert_eq!(img.pixel(0, 0), Color { r, g, b, a });
+        assert_eq!(img.pixel(2, 2), Color::RED);
+    }
+
+    #[test]
+    fn divisor_offset_and_channels() {
+        let mut invert = kernel(vec![vec![-1.0]]);
+        invert.divisor = Some(1.0);
+        invert.offset = 255.0;
+        invert.channels = Channels {
+            red: true,
+            green: false,
+            blue: false,
+            alpha: false,
+        };
+        let mut img = Image::new_from_color(2, 2, &Color::RED.with_alpha(128));
+        img.convolve(&invert);
+        assert_eq!(img.pixel(1, 1), Color::BLACK.with_alpha(128));
+
+        let mut half = kernel(vec![vec![1.0]]);
+        half.divisor = Some(2.0);
+        half.channels.alpha = false;
+        let mut img = Image::new_from_color(2, 2, &WHITE);
+        img.convolve(&half);
+        let (r, g, b, a) = (128, 128, 128, 255);
+        assert_eq!(img.pixel(0, 0), Color { r, g, b, a });
+    }
+
+    #[test]
+    fn malformed_kernels() {
+        assert!(kernel(vec![vec![0.0, 1.0], vec![1.0, 0.0]])
+            .validate()
+            .is_ok());
+        assert!(kernel(vec![]).validate().is_err());
+        assert!(kernel(vec![vec![]]).validate().is_err());
+        assert!(kernel(vec![vec![1.0, 2.0], vec![1.0]]).validate().is_err());
+        assert!(kernel(vec![vec![f64::NAN]]).validate().is_err());
+        assert!(kernel(vec![vec![1.0; 64]]).validate().is_err());
+        let mut zero = kernel(vec![vec![1.0]]);
+        zero.divisor = Some(0.0);
+        assert!(zero.validate().is_err());
+
+        let mut img = Image::new_from_color(2, 2, &Color::RED);
+        img.convolve(&zero);
+        assert_eq!(img.pixel(0, 0), Color::RED);
+    }
+}
@@ -0,0 +1,435 @@
+use common::Position;
+use image::{ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+use imageproc::{
+    filter::{filter3x3, median_filter, separable_filter_equal},
+    gradients::{HORIZONTAL_SOBEL, VERTICAL_SOBEL},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{image::Buffer, Image};
+
+/// Above this standard deviation gaussian blurs are approximated by box blurs,
+/// which take the same time whatever the radius
+const EXACT_GAUSSIAN_SIGMA: f64 = 3.0;
+
+/// Upper bound of the samples per pixel along a motion or radial blur
+const MAX_SAMPLES: usize = 128;
+
+/// How a radial blur moves the samples around its center
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum RadialBlurKind {
+    /// Along circles around the center, the amount is the angle in degrees
+    #[default]
+    #[serde(rename = "spin")]
+    Spin,
+
+    /// Along lines towards the center, the amount is the fraction of the distance from 0 to 1
+    #[serde(rename = "zoom")]
+    Zoom,
+}
+
+/// Operator that finds the edges of an image
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum EdgeOperator {
+    /// Magnitude of the gradient
+    #[default]
+    #[serde(rename = "sobel")]
+    Sobel,
+
+    /// Magnitude of the second derivative
+    #[serde(rename = "laplacian")]
+    Laplacian,
+}
+
+impl Image {
+    /// Channels with premultiplied alpha, filters that mix neighbouring pixels work on these
+    /// so the colors of transparent pixels don't bleed into visible ones
+    pub(crate) fn premultiplied(&self) -> Rgba32FImage {
+        ImageBuffer::from_fn(self.width(), self.height(), |x, y| {
+            let [r, g, b, a] = self.get(x, y);
+            Rgba([r * a, g * a, b * a, a].map(|c| c as f32))
+        })
+    }
+
+    pub(super) fn set_premultiplied(&mut self, buf: &Rgba32FImage) {
+        for (x, y, pixel) in buf.enumerate_pixels() {
+            let [r, g, b, a] = pixel.0.map(f64::from);
+            let pixel = if a > 0.0 {
+                [r / a, g / a, b / a, a]
+            } else {
+                [0.0; 4]
+            };
+            self.set(x, y, pixel);
+        }
+    }
+
+    /// Gaussian blur with the standard deviation in pixels
+    pub fn gaussian_blur(&mut self, sigma: f64) {
+        let buf = self.premultiplied();
+        let blurred = if sigma <= EXACT_GAUSSIAN_SIGMA {
+            separable_filter_equal(&buf, &gaussian_kernel(sigma))
+        } else {
+            // three box blurs come close to a gaussian
+            box_sizes(sigma, 3)
+                .into_iter()
+                .fold(buf, |buf, radius| box_blur(&buf, radius))
+        };
+        self.set_premultiplied(&blurred);
+    }
+
+    /// Mean over a square of `2 * radius + 1` pixels
+    pub fn box_blur(&mut self, radius: u32) {
+        let blurred = box_blur(&self.premultiplied(), radius);
+        self.set_premultiplied(&blurred);
+    }
+
+    /// Mean along a line of the given length in pixels, the angle is in degrees counterclockwise from the x-axis
+    pub fn motion_blur(&mut self, angle: f64, distance: f64) {
+        let buf = self.premultiplied();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let samples = (distance.ceil() as usize + 1).clamp(2, MAX_SAMPLES);
+        let blurred = ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+            mean((0..samples).map(|i| {
+                let t = (i as f64 / (samples - 1) as f64 - 0.5) * distance;
+                sample(&buf, x as f64 + t * cos, y as f64 - t * sin)
+            }))
+        });
+        self.set_premultiplied(&blurred);
+    }
+
+    /// Spin or zoom blur around the center, in image coordinates
+    pub fn radial_blur(&mut self, center: Position, kind: RadialBlurKind, amount: f64) {
+        let buf = self.premultiplied();
+        let (cx, cy) = (center.x as f64, center.y as f64);
+        let blurred = ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            let radius = dx.hypot(dy);
+            // enough samples to leave no gaps along the path of the farthest pixel
+            let length = match kind {
+                RadialBlurKind::Spin => radius * amount.to_radians().abs(),
+                RadialBlurKind::Zoom => radius * amount.abs(),
+            };
+            let samples = (length.ceil() as usize + 1).clamp(2, MAX_SAMPLES);
+            mean((0..samples).map(|i| {
+                let t = i as f64 / (samples - 1) as f64;
+                match kind {
+                    RadialBlurKind::Spin => {
+                        let (sin, cos) = ((t - 0.5) * amount).to_radians().sin_cos();
+                        sample(&buf, cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+                    }
+                    RadialBlurKind::Zoom => {
+                        let scale = 1.0 - t * amount;
+                        sample(&buf, cx + dx * scale, cy + dy * scale)
+                    }
+                }
+            }))
+        });
+        self.set_premultiplied(&blurred);
+    }
+
+    /// Adds the difference to a gaussian blur of the image, scaled by the amount.
+    /// Differences below the threshold on the 8-bit scale are left alone.
+    pub fn unsharp_mask(&mut self, sigma: f64, amount: f64, threshold: f64) {
+        let mut blurred = self.clone();
+        blurred.gaussian_blur(sigma);
+        for (x, y) in self.points() {
+            let mut pixel = self.get(x, y);
+            let smooth = blurred.get(x, y);
+            for (i, c) in pixel.iter_mut().take(3).enumerate() {
+                let difference = *c - smooth[i];
+                if difference.abs() * 255.0 >= threshold {
+                    *c += difference * amount;
+                }
+            }
+            self.set(x, y, pixel);
+        }
+    }
+
+    /// Median of every channel over a square of `2 * radius + 1` pixels, removes speckles but keeps edges
+    pub fn median(&mut self, radius: u32) {
+        let buf = self.premultiplied();
+        let filtered = match self.buf {
+            Buffer::Eight(_) => {
+                let eight: RgbaImage = ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+                    Rgba(buf.get_pixel(x, y).0.map(|c| (c * 255.0).round() as u8))
+                });
+                let filtered = median_filter(&eight, radius, radius);
+                ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+                    Rgba(filtered.get_pixel(x, y).0.map(|c| c as f32 / 255.0))
+                })
+            }
+            // the filter of imageproc only handles 8-bit channels
+            _ => median(&buf, radius),
+        };
+        self.set_premultiplied(&filtered);
+    }
+
+    /// Replaces the colors with the strength of the edges, alpha stays as it is
+    pub fn detect_edges(&mut self, operator: EdgeOperator) {
+        let buf = self.premultiplied();
+        let magnitude: Rgba32FImage = match operator {
+            EdgeOperator::Sobel => {
+                let kernel = |k: &[i32; 9]| k.map(|c| c as f32);
+                let gx: Rgba32FImage = filter3x3(&buf, &kernel(&HORIZONTAL_SOBEL));
+                let gy: Rgba32FImage = filter3x3(&buf, &kernel(&VERTICAL_SOBEL));
+                ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+                    let (gx, gy) = (gx.get_pixel(x, y).0, gy.get_pixel(x, y).0);
+                    // the largest response of the kernels is 4
+                    Rgba([0, 1, 2, 3].map(|i| gx[i].hypot(gy[i]) / 4.0))
+                })
+            }
+            EdgeOperator::Laplacian => {
+                let kernel = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+                let laplacian: Rgba32FImage = filter3x3(&buf, &kernel);
+                ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+                    Rgba(laplacian.get_pixel(x, y).0.map(|c| c.abs() / 4.0))
+                })
+            }
+        };
+        for (x, y) in self.points() {
+            let [r, g, b, _] = magnitude.get_pixel(x, y).0.map(f64::from);
+            let alpha = self.get(x, y)[3];
+            self.set(x, y, [r, g, b, alpha]);
+        }
+    }
+}
+
+/// Normalized kernel reaching three standard deviations, the one of imageproc stops
+/// at two and doesn't add up to one which darkens the image
+fn gaussian_kernel(sigma: f64) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil() as i32;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.into_iter().map(|k| (k / sum) as f32).collect()
+}
+
+/// Sizes of box blurs that add up to a gaussian blur, as radii
+fn box_sizes(sigma: f64, passes: u32) -> Vec<u32> {
+    let n = passes as f64;
+    let ideal = (12.0 * sigma * sigma / n + 1.0).sqrt();
+    // widths are odd, kept as floats so huge deviations can't overflow
+    let lower = ideal.floor();
+    let lower = if lower % 2.0 == 0.0 {
+        lower - 1.0
+    } else {
+        lower
+    };
+    let l = lower;
+    let smaller = ((12.0 * sigma * sigma - n * l * l - 4.0 * n * l - 3.0 * n) / (-4.0 * l - 4.0))
+        .round()
+        .max(0.0);
+    (0..passes)
+        .map(|i| {
+            if (i as f64) < smaller {
+                lower
+            } else {
+                lower + 2.0
+            }
+        })
+        .map(|width| (width / 2.0).floor().min(u32::MAX as f64) as u32)
+        .collect()
+}
+
+/// Mean over a square window with prefix sums, edges are extended
+fn box_blur(buf: &Rgba32FImage, radius: u32) -> Rgba32FImage {
+    box_pass(&box_pass(buf, radius, false), radius, true)
+}
+
+fn box_pass(buf: &Rgba32FImage, radius: u32, vertical: bool) -> Rgba32FImage {
+    let (width, height) = buf.dimensions();
+    let (length, lines) = if vertical {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let mut result = Rgba32FImage::new(width, height);
+    if length == 0 {
+        return result;
+    }
+    let (r, last) = (radius as i64, length as i64 - 1);
+    let mut prefix = vec![[0.0; 4]; length as usize + 1];
+    for line in 0..lines {
+        let at = |i: i64| {
+            let (x, y) = if vertical {
+                (line, i as u32)
+            } else {
+                (i as u32, line)
+            };
+            buf.get_pixel(x, y).0.map(f64::from)
+        };
+        for i in 0..=last {
+            let mut sum = prefix[i as usize];
+            add(&mut sum, at(i), 1.0);
+            prefix[i as usize + 1] = sum;
+        }
+        let (first, end) = (at(0), at(last));
+        for i in 0..=last {
+            // the parts of the window beyond the edges repeat the edge pixels
+            let (from, to) = (i - r, i + r);
+            let mut sum = prefix[to.min(last) as usize + 1];
+            add(&mut sum, prefix[from.max(0) as usize], -1.0);
+            add(&mut sum, first, (-from).max(0) as f64);
+            add(&mut sum, end, (to - last).max(0) as f64);
+            let mean = sum.map(|c| (c / (2 * r + 1) as f64) as f32);
+            let (x, y) = if vertical {
+                (line, i as u32)
+            } else {
+                (i as u32, line)
+            };
+            result.put_pixel(x, y, Rgba(mean));
+        }
+    }
+    result
+}
+
+fn median(buf: &Rgba32FImage, radius: u32) -> Rgba32FImage {
+    let (width, height) = buf.dimensions();
+    let r = radius as i64;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let window: Vec<[f32; 4]> = (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| {
+                let x = (x as i64 + dx).clamp(0, width as i64 - 1) as u32;
+                let y = (y as i64 + dy).clamp(0, height as i64 - 1) as u32;
+                buf.get_pixel(x, y).0
+            })
+            .collect();
+        Rgba([0, 1, 2, 3].map(|i| {
+            let mut channel: Vec<f32> = window.iter().map(|p| p[i]).collect();
+            channel.sort_by(f32::total_cmp);
+            channel[channel.len() / 2]
+        }))
+    })
+}
+
+fn add(sum: &mut [f64; 4], pixel: [f64; 4], sign: f64) {
+    for (s, c) in sum.iter_mut().zip(pixel) {
+        *s += sign * c;
+    }
+}
+
+fn mean(samples: impl Iterator<Item = [f64; 4]>) -> Rgba<f32> {
+    let mut sum = [0.0; 4];
+    let mut count = 0.0;
+    for pixel in samples {
+        add(&mut sum, pixel, 1.0);
+        count += 1.0;
+    }
+    Rgba(sum.map(|c| (c / count) as f32))
+}
+
+/// Bilinear sample at the position, edges are extended
+fn sample(buf: &Rgba32FImage, x: f64, y: f64) -> [f64; 4] {
+    let (width, height) = buf.dimensions();
+    let x = x.clamp(0.0, (width - 1) as f64);
+    let y = y.clamp(0.0, (height - 1) as f64);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let at = |x, y| buf.get_pixel(x, y).0.map(f64::from);
+    let (a, b, c, d) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+    [0, 1, 2, 3].map(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+
+    use crate::{Depth, Image};
+
+    use super::{box_sizes, EdgeOperator, RadialBlurKind};
+
+    /// Opaque white square in the middle of a transparent image
+    fn square(depth: Depth) -> Image {
+        let mut img = Image::new_with_depth(21, 21, depth);
+        for y in 5..16 {
+            for x in 5..16 {
+                img.set(x, y, [1.0; 4]);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn blurs_keep_the_color_at_transparent_edges() {
+        for depth in [Depth::Eight, Depth::Sixteen, Depth::Float] {
+            for blur in [
+                |img: &mut Image| img.gaussian_blur(1.5),
+                |img: &mut Image| img.gaussian_blur(6.0),
+                |img: &mut Image| img.box_blur(3),
+                |img: &mut Image| img.motion_blur(30.0, 8.0),
+                |img: &mut Image| img.radial_blur(Position::new(0, 0), RadialBlurKind::Spin, 20.0),
+                |img: &mut Image| img.radial_blur(Position::new(10, 10), RadialBlurKind::Zoom, 0.5),
+            ] {
+                let mut img = square(depth);
+                blur(&mut img);
+                // the transparent surroundings don't darken the edge
+                let [r, g, b, a] = img.get(4, 10);
+                assert!(a > 0.0 && a < 1.0);
+                assert!([r, g, b].iter().all(|c| (c - 1.0).abs() < 1e-2));
+            }
+        }
+    }
+
+    #[test]
+    fn box_blur_averages() {
+        let mut img = Image::new(5, 1);
+        img.put_pixel(2, 0, Color::RED);
+        img.box_blur(1);
+        let (r, g, b, a) = (255, 0, 0, 85);
+        for x in 1..4 {
+            assert_eq!(img.pixel(x, 0), Color { r, g, b, a });
+        }
+        assert_eq!(img.pixel(0, 0).a, 0);
+        // flat areas stay as they are
+        let mut img = Image::new_from_color(10, 10, &Color::RED);
+        img.gaussian_blur(2.0);
+        assert_eq!(img.pixel(0, 5), Color::RED);
+        // radii far beyond the image only see the edges
+        img.box_blur(u32::MAX);
+        img.gaussian_blur(1e12);
+        assert_eq!(img.pixel(9, 9), Color::RED);
+        // three passes add up to the variance of the gaussian
+        let sigma: f64 = 10.0;
+        let variance: f64 = box_sizes(sigma, 3)
+            .iter()
+            .map(|r| ((2 * r + 1).pow(2) - 1) as f64 / 12.0)
+            .sum();
+        assert!((variance.sqrt() - sigma).abs() < 0.5);
+    }
+
+    #[test]
+    fn sharpen_median_and_edges() {
+        for depth in [Depth::Eight, Depth::Sixteen] {
+            let mut img = square(depth);
+            img.set(10, 10, [0.0, 0.0, 0.0, 1.0]);
+            img.median(1);
+            assert_eq!(img.get(10, 10), [1.0; 4]);
+            assert_eq!(img.get(0, 0)[3], 0.0);
+        }
+
+        let mut img = Image::new_from_color(9, 1, &Color::BLACK);
+        for x in 5..9 {
+            img.set(x, 0, [0.5, 0.5, 0.5, 1.0]);
+        }
+        img.unsharp_mask(1.0, 1.0, 0.0);
+        // the step becomes steeper on both sides
+        assert!(img.get(4, 0)[0] < 0.01);
+        assert!(img.get(5, 0)[0] > 0.55);
+        assert_eq!(img.get(0, 0), [0.0, 0.0, 0.0, 1.0]);
+
+        for operator in [EdgeOperator::Sobel, EdgeOperator::Laplacian] {
+            let mut img = square(Depth::Eight);
+            img.detect_edges(operator);
+            assert!(img.get(5, 10)[0] > 0.2);
+            assert_eq!(img.get(10, 10), [0.0, 0.0, 0.0, 1.0]);
+            assert_eq!(img.get(0, 0)[3], 0.0);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use common::{Color, Position, Rectangle, Size};
+use image::{imageops, ImageBuffer, Rgba};
+
+use crate::{
+    image::{each_buffer, Buffer},
+    Image,
+};
+
+mod color;
+mod convolution;
+mod filter;
+
+pub use self::color::{ColorBalance, Curves, GrayscaleMethod, Level, Levels};
+pub use self::convolution::{Channels, Convolution, EdgeMode};
+pub use self::filter::{EdgeOperator, RadialBlurKind};
+
+impl Image {
+    /// Adds noise to every channel, mean and standard deviation are given on the 8-bit scale
+    pub fn gaussian_noise(&mut self, mean: f64, stddev: f64, seed: u64) {
+        use imageproc::noise::gaussian_noise_mut;
+        match &mut self.buf {
+            Buffer::Eight(buf) => gaussian_noise_mut(buf, mean, stddev, seed),
+            Buffer::Sixteen(buf) => gaussian_noise_mut(buf, mean * 257.0, stddev * 257.0, seed),
+            Buffer::Float(buf) => {
+                // imageproc only adds noise to f64 channels of floating point images
+                let mut wide: ImageBuffer<Rgba<f64>, Vec<f64>> =
+                    ImageBuffer::from_fn(buf.width(), buf.height(), |x, y| {
+                        Rgba(buf.get_pixel(x, y).0.map(f64::from))
+                    });
+                gaussian_noise_mut(&mut wide, mean / 255.0, stddev / 255.0, seed);
+                for (pixel, noisy) in buf.pixels_mut().zip(wide.pixels()) {
+                    *pixel = Rgba(noisy.0.map(|c| c as f32));
+                }
+            }
+        }
+    }
+
+    pub fn flip_horizontally(&mut self) {
+        each_buffer!(&mut self.buf, buf => imageops::flip_horizontal_in_place(buf));
+    }
+
+    pub fn flip_vertically(&mut self) {
+        each_buffer!(&mut self.buf, buf => imageops::flip_vertical_in_place(buf));
+    }
+
+    /// Rotates the image by a quarter turn clockwise, width and height swap
+    pub fn rotate90(&mut self) {
+        self.buf = match &self.buf {
+            Buffer::Eight(buf) => Buffer::Eight(imageops::rotate90(buf)),
+            Buffer::Sixteen(buf) => Buffer::Sixteen(imageops::rotate90(buf)),
+            Buffer::Float(buf) => Buffer::Float(imageops::rotate90(buf)),
+        };
+    }
+
+    pub fn rotate180(&mut self) {
+        each_buffer!(&mut self.buf, buf => imageops::rotate180_in_place(buf));
+    }
+
+    /// Rotates the image by a quarter turn counterclockwise, width and height swap
+    pub fn rotate270(&mut self) {
+        self.buf = match &self.buf {
+            Buffer::Eight(buf) => Buffer::Eight(imageops::rotate270(buf)),
+            Buffer::Sixteen(buf) => Buffer::Sixteen(imageops::rotate270(buf)),
+            Buffer::Float(buf) => Buffer::Float(imageops::rotate270(buf)),
+        };
+    }
+
+    /// Draws the stamp image at each position of the track on the image struct.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn draw_line(&mut self, stamp: &Image, track: &[Position]) -> Rectangle {
+        log::debug!("Starting to draw");
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        let (stamp_w, stamp_h) = (stamp.width() as i32, stamp.height() as i32);
+
+        track
+            .iter()
+            .map(|a| (a.x, a.y))
+            // expand x coordinates
+            .map(|(x0, y0)| (x0, y0, (x0 - stamp_w)..(x0 + stamp_w)))
+            .flat_map(|(x0, y0, xr)| xr.map(move |q| (x0, y0, q)))
+            .filter(|(_x0, _y0, x)| *x >= 0 && *x < width)
+            .filter(|(_x0, y0, _x)| y0 - stamp_h < height && y0 + stamp_h > 0)
+            // expand y coordinates
+            .map(|(x0, y0, x)| (x0, y0, x, (y0 - stamp_h)..(y0 + stamp_h)))
+            .flat_map(|(x0, y0, x, yr)| yr.map(move |q| (x0, y0, x, q)))
+            .filter(|(_x0, _y0, _x, y)| *y >= 0 && *y < height)
+            // map into stamp coordinates
+            .map(|(x0, y0, x, y)| (x - (x0 - stamp_w / 2), y - (y0 - stamp_h / 2), x, y))
+            .filter(|(x_stamp, y_stamp, _x, _y)| {
+                *x_stamp >= 0 && *x_stamp < stamp_w && *y_stamp >= 0 && *y_stamp < stamp_h
+            })
+            .for_each(|(x_stamp, y_stamp, x, y)| {
+                let pixel = stamp.get(x_stamp as u32, y_stamp as u32);
+                self.draw_pixel(x as u32, y as u32, pixel);
+            });
+
+        stamp_damage(stamp, track)
+    }
+
+    /// Sets a hard `size` x `size` square of exactly the given color at each position of the track,
+    /// without any anti-aliasing or blending.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn draw_pixels(&mut self, color: &Color, size: u32, track: &[Position]) -> Rectangle {
+        let half = Position::new(size as i32 / 2, size as i32 / 2);
+        let squares: Vec<Rectangle> = track
+            .iter()
+            .map(|&pos| Rectangle::of(pos - half, Size::new(size, size)))
+            .collect();
+        for square in &squares {
+            for p in square.points() {
+                if self.contains(&p) {
+                    self.put_pixel(p.x as u32, p.y as u32, *color);
+                }
+            }
+        }
+        Rectangle::bounding_all(squares)
+    }
+
+    /// Draws the pixels of `source` through the stamp at each position of the track on the image struct.
+    /// The pixel for the image coordinate `p` is copied from `p + offset` in source coordinates,
+    /// only the alpha of the stamp is used.
+    ///
+    /// If `heal` is given, the copied pixels are adjusted to the color and lighting that `heal`
+    /// has underneath the stamp while keeping the texture of the source.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn draw_clone(
+        &mut self,
+        stamp: &Image,
+        source: &Image,
+        offset: Position,
+        track: &[Position],
+        heal: Option<&Image>,
+    ) -> Rectangle {
+        log::debug!("Starting to clone");
+        let half = Position::new(stamp.width() as i32 / 2, stamp.height() as i32 / 2);
+        for &center in track {
+            let origin = center - half;
+            let transfer = heal.and_then(|base| {
+                ColorTransfer::new(stamp, (source, origin + offset), (base, origin))
+            });
+            for (x_stamp, y_stamp) in stamp.points() {
+                let weight = stamp.get(x_stamp, y_stamp)[3];
+                if weight == 0.0 {
+                    continue;
+                }
+                let p = origin + Position::new(x_stamp as i32, y_stamp as i32);
+                let s = p + offset;
+                if !self.contains(&p) || !source.contains(&s) {
+                    continue;
+                }
+                let mut pixel = source.get(s.x as u32, s.y as u32);
+                if let Some(transfer) = &transfer {
+                    transfer.apply(&mut pixel);
+                }
+                pixel[3] *= weight;
+                self.draw_pixel(p.x as u32, p.y as u32, pixel);
+            }
+        }
+        stamp_damage(stamp, track)
+    }
+}
+
+/// The area covered by the stamp when placed at each position of the track.
+fn stamp_damage(stamp: &Image, track: &[Position]) -> Rectangle {
+    let (stamp_w, stamp_h) = (stamp.width() as i32, stamp.height() as i32);
+    let half = Position::new(stamp_w / 2, stamp_h / 2);
+    track
+        .iter()
+        .map(|&pos| (pos - half, Size::new(stamp_w as u32, stamp_h as u32)))
+        .map(|(pos, size)| Rectangle::of(pos, size))
+        .reduce(|rhs, lhs| Rectangle::bounding(&rhs, &lhs))
+        .unwrap_or(Rectangle::new(0, 0, 0, 0))
+}
+
+/// Per channel statistics transfer (mean and standard deviation) from a source patch onto a destination patch.
+/// Used by the healing brush to keep the texture of the source but take over the color and lighting of the destination.
+struct ColorTransfer {
+    source: [(f64, f64); 3],
+    destination: [(f64, f64); 3],
+}
+
+impl ColorTransfer {
+    /// Returns `None` if one of the patches is completely transparent.
+    fn new(
+        stamp: &Image,
+        source: (&Image, Position),
+        destination: (&Image, Position),
+    ) -> Option<Self> {
+        Some(ColorTransfer {
+            source: Self::statistics(stamp, source)?,
+            destination: Self::statistics(stamp, destination)?,
+        })
+    }
+
+    /// Mean and standard deviation of each color channel underneath the stamp, weighted by the stamp alpha.
+    fn statistics(stamp: &Image, (img, origin): (&Image, Position)) -> Option<[(f64, f64); 3]> {
+        let mut weight_sum = 0.0;
+        let mut sum = [0.0; 3];
+        let mut squared_sum = [0.0; 3];
+        for (x, y) in stamp.points() {
+            let p = origin + Position::new(x as i32, y as i32);
+            if !img.contains(&p) {
+                continue;
+            }
+            let pixel = img.get(p.x as u32, p.y as u32).map(|c| c * 255.0);
+            let weight = stamp.get(x, y)[3] * (pixel[3] / 255.);
+            weight_sum += weight;
+            for i in 0..3 {
+                sum[i] += weight * pixel[i];
+                squared_sum[i] += weight * pixel[i].powi(2);
+            }
+        }
+        if weight_sum <= 0.0 {
+            return None;
+        }
+        let mut result = [(0.0, 0.0); 3];
+        for i in 0..3 {
+            let mean = sum[i] / weight_sum;
+            let variance = (squared_sum[i] / weight_sum - mean * mean).max(0.0);
+            result[i] = (mean, variance.sqrt());
+        }
+        Some(result)
+    }
+
+    /// Adjusts the channels from 0 to 1, statistics are on the 8-bit scale
+    fn apply(&self, pixel: &mut [f64; 4]) {
+        for (i, channel) in pixel.iter_mut().take(3).enumerate() {
+            let (source_mean, source_deviation) = self.source[i];
+            let (destination_mean, destination_deviation) = self.destination[i];
+            // flat patches carry no contrast information, keep the texture as it is then
+            let factor = if source_deviation < 1.0 || destination_deviation < 1.0 {
+                1.0
+            } else {
+                (destination_deviation / source_deviation).clamp(0.25, 4.0)
+            };
+            let value = (*channel * 255.0 - source_mean) * factor + destination_mean;
+            *channel = value.clamp(0.0, 255.0) / 255.0;
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use std::io::Cursor;
+
+use image::ImageFormat;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+use serde::{Deserialize, Serialize};
+use tiff::tags::Tag;
+
+use crate::Image;
+
+/// TIFF tag that holds an embedded ICC profile
+pub(crate) const ICC_TAG: u16 = 34675;
+
+/// White point of the profile connection space
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const D65: (f64, f64) = (0.3127, 0.3290);
+
+type Matrix = [[f64; 3]; 3];
+
+/// Color space of a document, imported images are converted into it and exports are tagged with it
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+pub enum WorkingSpace {
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+
+    /// Wide gamut of recent displays with the transfer curve of sRGB
+    #[serde(rename = "display_p3")]
+    DisplayP3,
+
+    /// Wide gamut for print with a plain 2.2 gamma
+    #[serde(rename = "adobe_rgb")]
+    AdobeRgb,
+
+    /// Gamut of ITU-R BT.2020
+    #[serde(rename = "rec2020")]
+    Rec2020,
+}
+
+impl WorkingSpace {
+    pub fn profile(&self) -> ColorProfile {
+        let srgb = Curve::Parametric([
+            2.4,
+            1.0 / 1.055,
+            0.055 / 1.055,
+            1.0 / 12.92,
+            0.04045,
+            0.0,
+            0.0,
+        ]);
+        match self {
+            WorkingSpace::Srgb => {
+                ColorProfile::from_primaries(D65, [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)], srgb)
+            }
+            WorkingSpace::DisplayP3 => {
+                ColorProfile::from_primaries(D65, [(0.68, 0.32), (0.265, 0.69), (0.15, 0.06)], srgb)
+            }
+            WorkingSpace::AdobeRgb => ColorProfile::from_primaries(
+                D65,
+                [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)],
+                Curve::gamma(563.0 / 256.0),
+            ),
+            WorkingSpace::Rec2020 => ColorProfile::from_primaries(
+                D65,
+                [(0.708, 0.292), (0.17, 0.797), (0.131, 0.046)],
+                Curve::Parametric([
+                    1.0 / 0.45,
+                    1.0 / 1.0993,
+                    0.0993 / 1.0993,
+                    1.0 / 4.5,
+                    0.08145,
+                    0.0,
+                    0.0,
+                ]),
+            ),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            WorkingSpace::Srgb => "sRGB",
+            WorkingSpace::DisplayP3 => "Display P3",
+            WorkingSpace::AdobeRgb => "Adobe RGB (1998) compatible",
+            WorkingSpace::Rec2020 => "Rec. 2020",
+        }
+    }
+
+    /// ICC profile to embed into exported files
+    pub fn icc(&self) -> Vec<u8> {
+        self.profile().to_icc(self.description())
+    }
+}
+
+/// Transfer curve of a channel, maps encoded values to linear light
+#[derive(Clone, Debug, PartialEq)]
+enum Curve {
+    /// `(a x + b)^g + e` from `d` on and `c x + f` below, with the parameters in the order `g a b c d e f`
+    Parametric([f64; 7]),
+
+    /// Samples over the unit interval that are interpolated linearly
+    Table(Vec<f64>),
+}
+
+impl Curve {
+    fn gamma(g: f64) -> Self {
+        Curve::Parametric([g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn decode(&self, x: f64) -> f64 {
+        match self {
+            Curve::Parametric([g, a, b, c, d, e, f]) => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+            Curve::Table(table) => {
+                let pos = x.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let i = (pos as usize).min(table.len() - 2);
+                table[i] + (table[i + 1] - table[i]) * (pos - i as f64)
+            }
+        }
+    }
+
+    fn encode(&self, y: f64) -> f64 {
+        match self {
+            Curve::Parametric([g, a, b, c, d, e, f]) => {
+                if *c > 0.0 && y < c * d + f {
+                    (y - f) / c
+                } else {
+                    ((y - e).max(0.0).powf(1.0 / g) - b) / a
+                }
+            }
+            Curve::Table(table) => {
+                let i = table.partition_point(|v| *v < y).clamp(1, table.len() - 1);
+                let (low, high) = (table[i - 1], table[i]);
+                let t = if high > low {
+                    ((y - low) / (high - low)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (i - 1) as f64 / (table.len() - 1) as f64 + t / (table.len() - 1) as f64
+            }
+        }
+    }
+
+    fn parse(tag: &[u8]) -> Option<Self> {
+        match tag.get(0..4)? {
+            b"curv" => {
+                let count = read_u32(tag, 8)? as usize;
+                match count {
+                    0 => Some(Curve::gamma(1.0)),
+                    1 => Some(Curve::gamma(read_u16(tag, 12)? as f64 / 256.0)),
+                    _ => {
+                        let table: Option<Vec<f64>> = (0..count)
+                            .map(|i| Some(read_u16(tag, 12 + 2 * i)? as f64 / 65535.0))
+                            .collect();
+                        Some(Curve::Table(table?))
+                    }
+                }
+            }
+            b"para" => {
+                let p = |i: usize| read_s15_16(tag, 12 + 4 * i);
+                let (g, a, b) = (p(0)?, 1.0, 0.0);
+                let curve = match read_u16(tag, 8)? {
+                    0 => [g, a, b, 0.0, 0.0, 0.0, 0.0],
+                    1 => [g, p(1)?, p(2)?, 0.0, -p(2)? / p(1)?, 0.0, 0.0],
+                    2 => [g, p(1)?, p(2)?, 0.0, -p(2)? / p(1)?, p(3)?, p(3)?],
+                    3 => [g, p(1)?, p(2)?, p(3)?, p(4)?, 0.0, 0.0],
+                    4 => [g, p(1)?, p(2)?, p(3)?, p(4)?, p(5)?, p(6)?],
+                    _ => return None,
+                };
+                Some(Curve::Parametric(curve))
+            }
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Curve::Parametric(params) => {
+                let [_, a, b, c, d, e, f] = *params;
+                let (kind, count) = if e != 0.0 || f != 0.0 {
+                    (4, 7)
+                } else if a != 1.0 || b != 0.0 || c != 0.0 || d != 0.0 {
+                    (3, 5)
+                } else {
+                    (0, 1)
+                };
+                out.extend_from_slice(b"para\0\0\0\0");
+                out.extend_from_slice(&[0, kind, 0, 0]);
+                for p in &params[..count] {
+                    out.extend_from_slice(&s15_16(*p));
+                }
+            }
+            Curve::Table(table) => {
+                out.extend_from_slice(b"curv\0\0\0\0");
+                out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+                for v in table {
+                    let v = (v * 65535.0).round().clamp(0.0, 65535.0) as u16;
+                    out.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// RGB color space given by the colors of its primaries and a transfer curve per channel,
+/// which covers the matrix/TRC kind of ICC profiles that cameras, displays and working spaces use
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorProfile {
+    /// Linear channels to the D50 XYZ of the profile connection space
+    to_xyz: Matrix,
+    curves: [Curve; 3],
+    /// White of the source that has been adapted to D50
+    white: [f64; 3],
+}
+
+impl ColorProfile {
+    /// Profile from the chromaticities of the white point and the red, green and blue primaries
+    fn from_primaries(white: (f64, f64), primaries: [(f64, f64); 3], curve: Curve) -> Self {
+        let xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+        let [r, g, b] = primaries.map(xyz);
+        let white = xyz(white);
+        let columns = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        let scale = apply(&invert(&columns), white);
+        let to_xyz = columns.map(|row| [0, 1, 2].map(|i| row[i] * scale[i]));
+        ColorProfile {
+            to_xyz: multiply(&adaptation(white), &to_xyz),
+            curves: [curve.clone(), curve.clone(), curve],
+            white,
+        }
+    }
+
+    /// Parses an ICC profile, only RGB profiles made of a matrix and transfer curves are supported
+    pub fn from_icc(data: &[u8]) -> Option<Self> {
+        if data.get(36..40)? != b"acsp"
+            || data.get(16..20)? != b"RGB "
+            || data.get(20..24)? != b"XYZ "
+        {
+            return None;
+        }
+        let tag = |signature: &[u8]| {
+            (0..read_u32(data, 128)? as usize)
+                .map(|i| 132 + 12 * i)
+                .find(|entry| data.get(*entry..entry + 4) == Some(signature))
+                .and_then(|entry| {
+                    let offset = read_u32(data, entry + 4)? as usize;
+                    let size = read_u32(data, entry + 8)? as usize;
+                    data.get(offset..offset.checked_add(size)?)
+                })
+        };
+        let xyz = |signature: &[u8]| {
+            let tag = tag(signature)?;
+            if tag.get(0..4)? != b"XYZ " {
+                return None;
+            }
+            Some([
+                read_s15_16(tag, 8)?,
+                read_s15_16(tag, 12)?,
+                read_s15_16(tag, 16)?,
+            ])
+        };
+        let [r, g, b] = [xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?];
+        let curve = |signature: &[u8]| Curve::parse(tag(signature)?);
+        Some(ColorProfile {
+            to_xyz: [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]],
+            curves: [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?],
+            white: D50,
+        })
+    }
+
+    /// Writes the profile as a version 4 ICC display profile
+    pub fn to_icc(&self, description: &str) -> Vec<u8> {
+        let mut text = vec![];
+        for unit in description.encode_utf16() {
+            text.extend_from_slice(&unit.to_be_bytes());
+        }
+        let mluc = |text: &[u8]| {
+            let mut tag = b"mluc\0\0\0\0".to_vec();
+            for v in [1, 12] {
+                tag.extend_from_slice(&(v as u32).to_be_bytes());
+            }
+            tag.extend_from_slice(b"enUS");
+            tag.extend_from_slice(&(text.len() as u32).to_be_bytes());
+            tag.extend_from_slice(&28u32.to_be_bytes());
+            tag.extend_from_slice(text);
+            tag
+        };
+        let xyz = |v: [f64; 3]| {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            v.iter().for_each(|c| tag.extend_from_slice(&s15_16(*c)));
+            tag
+        };
+        let column = |i: usize| xyz(self.to_xyz.map(|row| row[i]));
+        let curve = |i: usize| {
+            let mut tag = vec![];
+            self.curves[i].write(&mut tag);
+            tag
+        };
+        let mut chad = b"sf32\0\0\0\0".to_vec();
+        for row in adaptation(self.white) {
+            row.iter().for_each(|c| chad.extend_from_slice(&s15_16(*c)));
+        }
+        let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+            (b"desc", mluc(&text)),
+            (b"cprt", mluc(&[0, b'C', 0, b'C', 0, b'0'])),
+            (b"wtpt", xyz(D50)),
+            (b"chad", chad),
+            (b"rXYZ", column(0)),
+            (b"gXYZ", column(1)),
+            (b"bXYZ", column(2)),
+            (b"rTRC", curve(0)),
+            (b"gTRC", curve(1)),
+            (b"bTRC", curve(2)),
+        ];
+
+        let mut header = vec![0; 128];
+        header[8..12].copy_from_slice(&[4, 0x30, 0, 0]);
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(b"RGB ");
+        header[20..24].copy_from_slice(b"XYZ ");
+        // 2023-01-01 00:00:00
+        for (i, v) in [2023u16, 1, 1].iter().enumerate() {
+            header[24 + 2 * i..26 + 2 * i].copy_from_slice(&v.to_be_bytes());
+        }
+        header[36..40].copy_from_slice(b"acsp");
+        for (i, c) in D50.iter().enumerate() {
+            header[68 + 4 * i..72 + 4 * i].copy_from_slice(&s15_16(*c));
+        }
+
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = vec![];
+        let start = header.len() + 4 + 12 * tags.len();
+        for (signature, tag) in &tags {
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&((start + data.len()) as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(tag);
+            data.resize(data.len().div_ceil(4) * 4, 0);
+        }
+        let mut profile = header;
+        profile.extend(table);
+        profile.extend(data);
+        let size = (profile.len() as u32).to_be_bytes();
+        profile[0..4].copy_from_slice(&size);
+        profile
+    }
+
+    /// Whether both profiles describe the same colors, up to the precision of ICC files
+    fn matches(&self, other: &ColorProfile) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+        let same_curves = self.curves.iter().zip(&other.curves).all(|(a, b)| {
+            (0..=16).all(|i| close(a.decode(i as f64 / 16.0), b.decode(i as f64 / 16.0)))
+        });
+        let same_matrix =
+            (0..9).all(|i| close(self.to_xyz[i / 3][i % 3], other.to_xyz[i / 3][i % 3]));
+        same_curves && same_matrix
+    }
+
+    /// Converts the colors of the image from this profile into the given one, alpha is kept.
+    /// Colors outside of the target gamut are clipped unless the image has floating point channels.
+    pub fn convert(&self, img: &mut Image, into: &ColorProfile) {
+        if self.matches(into) {
+            return;
+        }
+        let matrix = multiply(&invert(&into.to_xyz), &self.to_xyz);
+        for (x, y) in img.points() {
+            let mut pixel = img.get(x, y);
+            let linear = [0, 1, 2].map(|i| self.curves[i].decode(pixel[i]));
+            let converted = apply(&matrix, linear);
+            for (i, c) in pixel.iter_mut().take(3).enumerate() {
+                *c = into.curves[i].encode(converted[i]);
+            }
+            img.set(x, y, pixel);
+        }
+    }
+}
+
+/// The profile the encoded image is tagged with, `None` for untagged files and unsupported profiles
+pub(crate) fn embedded_profile(buffer: &[u8]) -> Option<ColorProfile> {
+    let icc = match image::guess_format(buffer).ok()? {
+        ImageFormat::Png => {
+            if let Some(chunk) = png_chunk(buffer, b"iCCP") {
+                // profile name, compression method and the compressed profile
+                let start = chunk.iter().position(|c| *c == 0)? + 2;
+                decompress_to_vec_zlib(chunk.get(start..)?).ok()?
+            } else if png_chunk(buffer, b"sRGB").is_some() {
+                return Some(WorkingSpace::Srgb.profile());
+            } else {
+                let gamma = read_u32(png_chunk(buffer, b"gAMA")?, 0).filter(|g| *g > 0)?;
+                let gamma = gamma as f64 / 100000.0;
+                let [white, r, g, b] = match png_chunk(buffer, b"cHRM") {
+                    Some(chunk) => [0, 1, 2, 3].map(|i| {
+                        let value = |at| read_u32(chunk, at).unwrap_or(0) as f64 / 100000.0;
+                        (value(8 * i), value(8 * i + 4))
+                    }),
+                    None => [D65, (0.64, 0.33), (0.30, 0.60), (0.15, 0.06)],
+                };
+                return Some(ColorProfile::from_primaries(
+                    white,
+                    [r, g, b],
+                    Curve::gamma(1.0 / gamma),
+                ));
+            }
+        }
+        ImageFormat::Jpeg => {
+            let mut decoder = jpeg_decoder::Decoder::new(buffer);
+            decoder.read_info().ok()?;
+            decoder.icc_profile()?
+        }
+        ImageFormat::Tiff => tiff::decoder::Decoder::new(Cursor::new(buffer))
+            .ok()?
+            .find_tag_unsigned_vec(Tag::Unknown(ICC_TAG))
+            .ok()??,
+        _ => return None,
+    };
+    let profile = ColorProfile::from_icc(&icc);
+    if profile.is_none() {
+        log::warn!("Ignoring unsupported color profile");
+    }
+    profile
+}
+
+/// Data of the first chunk of the given kind before the image data of a PNG file
+fn png_chunk<'a>(buffer: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut at = 8;
+    loop {
+        let length = read_u32(buffer, at)? as usize;
+        let data = buffer.get(at + 8..(at + 8).checked_add(length)?)?;
+        match buffer.get(at + 4..at + 8)? {
+            b"IDAT" => return None,
+            found if found == kind => return Some(data),
+            _ => at += length + 12,
+        }
+    }
+}
+
+/// Bradford adaptation from the given white to D50
+fn adaptation(white: [f64; 3]) -> Matrix {
+    let bradford = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let (source, target) = (apply(&bradford, white), apply(&bradford, D50));
+    let scale = [0, 1, 2].map(|i| bradford[i].map(|c| c * target[i] / source[i]));
+    multiply(&invert(&bradford), &scale)
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    a.map(|row| [0, 1, 2].map(|j| row[0] * b[0][j] + row[1] * b[1][j] + row[2] * b[2][j]))
+}
+
+fn invert(m: &Matrix) -> Matrix {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *m;
+    let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+    [
+        [e * i - f * h, c * h - b * i, b * f - c * e],
+        [f * g - d * i, a * i - c * g, c * d - a * f],
+        [d * h - e * g, b * g - a * h, a * e - b * d],
+    ]
+    .map(|row| row.map(|v| v / det))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_s15_16(data: &[u8], at: usize) -> Option<f64> {
+    let raw = i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?);
+    Some(raw as f64 / 65536.0)
+}
+
+fn s15_16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use crate::Image;
+
+    use super::{embedded_profile, ColorProfile, WorkingSpace};
+
+    #[test]
+    fn icc_round_trip() {
+        for space in [
+            WorkingSpace::Srgb,
+            WorkingSpace::DisplayP3,
+            WorkingSpace::AdobeRgb,
+            WorkingSpace::Rec2020,
+        ] {
+            let parsed = ColorProfile::from_icc(&space.icc()).unwrap();
+            assert!(parsed.matches(&space.profile()));
+        }
+        assert!(!WorkingSpace::Srgb
+            .profile()
+            .matches(&WorkingSpace::DisplayP3.profile()));
+    }
+
+    #[test]
+    fn convert_between_spaces() {
+        let mut img = Image::new_from_color(1, 1, &Color::RED);
+        let (srgb, p3) = (
+            WorkingSpace::Srgb.profile(),
+            WorkingSpace::DisplayP3.profile(),
+        );
+        srgb.convert(&mut img, &p3);
+        // the red of sRGB lies within the gamut of Display P3
+        let red = img.pixel(0, 0);
+        assert!(red.r < 240 && red.g > 40 && red.b > 20);
+        assert_eq!(red.a, 255);
+        p3.convert(&mut img, &srgb);
+        let back = img.pixel(0, 0);
+        assert!(back.r >= 254 && back.g <= 1 && back.b <= 1);
+    }
+
+    #[test]
+    fn import_and_export_keep_colors() {
+        let gray = Image::new_from_color(
+            1,
+            1,
+            &Color {
+                r: 32,
+                g: 32,
+                b: 32,
+                a: 255,
+            },
+        );
+        let bytes = gray.into_png_bytes_in(WorkingSpace::AdobeRgb);
+        assert!(embedded_profile(&bytes)
+            .unwrap()
+            .matches(&WorkingSpace::AdobeRgb.profile()));
+        let same = Image::from_bytes_in(&bytes, WorkingSpace::AdobeRgb).unwrap();
+        assert_eq!(same, gray);
+        // the same dark gray takes a smaller value in sRGB
+        let srgb = Image::from_bytes(&bytes).unwrap();
+        assert!(srgb.pixel(0, 0).r < 30);
+
+        let tiff = gray.into_tiff_bytes_in(WorkingSpace::Rec2020);
+        assert!(embedded_profile(&tiff)
+            .unwrap()
+            .matches(&WorkingSpace::Rec2020.profile()));
+        assert!(embedded_profile(&gray.into_png_bytes())
+            .unwrap()
+            .matches(&WorkingSpace::Srgb.profile()));
+    }
+}
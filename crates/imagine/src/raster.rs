@@ -0,0 +1,277 @@
+use crate::{image::with_alpha, Image, Mask};
+use common::{Color, FillRule, Path, Point, Position, Rectangle, Size, StrokeStyle, TOLERANCE};
+
+/// Sub-scanlines per pixel row used for anti-aliasing
+const SAMPLES: usize = 16;
+
+/// Edge of a polygon going from the upper to the lower point
+struct Edge {
+    x_top: f64,
+    y_top: f64,
+    y_bottom: f64,
+    slope: f64,
+    winding: i32,
+}
+
+impl Image {
+    /// Fills the area enclosed by the path (in image coordinates) according to the fill rule.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn fill_path(
+        &mut self,
+        path: &Path,
+        rule: FillRule,
+        color: &Color,
+        antialias: bool,
+    ) -> Rectangle {
+        let contours: Vec<Vec<Point>> = path
+            .flatten(TOLERANCE)
+            .into_iter()
+            .map(|polyline| polyline.points)
+            .collect();
+        self.fill_polygons(&contours, rule, color, antialias)
+    }
+
+    /// Draws the stroke along the path (in image coordinates).
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn stroke_path(
+        &mut self,
+        path: &Path,
+        style: &StrokeStyle,
+        color: &Color,
+        antialias: bool,
+    ) -> Rectangle {
+        self.fill_polygons(&path.stroke(style), FillRule::NonZero, color, antialias)
+    }
+
+    /// Fills the area enclosed by the given contours with the color according to the fill rule.
+    /// Contours are closed implicitly and given in image coordinates, where pixel `(x, y)` covers
+    /// the area from `(x, y)` to `(x + 1, y + 1)`.
+    ///
+    /// Without anti-aliasing a pixel is covered if its center is inside the area.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn fill_polygons(
+        &mut self,
+        contours: &[Vec<Point>],
+        rule: FillRule,
+        color: &Color,
+        antialias: bool,
+    ) -> Rectangle {
+        rasterize(contours, rule, antialias, self.size(), |x, y, coverage| {
+            let alpha = color.a as f64 / 255.0 * coverage;
+            self.draw_pixel(x, y, with_alpha(color, alpha));
+        })
+    }
+}
+
+impl Mask {
+    /// Covers the area enclosed by the given contours according to the fill rule,
+    /// see [Image::fill_polygons].
+    ///
+    /// Returns the covered area.
+    pub fn fill_polygons(
+        &mut self,
+        contours: &[Vec<Point>],
+        rule: FillRule,
+        antialias: bool,
+    ) -> Rectangle {
+        rasterize(contours, rule, antialias, self.size(), |x, y, coverage| {
+            let value = (coverage * 255.0).round() as u8;
+            if value > self.get(&Position::new(x as i32, y as i32)) {
+                self.set(x, y, value);
+            }
+        })
+    }
+}
+
+/// Calls `cover` with the coverage from 0 to 1 of every pixel within the size that is touched by the area
+/// enclosed by the contours.
+///
+/// Returns the touched area.
+fn rasterize(
+    contours: &[Vec<Point>],
+    rule: FillRule,
+    antialias: bool,
+    size: Size,
+    mut cover: impl FnMut(u32, u32, f64),
+) -> Rectangle {
+    let edges: Vec<Edge> = contours
+        .iter()
+        .flat_map(|contour| {
+            contour
+                .iter()
+                .zip(contour.iter().cycle().skip(1))
+                .filter_map(|(a, b)| Edge::new(a, b))
+        })
+        .collect();
+    let Some(area) = bounds(contours, size) else {
+        return Rectangle::new(0, 0, 0, 0);
+    };
+    let (x0, width) = (area.position.x, area.size.width as usize);
+    let samples = if antialias { SAMPLES } else { 1 };
+    let mut coverage = vec![0.0; width];
+    for y in area.position.y..area.position.y + area.size.height as i32 {
+        coverage.iter_mut().for_each(|c| *c = 0.0);
+        for sample in 0..samples {
+            let sample_y = y as f64 + (sample as f64 + 0.5) / samples as f64;
+            for (start, end) in spans(&edges, rule, sample_y) {
+                let (start, end) = (start - x0 as f64, end - x0 as f64);
+                if antialias {
+                    add_span(&mut coverage, start, end, 1.0 / samples as f64);
+                } else {
+                    // pixel centers within the span
+                    let first = (start - 0.5).ceil().max(0.0) as usize;
+                    let last = ((end - 0.5).ceil().max(0.0) as usize).min(width);
+                    (first..last).for_each(|x| coverage[x] = 1.0);
+                }
+            }
+        }
+        for (x, c) in coverage.iter().enumerate() {
+            if *c > 0.0 {
+                cover((x0 + x as i32) as u32, y as u32, c.min(1.0));
+            }
+        }
+    }
+    area
+}
+
+/// Pixels touched by the contours, constrained to the given size
+fn bounds(contours: &[Vec<Point>], size: Size) -> Option<Rectangle> {
+    let area = Rectangle::covering(contours.iter().flatten().copied());
+    let area = Rectangle::intersect(&area, &Rectangle::of((0, 0).into(), size));
+    if area.size.width == 0 || area.size.height == 0 {
+        return None;
+    }
+    Some(area)
+}
+
+impl Edge {
+    /// Returns `None` for horizontal edges as they never cross a scanline
+    fn new(a: &Point, b: &Point) -> Option<Self> {
+        if a.y == b.y {
+            return None;
+        }
+        let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+        Some(Edge {
+            x_top: top.x,
+            y_top: top.y,
+            y_bottom: bottom.y,
+            slope: (bottom.x - top.x) / (bottom.y - top.y),
+            winding,
+        })
+    }
+}
+
+/// Horizontal spans inside the area at the scanline `y` according to the fill rule
+fn spans(edges: &[Edge], rule: FillRule, y: f64) -> Vec<(f64, f64)> {
+    let mut crossings: Vec<(f64, i32)> = edges
+        .iter()
+        .filter(|e| e.y_top <= y && y < e.y_bottom)
+        .map(|e| (e.x_top + (y - e.y_top) * e.slope, e.winding))
+        .collect();
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut result = vec![];
+    let mut winding = 0;
+    let mut start = 0.0;
+    for (x, w) in crossings {
+        let was_inside = rule.is_inside(winding);
+        winding += w;
+        match (was_inside, rule.is_inside(winding)) {
+            (false, true) => start = x,
+            (true, false) => result.push((start, x)),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Adds the exact horizontal coverage of the span `start..end` to each pixel, scaled by `weight`
+fn add_span(coverage: &mut [f64], start: f64, end: f64, weight: f64) {
+    let start = start.max(0.0);
+    let end = end.min(coverage.len() as f64);
+    if start >= end {
+        return;
+    }
+    let (first, last) = (start.floor() as usize, end.ceil() as usize);
+    for (x, c) in coverage.iter_mut().enumerate().take(last).skip(first) {
+        let covered = end.min(x as f64 + 1.0) - start.max(x as f64);
+        *c += covered * weight;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, FillRule, Path, Point, Rectangle};
+
+    use crate::Image;
+
+    fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<Point> {
+        vec![
+            Point::new(x0, y0),
+            Point::new(x1, y0),
+            Point::new(x1, y1),
+            Point::new(x0, y1),
+        ]
+    }
+
+    #[test]
+    fn fill_square() {
+        let mut img = Image::new(10, 10);
+        let damage = img.fill_polygons(
+            &[square(2.0, 2.0, 5.0, 5.0)],
+            FillRule::NonZero,
+            &Color::RED,
+            true,
+        );
+        assert_eq!(damage, Rectangle::new(2, 2, 3, 3));
+        assert_eq!(img.pixel(2, 2), Color::RED);
+        assert_eq!(img.pixel(4, 4), Color::RED);
+        assert_eq!(img.pixel(5, 5), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn antialiased_edge() {
+        let mut img = Image::new(10, 10);
+        img.fill_polygons(
+            &[square(2.5, 2.0, 5.0, 5.0)],
+            FillRule::NonZero,
+            &Color::RED,
+            true,
+        );
+        assert_eq!(img.pixel(2, 3).a, 128);
+        img = Image::new(10, 10);
+        img.fill_polygons(
+            &[square(2.6, 2.0, 5.0, 5.0)],
+            FillRule::NonZero,
+            &Color::RED,
+            false,
+        );
+        assert_eq!(img.pixel(2, 3), Color::TRANSPARENT);
+        assert_eq!(img.pixel(3, 3), Color::RED);
+    }
+
+    #[test]
+    fn nonzero_winding() {
+        let mut img = Image::new(10, 10);
+        let mut hole = square(3.0, 3.0, 6.0, 6.0);
+        hole.reverse();
+        let overlapping = square(0.0, 0.0, 2.0, 2.0);
+        let contours = [square(0.0, 0.0, 9.0, 9.0), hole, overlapping];
+        img.fill_polygons(&contours, FillRule::NonZero, &Color::RED, false);
+        assert_eq!(img.pixel(1, 1), Color::RED);
+        assert_eq!(img.pixel(4, 4), Color::TRANSPARENT);
+        assert_eq!(img.pixel(7, 7), Color::RED);
+    }
+
+    #[test]
+    fn even_odd() {
+        let mut img = Image::new(10, 10);
+        let outer = Path::rectangle(Point::new(0.0, 0.0), Point::new(9.0, 9.0));
+        let inner = Path::rectangle(Point::new(3.0, 3.0), Point::new(6.0, 6.0));
+        img.fill_path(&outer.append(inner), FillRule::EvenOdd, &Color::RED, false);
+        assert_eq!(img.pixel(1, 1), Color::RED);
+        assert_eq!(img.pixel(4, 4), Color::TRANSPARENT);
+    }
+}
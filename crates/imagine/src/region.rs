@@ -0,0 +1,190 @@
+use common::{Color, Position, Rectangle};
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+
+use crate::{image::with_alpha, Image, Mask};
+
+/// How the difference of two colors is measured, both range from 0 to 255
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum ColorDistance {
+    /// Largest difference of a single channel
+    #[default]
+    #[serde(rename = "rgb")]
+    Rgb,
+
+    /// Difference in the CIELAB color space (CIE76), close to how different the colors are perceived
+    #[serde(rename = "perceptual")]
+    Perceptual,
+}
+
+impl ColorDistance {
+    /// The difference of the colors is only as relevant as the less opaque of them,
+    /// so all fully transparent pixels are equal.
+    pub fn between(&self, a: &Rgba<u8>, b: &Rgba<u8>) -> f64 {
+        self.between_channels(a.0.map(|c| c as f64), b.0.map(|c| c as f64))
+    }
+
+    /// Difference of two pixels with channels from 0 to 255, as for [ColorDistance::between]
+    fn between_channels(&self, a: [f64; 4], b: [f64; 4]) -> f64 {
+        let visibility = a[3].min(b[3]) / 255.0;
+        let alpha = (a[3] - b[3]).abs();
+        let color = match self {
+            ColorDistance::Rgb => (0..3).map(|i| (a[i] - b[i]).abs()).fold(0.0, f64::max),
+            ColorDistance::Perceptual => {
+                let (a, b) = (lab(a), lab(b));
+                // Lab distances are in 0..=100 for the lightness
+                2.55 * (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>().sqrt()
+            }
+        };
+        (color * visibility).max(alpha)
+    }
+}
+
+/// Converts an sRGB color with channels from 0 to 255 into CIELAB (D65 white point)
+fn lab(pixel: [f64; 4]) -> [f64; 3] {
+    let linear = |c: f64| {
+        let c = c / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+impl Image {
+    /// Pixels whose color differs at most by `tolerance` from the color at the seed (in image coordinates).
+    /// If `contiguous` is set only pixels connected to the seed through such pixels are part of the region.
+    ///
+    /// The region is empty if the seed lies outside of the image.
+    pub fn similar_region(
+        &self,
+        seed: &Position,
+        tolerance: f64,
+        distance: ColorDistance,
+        contiguous: bool,
+    ) -> Mask {
+        let mut mask = Mask::new(self.width(), self.height());
+        if !self.contains(seed) {
+            return mask;
+        }
+        let reference = self.channels(seed.x as u32, seed.y as u32);
+        if !contiguous {
+            return self.range_of(reference, tolerance, distance);
+        }
+        let similar =
+            |x: u32, y: u32| distance.between_channels(reference, self.channels(x, y)) <= tolerance;
+        let mut stack = vec![*seed];
+        mask.set(seed.x as u32, seed.y as u32, 255);
+        while let Some(pos) = stack.pop() {
+            for d in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let next = pos + d.into();
+                if self.contains(&next)
+                    && mask.get(&next) == 0
+                    && similar(next.x as u32, next.y as u32)
+                {
+                    mask.set(next.x as u32, next.y as u32, 255);
+                    stack.push(next);
+                }
+            }
+        }
+        mask
+    }
+
+    /// All pixels whose color differs at most by `tolerance` from the given color
+    pub fn color_range(&self, color: &Color, tolerance: f64, distance: ColorDistance) -> Mask {
+        let reference = [color.r, color.g, color.b, color.a].map(|c| c as f64);
+        self.range_of(reference, tolerance, distance)
+    }
+
+    fn range_of(&self, reference: [f64; 4], tolerance: f64, distance: ColorDistance) -> Mask {
+        let mut mask = Mask::new(self.width(), self.height());
+        for (x, y) in self.points() {
+            if distance.between_channels(reference, self.channels(x, y)) <= tolerance {
+                mask.set(x, y, 255);
+            }
+        }
+        mask
+    }
+
+    /// Channels of the pixel from 0 to 255, with the precision of the image
+    fn channels(&self, x: u32, y: u32) -> [f64; 4] {
+        self.get(x, y).map(|c| c * 255.0)
+    }
+
+    /// Draws the color onto the image wherever the mask covers it.
+    /// The mask value for the image coordinate `p` is taken from `p + offset` in mask coordinates.
+    ///
+    /// Returns damaged area in image coordinates.
+    pub fn fill_mask(&mut self, mask: &Mask, offset: Position, color: &Color) -> Rectangle {
+        let area = &mask.bounds() - &offset;
+        let area = Rectangle::intersect(&area, &Rectangle::of((0, 0).into(), self.size()));
+        for p in area.points() {
+            let coverage = mask.get(&(p + offset));
+            if coverage == 0 {
+                continue;
+            }
+            let alpha = (color.a as u32 * coverage as u32 / 255) as f64 / 255.0;
+            self.draw_pixel(p.x as u32, p.y as u32, with_alpha(color, alpha));
+        }
+        area
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Position};
+    use image::Rgba;
+
+    use super::ColorDistance;
+    use crate::Image;
+
+    #[test]
+    fn transparent_pixels_are_equal() {
+        let (a, b) = (Rgba([255, 0, 0, 0]), Rgba([0, 0, 255, 0]));
+        assert_eq!(ColorDistance::Rgb.between(&a, &b), 0.0);
+        assert_eq!(ColorDistance::Perceptual.between(&a, &b), 0.0);
+        let c = Rgba([0, 0, 0, 255]);
+        let d = Rgba([255, 255, 255, 255]);
+        assert!((ColorDistance::Perceptual.between(&c, &d) - 255.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn contiguous_region() {
+        // left and right halves are separated by a red column
+        let mut img = Image::new(9, 5);
+        for y in 0..5 {
+            img.put_pixel(4, y, Color::RED);
+        }
+        let seed = Position::new(1, 1);
+        let region = img.similar_region(&seed, 10.0, ColorDistance::Rgb, true);
+        assert_eq!(region.bounds(), (0, 0, 4, 5).into());
+        let region = img.similar_region(&seed, 10.0, ColorDistance::Rgb, false);
+        assert_eq!(region.get(&Position::new(8, 4)), 255);
+        assert_eq!(region.get(&Position::new(4, 4)), 0);
+    }
+
+    #[test]
+    fn color_range() {
+        let mut img = Image::new(3, 1);
+        img.put_pixel(1, 0, Color::RED);
+        img.put_pixel(2, 0, Color::RED.with_alpha(200));
+        let range = img.color_range(&Color::RED, 60.0, ColorDistance::Rgb);
+        assert_eq!(range.bounds(), (1, 0, 2, 1).into());
+        let range = img.color_range(&Color::RED, 50.0, ColorDistance::Rgb);
+        assert_eq!(range.bounds(), (1, 0, 1, 1).into());
+    }
+}
@@ -0,0 +1,170 @@
+use common::Color;
+use rusttype::{point, Scale};
+use serde::{Deserialize, Serialize};
+
+use crate::{image::with_alpha, Image};
+
+/// DejaVu Sans, see `res/fonts/LICENSE`
+const BUNDLED: &[u8] = include_bytes!("../res/fonts/DejaVuSans.ttf");
+
+/// A TrueType or OpenType font
+#[derive(Clone, Debug)]
+pub struct Font(rusttype::Font<'static>);
+
+impl Font {
+    /// The font that ships with the application
+    pub fn bundled() -> Self {
+        Font(rusttype::Font::try_from_bytes(BUNDLED).expect("Bundled font is valid"))
+    }
+
+    /// Parses the font file, `None` if it isn't a valid font
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        rusttype::Font::try_from_vec(bytes).map(Font)
+    }
+}
+
+/// Horizontal alignment of the lines of a text
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum TextAlign {
+    #[default]
+    #[serde(rename = "left")]
+    Left,
+
+    #[serde(rename = "center")]
+    Center,
+
+    #[serde(rename = "right")]
+    Right,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct TextStyle {
+    /// Height of the font in pixels
+    pub size: f32,
+    pub color: Color,
+    #[serde(default)]
+    pub align: TextAlign,
+    /// Distance between the baselines of consecutive lines relative to the natural line height of the font
+    #[serde(default = "TextStyle::default_line_spacing")]
+    pub line_spacing: f32,
+}
+
+impl TextStyle {
+    fn default_line_spacing() -> f32 {
+        1.0
+    }
+}
+
+impl Image {
+    /// Renders the text into an image that is just large enough to hold all of its lines.
+    /// Lines are separated by `\n` and aligned within the width of the longest line.
+    pub fn from_text(text: &str, font: &Font, style: &TextStyle) -> Image {
+        let font = &font.0;
+        let scale = Scale::uniform(style.size.max(0.0));
+        let metrics = font.v_metrics(scale);
+        let line_height =
+            (metrics.ascent - metrics.descent + metrics.line_gap) * style.line_spacing;
+
+        let lines: Vec<&str> = text.split('\n').collect();
+        let widths: Vec<f32> = lines
+            .iter()
+            .map(|line| {
+                font.layout(line, scale, point(0.0, 0.0))
+                    .last()
+                    .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+                    .unwrap_or(0.0)
+            })
+            .collect();
+        let width = widths.iter().copied().fold(0.0, f32::max);
+        let height = line_height * (lines.len() - 1) as f32 + metrics.ascent - metrics.descent;
+        let mut result = Image::new((width.ceil() as u32).max(1), (height.ceil() as u32).max(1));
+
+        let factor = match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => 0.5,
+            TextAlign::Right => 1.0,
+        };
+        for (i, (line, line_width)) in lines.iter().zip(widths).enumerate() {
+            let origin = point(
+                (width - line_width) * factor,
+                metrics.ascent + line_height * i as f32,
+            );
+            for glyph in font.layout(line, scale, origin) {
+                let Some(bounds) = glyph.pixel_bounding_box() else {
+                    continue;
+                };
+                glyph.draw(|x, y, coverage| {
+                    let (x, y) = (x as i32 + bounds.min.x, y as i32 + bounds.min.y);
+                    if !result.contains(&(x, y).into()) {
+                        return;
+                    }
+                    let alpha = style.color.a as f64 / 255.0 * coverage as f64;
+                    result.draw_pixel(x as u32, y as u32, with_alpha(&style.color, alpha));
+                });
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::Color;
+
+    use super::{Font, TextAlign, TextStyle};
+    use crate::Image;
+
+    fn style(align: TextAlign) -> TextStyle {
+        TextStyle {
+            size: 20.0,
+            color: Color::RED,
+            align,
+            line_spacing: 1.0,
+        }
+    }
+
+    /// Horizontal range of covered pixels in the given rows
+    fn covered(img: &Image, rows: std::ops::Range<u32>) -> (u32, u32) {
+        let xs: Vec<u32> = rows
+            .flat_map(|y| (0..img.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| img.pixel(*x, *y).a > 0)
+            .map(|(x, _)| x)
+            .collect();
+        (*xs.iter().min().unwrap(), *xs.iter().max().unwrap())
+    }
+
+    #[test]
+    fn multiple_lines() {
+        let font = Font::bundled();
+        let single = Image::from_text("Hello", &font, &style(TextAlign::Left));
+        let double = Image::from_text("Hello\nHello", &font, &style(TextAlign::Left));
+        assert_eq!(single.width(), double.width());
+        assert!(double.height() > single.height() * 3 / 2);
+        let mut spaced = style(TextAlign::Left);
+        spaced.line_spacing = 2.0;
+        let spaced = Image::from_text("Hello\nHello", &font, &spaced);
+        assert!(spaced.height() > double.height());
+        assert!((0..single.width()).any(|x| single.pixel(x, 10) == Color::RED));
+    }
+
+    #[test]
+    fn alignment() {
+        let font = Font::bundled();
+        let text = "Wide line\nab";
+        let left = Image::from_text(text, &font, &style(TextAlign::Left));
+        let right = Image::from_text(text, &font, &style(TextAlign::Right));
+        let center = Image::from_text(text, &font, &style(TextAlign::Center));
+        let second_line = 25..left.height();
+        let (l, _) = covered(&left, second_line.clone());
+        let (_, r) = covered(&right, second_line.clone());
+        let (cl, cr) = covered(&center, second_line);
+        assert!(l < 3);
+        assert!(r > right.width() - 4);
+        assert!(cl > l + 20 && cr + 20 < r);
+    }
+
+    #[test]
+    fn invalid_font() {
+        assert!(Font::from_bytes(vec![1, 2, 3]).is_none());
+    }
+}
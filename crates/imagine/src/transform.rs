@@ -0,0 +1,441 @@
+use std::f64::consts::PI;
+
+use common::{Point, Position, Rectangle};
+use image::Rgba32FImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{Image, Mask};
+
+/// Longest side of a transformed image
+const MAX_SIZE: u32 = 16384;
+
+/// Upper bound of the source pixels a filter widens to when shrinking
+const MAX_FOOTPRINT: f64 = 16.0;
+
+/// How the pixels of a transformed image are taken from the original
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum Resampling {
+    /// The pixel that lands on the spot, keeps hard edges
+    #[serde(rename = "nearest")]
+    Nearest,
+
+    #[default]
+    #[serde(rename = "bilinear")]
+    Bilinear,
+
+    /// Catmull-Rom spline, sharper than bilinear
+    #[serde(rename = "bicubic")]
+    Bicubic,
+
+    /// Windowed sinc with three lobes, the sharpest but slowest
+    #[serde(rename = "lanczos")]
+    Lanczos,
+}
+
+impl Resampling {
+    /// Distance in source pixels at which the filter drops to zero
+    fn support(&self) -> f64 {
+        match self {
+            Resampling::Nearest => 0.5,
+            Resampling::Bilinear => 1.0,
+            Resampling::Bicubic => 2.0,
+            Resampling::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Resampling::Nearest if x < 0.5 => 1.0,
+            Resampling::Nearest => 0.0,
+            Resampling::Bilinear => (1.0 - x).max(0.0),
+            Resampling::Bicubic if x < 1.0 => (1.5 * x - 2.5) * x * x + 1.0,
+            Resampling::Bicubic if x < 2.0 => ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0,
+            Resampling::Bicubic => 0.0,
+            Resampling::Lanczos if x < 1e-8 => 1.0,
+            Resampling::Lanczos if x < 3.0 => {
+                3.0 * (PI * x).sin() * (PI * x / 3.0).sin() / (PI * PI * x * x)
+            }
+            Resampling::Lanczos => 0.0,
+        }
+    }
+}
+
+/// Projective transformation of the plane, a 3x3 matrix in row-major order
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Projection {
+    m: [f64; 9],
+}
+
+impl Projection {
+    pub fn identity() -> Self {
+        Projection {
+            m: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Projection {
+            m: [1.0, 0.0, x, 0.0, 1.0, y, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Projection {
+            m: [x, 0.0, 0.0, 0.0, y, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Rotation around the origin by the angle in degrees, clockwise as the y-axis points down
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Projection {
+            m: [cos, -sin, 0.0, sin, cos, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Shear by the angles in degrees, `x` moves points to the right the further down they are
+    /// and `y` moves them down the further right they are
+    pub fn skew(x: f64, y: f64) -> Self {
+        let (x, y) = (x.to_radians().tan(), y.to_radians().tan());
+        Projection {
+            m: [1.0, x, 0.0, y, 1.0, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Maps the corners of the rectangle onto the corners of the quadrilateral,
+    /// clockwise from the top left. None if the quadrilateral isn't convex.
+    pub fn quad(from: &Rectangle, to: [Point; 4]) -> Option<Self> {
+        let turns: Vec<f64> = (0..4)
+            .map(|i| {
+                let (a, b, c) = (to[i], to[(i + 1) % 4], to[(i + 2) % 4]);
+                (b - a).cross(&(c - b))
+            })
+            .collect();
+        if !(turns.iter().all(|t| *t > 0.0) || turns.iter().all(|t| *t < 0.0)) {
+            return None;
+        }
+        // unit square onto the quadrilateral, as described by Heckbert
+        let [p0, p1, p2, p3] = to;
+        let (d1, d2, d3) = (p1 - p2, p3 - p2, p0 - p1 + p2 - p3);
+        let det = d1.cross(&d2);
+        let g = d3.cross(&d2) / det;
+        let h = d1.cross(&d3) / det;
+        let square = Projection {
+            m: [
+                p1.x - p0.x + g * p1.x,
+                p3.x - p0.x + h * p3.x,
+                p0.x,
+                p1.y - p0.y + g * p1.y,
+                p3.y - p0.y + h * p3.y,
+                p0.y,
+                g,
+                h,
+                1.0,
+            ],
+        };
+        let (x, y) = (from.position.x as f64, from.position.y as f64);
+        let (w, h) = (from.size.width as f64, from.size.height as f64);
+        Some(
+            Projection::translate(-x, -y)
+                .then(&Projection::scale(1.0 / w, 1.0 / h))
+                .then(&square),
+        )
+    }
+
+    /// This projection followed by the other one
+    pub fn then(&self, other: &Projection) -> Self {
+        let (a, b) = (&other.m, &self.m);
+        let mut m = [0.0; 9];
+        for (i, value) in m.iter_mut().enumerate() {
+            let (row, column) = (i / 3, i % 3);
+            *value = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + column]).sum();
+        }
+        Projection { m }
+    }
+
+    /// The point after the projection, None if it lies on or beyond the horizon
+    pub fn apply(&self, p: Point) -> Option<Point> {
+        let m = &self.m;
+        let w = m[6] * p.x + m[7] * p.y + m[8];
+        if w <= f64::EPSILON {
+            return None;
+        }
+        Some(Point::new(
+            (m[0] * p.x + m[1] * p.y + m[2]) / w,
+            (m[3] * p.x + m[4] * p.y + m[5]) / w,
+        ))
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let [a, b, c, d, e, f, g, h, i] = self.m;
+        let cofactors = [
+            e * i - f * h,
+            c * h - b * i,
+            b * f - c * e,
+            f * g - d * i,
+            a * i - c * g,
+            c * d - a * f,
+            d * h - e * g,
+            b * g - a * h,
+            a * e - b * d,
+        ];
+        let det = a * cofactors[0] + b * cofactors[3] + c * cofactors[6];
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some(Projection {
+            m: cofactors.map(|x| x / det),
+        })
+    }
+}
+
+impl Image {
+    /// The image after the projection from image coordinates, together with the position of its top left corner.
+    /// None if the image reaches beyond the horizon of the projection or becomes too large.
+    pub fn transformed(
+        &self,
+        projection: &Projection,
+        resampling: Resampling,
+    ) -> Option<(Image, Position)> {
+        let (w, h) = (self.width() as f64, self.height() as f64);
+        let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)]
+            .map(|(x, y)| projection.apply(Point::new(x, y)).map(snap))
+            .into_iter()
+            .collect::<Option<Vec<Point>>>()?;
+        let bounds = Rectangle::covering(corners.into_iter());
+        if bounds.size.width > MAX_SIZE || bounds.size.height > MAX_SIZE {
+            return None;
+        }
+        let inverse = projection.inverse()?;
+        let source = self.premultiplied();
+        let origin = bounds.position;
+        let mut result = Image::new_with_depth(bounds.size.width, bounds.size.height, self.depth());
+        for (x, y) in result.points() {
+            let center = Point::center_of(&(origin + Position::new(x as i32, y as i32)));
+            let Some(p) = inverse.apply(center) else {
+                continue;
+            };
+            // shrinking widens the filter to the area the pixel covers in the source
+            let footprint = match (
+                inverse.apply(center + Point::new(1.0, 0.0)),
+                inverse.apply(center + Point::new(0.0, 1.0)),
+            ) {
+                (Some(dx), Some(dy)) => (dx - p).cross(&(dy - p)).abs().sqrt(),
+                _ => 1.0,
+            };
+            let covered = match resampling {
+                Resampling::Nearest => 1.0,
+                _ => coverage(&inverse, center, (w, h)),
+            };
+            if covered == 0.0 {
+                continue;
+            }
+            let pixel = sample(&source, p, resampling, footprint.clamp(1.0, MAX_FOOTPRINT));
+            let [r, g, b, a] = pixel.map(|c| c * covered);
+            let a = a.min(1.0);
+            if a > 0.0 {
+                result.set(x, y, [r / a, g / a, b / a, a].map(|c| c.max(0.0)));
+            }
+        }
+        Some((result, origin))
+    }
+}
+
+impl Mask {
+    /// The mask after the projection like [`Image::transformed`], nothing is covered outside of the projected area
+    pub fn transformed(
+        &self,
+        projection: &Projection,
+        resampling: Resampling,
+    ) -> Option<(Mask, Position)> {
+        let (img, pos) = self.to_image().transformed(projection, resampling)?;
+        let mut mask = Mask::new(img.width(), img.height());
+        mask.set_luminance(&img, &Rectangle::of((0, 0).into(), img.size()));
+        Some((mask, pos))
+    }
+}
+
+/// Rounds coordinates that only miss a whole pixel by numerical noise
+fn snap(p: Point) -> Point {
+    let snap = |c: f64| {
+        if (c - c.round()).abs() < 1e-6 {
+            c.round()
+        } else {
+            c
+        }
+    };
+    Point::new(snap(p.x), snap(p.y))
+}
+
+/// Share of the pixel around the center that lies on the image once projected back,
+/// smooths the edges independent of the filter
+fn coverage(inverse: &Projection, center: Point, (w, h): (f64, f64)) -> f64 {
+    const STEPS: usize = 4;
+    let offsets = (0..STEPS).map(|i| (i as f64 + 0.5) / STEPS as f64 - 0.5);
+    let hits = offsets
+        .clone()
+        .flat_map(|dy| offsets.clone().map(move |dx| Point::new(dx, dy)))
+        .filter_map(|offset| inverse.apply(center + offset))
+        .filter(|p| p.x >= 0.0 && p.y >= 0.0 && p.x < w && p.y < h)
+        .count();
+    hits as f64 / (STEPS * STEPS) as f64
+}
+
+/// Filtered premultiplied pixel at the point in image coordinates.
+/// Only the pixels of the image are taken into account, edges are left to [`coverage`].
+fn sample(source: &Rgba32FImage, p: Point, resampling: Resampling, footprint: f64) -> [f64; 4] {
+    let (w, h) = (source.width() as i64, source.height() as i64);
+    if resampling == Resampling::Nearest {
+        let (x, y) = (p.x.floor() as i64, p.y.floor() as i64);
+        return if (0..w).contains(&x) && (0..h).contains(&y) {
+            source.get_pixel(x as u32, y as u32).0.map(f64::from)
+        } else {
+            [0.0; 4]
+        };
+    }
+    // the centers of the pixels lie halfway between the coordinates
+    let (cx, cy) = (p.x - 0.5, p.y - 0.5);
+    let reach = resampling.support() * footprint;
+    let taps = |c: f64| {
+        ((c - reach).ceil() as i64..=(c + reach).floor() as i64)
+            .map(|i| (i, resampling.weight((i as f64 - c) / footprint)))
+            // numerical noise of exact positions
+            .filter(|(_, weight)| weight.abs() > 1e-9)
+            .collect::<Vec<_>>()
+    };
+    let (columns, rows) = (taps(cx), taps(cy));
+    let mut sum = [0.0; 4];
+    let mut total = 0.0;
+    for &(y, wy) in rows.iter().filter(|(y, _)| (0..h).contains(y)) {
+        for &(x, wx) in columns.iter().filter(|(x, _)| (0..w).contains(x)) {
+            let weight = wx * wy;
+            total += weight;
+            let pixel = source.get_pixel(x as u32, y as u32).0;
+            for (s, c) in sum.iter_mut().zip(pixel) {
+                *s += weight * c as f64;
+            }
+        }
+    }
+    if total.abs() < f64::EPSILON {
+        // nothing of the image within reach, take the closest pixel
+        let x = (p.x.floor() as i64).clamp(0, w - 1) as u32;
+        let y = (p.y.floor() as i64).clamp(0, h - 1) as u32;
+        return source.get_pixel(x, y).0.map(f64::from);
+    }
+    sum.map(|s| s / total)
+}
+
+#[cfg(test)]
+mod test {
+    use common::{Color, Point, Position, Rectangle};
+
+    use crate::{Depth, Image};
+
+    use super::{Projection, Resampling};
+
+    fn checkers(depth: Depth) -> Image {
+        let mut img = Image::new_with_depth(4, 2, depth);
+        for (x, y) in img.points() {
+            if (x + y) % 2 == 0 {
+                img.put_pixel(x, y, Color::RED);
+            } else {
+                img.put_pixel(x, y, Color::BLACK);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn projections() {
+        let p = Projection::translate(1.0, 2.0)
+            .then(&Projection::scale(2.0, 3.0))
+            .then(&Projection::rotate(90.0));
+        let q = p.apply(Point::new(1.0, 0.0)).unwrap();
+        assert!((q.x + 6.0).abs() < 1e-9 && (q.y - 4.0).abs() < 1e-9);
+        let back = p.inverse().unwrap().apply(q).unwrap();
+        assert!((back.x - 1.0).abs() < 1e-9 && back.y.abs() < 1e-9);
+
+        let corners =
+            [(2.0, 0.0), (12.0, 1.0), (10.0, 8.0), (0.0, 5.0)].map(|(x, y)| Point::new(x, y));
+        let quad = Projection::quad(&Rectangle::new(1, 1, 4, 2), corners).unwrap();
+        for (from, to) in [(1.0, 1.0), (5.0, 1.0), (5.0, 3.0), (1.0, 3.0)]
+            .iter()
+            .zip(corners)
+        {
+            let p = quad.apply(Point::new(from.0, from.1)).unwrap();
+            assert!(p.distance_to(&to) < 1e-9);
+        }
+        // the corners cross each other
+        let [a, b, c, d] = corners;
+        assert!(Projection::quad(&Rectangle::new(0, 0, 1, 1), [a, c, b, d]).is_none());
+    }
+
+    #[test]
+    fn exact_moves_keep_the_pixels() {
+        for depth in [Depth::Eight, Depth::Float] {
+            let img = checkers(depth);
+            for resampling in [
+                Resampling::Nearest,
+                Resampling::Bilinear,
+                Resampling::Bicubic,
+                Resampling::Lanczos,
+            ] {
+                let (moved, pos) = img
+                    .transformed(&Projection::translate(3.0, -1.0), resampling)
+                    .unwrap();
+                assert_eq!(pos, Position::new(3, -1));
+                assert_eq!(moved, img);
+
+                // a quarter turn around the origin
+                let (turned, pos) = img
+                    .transformed(&Projection::rotate(90.0), resampling)
+                    .unwrap();
+                assert_eq!(pos, Position::new(-2, 0));
+                assert_eq!(turned.size(), (2, 4).into());
+                assert_eq!(turned.pixel(1, 0), img.pixel(0, 0));
+                assert_eq!(turned.pixel(0, 3), img.pixel(3, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn scaling() {
+        let img = checkers(Depth::Eight);
+        let (large, _) = img
+            .transformed(&Projection::scale(2.0, 2.0), Resampling::Nearest)
+            .unwrap();
+        assert_eq!(large.size(), (8, 4).into());
+        assert_eq!(large.pixel(1, 1), Color::RED);
+        assert_eq!(large.pixel(2, 1), Color::BLACK);
+
+        // shrinking averages instead of picking single pixels
+        for resampling in [
+            Resampling::Bilinear,
+            Resampling::Bicubic,
+            Resampling::Lanczos,
+        ] {
+            let (small, _) = img
+                .transformed(&Projection::scale(0.5, 0.5), resampling)
+                .unwrap();
+            assert_eq!(small.size(), (2, 1).into());
+            let [r, g, _, a] = small.get(0, 0);
+            assert!(r > 0.3 && r < 0.7 && g == 0.0 && a == 1.0, "{resampling:?}");
+        }
+
+        // smooth edges fade into transparency without changing the color
+        let (rotated, _) = Image::new_from_color(8, 8, &Color::RED)
+            .transformed(&Projection::rotate(30.0), Resampling::Bicubic)
+            .unwrap();
+        let edge = rotated
+            .points()
+            .map(|(x, y)| rotated.pixel(x, y))
+            .find(|p| p.a > 50 && p.a < 200)
+            .unwrap();
+        assert_eq!((edge.r, edge.g, edge.b), (255, 0, 0));
+
+        assert!(img
+            .transformed(&Projection::scale(10000.0, 1.0), Resampling::Nearest)
+            .is_none());
+    }
+}